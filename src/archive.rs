@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// A single downloaded file, as recorded in the archive manifest.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    pub product: String,
+    pub format: String,
+    /// Path of the file relative to the bundle directory.
+    pub path: String,
+    pub md5: String,
+    /// Computed locally with `download --sha256`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    pub file_size: u64,
+}
+
+/// Pack the files of `manifest` into a zip file next to `bundle_dir` and
+/// return the path of the created archive.
+///
+/// All files are stored under the bundle directory name, so extracting the
+/// archive recreates the original layout. Other files in `bundle_dir`, e.g.
+/// leftovers of earlier runs, are not included. `manifest` is written to the
+/// root of that directory inside the archive.
+pub fn create_bundle_archive(
+    bundle_dir: &Path,
+    manifest: &[ManifestEntry],
+) -> Result<PathBuf, anyhow::Error> {
+    let dir_name = bundle_dir
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("invalid bundle directory"))?
+        .to_string_lossy()
+        .to_string();
+
+    let archive_path = bundle_dir.with_file_name(format!("{}.zip", dir_name));
+    let mut zip = ZipWriter::new(File::create(&archive_path)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut added = BTreeSet::new();
+    for entry in manifest {
+        if entry.path == MANIFEST_NAME || !added.insert(&entry.path) {
+            continue;
+        }
+
+        let file = bundle_dir.join(&entry.path);
+        let file_len = fs::metadata(&file)?.len();
+        // Files of 4 GiB and more, like some game installers, need ZIP64.
+        let options = options.large_file(file_len > u32::MAX as u64);
+        zip.start_file(format!("{}/{}", dir_name, entry.path), options)?;
+        io::copy(&mut File::open(&file)?, &mut zip)?;
+    }
+
+    zip.start_file(format!("{}/{}", dir_name, MANIFEST_NAME), options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    zip.finish()?;
+
+    Ok(archive_path)
+}
+
+pub(crate) fn list_files(dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(list_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,717 @@
+use crate::bundle_download::{self, DownloadOptions};
+use crate::download::DownloadError;
+use crate::humble_api::{ApiError, HumbleApi};
+use crate::models::Bundle;
+use crate::{bundle_passes_claimed_filter, find_key, handle_http_errors, print_error_json};
+use crate::{key_match, read_all_bundles, util};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+
+/// The journal is kept in the download directory, next to the bundles.
+const JOURNAL_FILE: &str = ".humble-cli-journal.json";
+
+/// Failures of the last run, for other tools to read.
+const FAILURES_FILE: &str = "failures.json";
+
+/// Record of the bundles a bulk download has finished or failed, so an
+/// interrupted run can be continued.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Journal {
+    pub bundles: BTreeMap<String, BundleStatus>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum BundleStatus {
+    Completed,
+    Failed { error: String },
+}
+
+impl Journal {
+    /// Read the journal of the previous run. An empty journal is returned if
+    /// there is none.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = Path::new(JOURNAL_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read '{}'", JOURNAL_FILE))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse '{}'", JOURNAL_FILE))
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        std::fs::write(JOURNAL_FILE, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write '{}'", JOURNAL_FILE))
+    }
+
+    pub fn is_completed(&self, bundle_key: &str) -> bool {
+        self.bundles.get(bundle_key) == Some(&BundleStatus::Completed)
+    }
+
+    pub fn is_failed(&self, bundle_key: &str) -> bool {
+        matches!(
+            self.bundles.get(bundle_key),
+            Some(BundleStatus::Failed { .. })
+        )
+    }
+}
+
+/// What a download was doing when it failed. Attached to errors as context,
+/// so failures can be reported per item.
+#[derive(Debug)]
+pub enum FailedStep {
+    Metadata,
+    Item(String),
+}
+
+impl fmt::Display for FailedStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailedStep::Metadata => write!(f, "failed to read the bundle"),
+            FailedStep::Item(name) => write!(f, "failed to download '{}'", name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorClass {
+    Metadata,
+    Timeout,
+    Http,
+    Network,
+    Io,
+    Other,
+}
+
+impl ErrorClass {
+    fn of(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            // `DownloadError` is transparent, so the error it wraps doesn't
+            // show up in the chain by itself.
+            match cause.downcast_ref::<DownloadError>() {
+                Some(DownloadError::Network(e)) => return Self::of_network(e),
+                Some(DownloadError::IO(_)) => return ErrorClass::Io,
+                Some(DownloadError::Server(_)) => return ErrorClass::Http,
+                Some(DownloadError::Incomplete(..)) => return ErrorClass::Network,
+                _ => {}
+            }
+            match cause.downcast_ref::<ApiError>() {
+                Some(ApiError::NetworkError(e)) => return Self::of_network(e),
+                Some(e) if api_http_status(e).is_some() => return ErrorClass::Http,
+                _ => {}
+            }
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                return Self::of_network(e);
+            }
+            if cause.is::<std::io::Error>() {
+                return ErrorClass::Io;
+            }
+        }
+
+        match error.downcast_ref::<FailedStep>() {
+            Some(FailedStep::Metadata) => ErrorClass::Metadata,
+            _ => ErrorClass::Other,
+        }
+    }
+
+    fn of_network(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ErrorClass::Timeout
+        } else if error.is_status() {
+            ErrorClass::Http
+        } else {
+            ErrorClass::Network
+        }
+    }
+}
+
+fn api_http_status(error: &ApiError) -> Option<u16> {
+    match error {
+        ApiError::Unauthorized => Some(401),
+        ApiError::NotFound => Some(404),
+        ApiError::RateLimited { .. } => Some(429),
+        ApiError::ServerError { status, .. } | ApiError::UnexpectedStatus { status, .. } => {
+            Some(status.as_u16())
+        }
+        ApiError::NetworkError(e) => e.status().map(|s| s.as_u16()),
+        _ => None,
+    }
+}
+
+/// A failed bundle, as written to `failures.json`, or a failed command, as
+/// printed with `--errors-json`.
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub bundle_key: Option<String>,
+    /// The item that failed, if the bundle failed while downloading one.
+    pub item: Option<String>,
+    pub error_class: ErrorClass,
+    pub http_status: Option<u16>,
+    /// Whether trying again later may succeed.
+    pub retryable: bool,
+    pub error: String,
+}
+
+impl Failure {
+    pub fn new(bundle_key: &str, error: &anyhow::Error) -> Self {
+        Self {
+            bundle_key: Some(bundle_key.to_owned()),
+            ..Self::from_error(error)
+        }
+    }
+
+    /// A failure that isn't about a single bundle.
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let item = match error.downcast_ref::<FailedStep>() {
+            Some(FailedStep::Item(name)) => Some(name.clone()),
+            _ => None,
+        };
+
+        let mut http_status = None;
+        let mut retryable = false;
+        for cause in error.chain() {
+            if let Some(e) = cause.downcast_ref::<DownloadError>() {
+                if let DownloadError::Server(status) = e {
+                    http_status = Some(status.as_u16());
+                }
+                retryable = e.is_transient();
+                break;
+            }
+            if let Some(e) = cause.downcast_ref::<ApiError>() {
+                http_status = api_http_status(e);
+                retryable = matches!(
+                    e,
+                    ApiError::NetworkError(_)
+                        | ApiError::RateLimited { .. }
+                        | ApiError::ServerError { .. }
+                );
+                break;
+            }
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                http_status = e.status().map(|s| s.as_u16());
+                retryable = e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|s| s.is_server_error());
+                break;
+            }
+        }
+
+        Self {
+            bundle_key: None,
+            item,
+            error_class: ErrorClass::of(error),
+            http_status,
+            retryable,
+            error: format!("{:#}", error),
+        }
+    }
+}
+
+/// Write the failures of a run, or remove the file of a previous run if
+/// nothing failed.
+pub fn save_failures(failures: &[Failure]) -> Result<(), anyhow::Error> {
+    let path = Path::new(FAILURES_FILE);
+    if failures.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to remove '{}'", FAILURES_FILE))?;
+        }
+        return Ok(());
+    }
+
+    std::fs::write(path, serde_json::to_string_pretty(failures)?)
+        .with_context(|| format!("failed to write '{}'", FAILURES_FILE))
+}
+
+/// A bundle to download, with optional settings that override the ones given
+/// on the command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BulkEntry {
+    pub key: String,
+    /// Item numbers or ranges, in the `--item-numbers` format.
+    pub item_numbers: Option<String>,
+    /// Formats to download (lowercase). Empty means the formats of the command line.
+    pub formats: Vec<String>,
+}
+
+impl BulkEntry {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Parse a line in the `KEY[,Name][,items=1-3;5][,formats=epub;pdf]` format.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split(',').map(str::trim);
+        let key = fields.next().filter(|k| !k.is_empty())?;
+
+        let mut entry = Self::new(key);
+        // Other fields, like the bundle name, are ignored
+        for field in fields {
+            if let Some(items) = field.strip_prefix("items=") {
+                entry.item_numbers = Some(items.replace(';', ","));
+            } else if let Some(formats) = field.strip_prefix("formats=") {
+                entry.formats = formats
+                    .split(';')
+                    .map(|f| f.trim().to_lowercase())
+                    .filter(|f| !f.is_empty())
+                    .collect();
+            }
+        }
+
+        Some(entry)
+    }
+}
+
+/// Read the bundles to download from a file, or from stdin if `path` is `-`.
+pub fn read_input(path: &str) -> Result<Vec<BulkEntry>, anyhow::Error> {
+    let content = if path == "-" {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .context("failed to read bundle keys from stdin")?;
+        content
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("failed to read '{}'", path))?
+    };
+
+    Ok(parse_input(&content))
+}
+
+/// Each line holds a bundle key, optionally followed by comma-separated
+/// fields. See `BulkEntry::parse`. Empty lines and lines starting with `#`
+/// are skipped.
+fn parse_input(content: &str) -> Vec<BulkEntry> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(BulkEntry::parse)
+        .collect()
+}
+
+/// Options for `download_bundles`, on top of the `DownloadOptions` used for
+/// every bundle.
+#[derive(Debug, Default)]
+pub struct BulkDownloadOptions {
+    /// Only download bundles whose name matches this glob pattern.
+    pub name_filter: Option<String>,
+    /// Only download bundles with claimed (`yes`) or unclaimed (`no`) keys,
+    /// like `list --claimed`.
+    pub claimed_filter: Option<String>,
+    /// Skip the bundles that the previous run completed.
+    pub continue_run: bool,
+    /// Only download the bundles that failed in the previous run.
+    pub retry_failed: bool,
+    /// Number of bundles to process at the same time. `0` is the same as `1`.
+    pub bundle_jobs: usize,
+    /// Only print what would be downloaded and how big it is.
+    pub dry_run: bool,
+    /// Also download orders that were bought as gifts.
+    pub include_gifts: bool,
+}
+
+/// Download the bundles of `entries` with `bundle_jobs` workers, and keep
+/// the journal and the failure list of the run.
+pub fn download_bundles(
+    api: &HumbleApi,
+    entries: &[BulkEntry],
+    options: &DownloadOptions,
+    bulk_options: &BulkDownloadOptions,
+) -> Result<(), anyhow::Error> {
+    let mut entries = select_bundles(api, entries, bulk_options)?;
+
+    // The journal of the previous run is only needed to continue it.
+    // Otherwise a new run starts with an empty journal.
+    let journal = if bulk_options.continue_run || bulk_options.retry_failed {
+        Journal::load()?
+    } else {
+        Journal::default()
+    };
+
+    let input_count = entries.len();
+    if bulk_options.retry_failed {
+        entries.retain(|e| journal.is_failed(&e.key));
+    } else if bulk_options.continue_run {
+        entries.retain(|e| !journal.is_completed(&e.key));
+    }
+    if entries.len() < input_count {
+        println!(
+            "Skipping {} bundle(s) based on the previous run",
+            input_count - entries.len()
+        );
+    }
+
+    let all_keys = handle_http_errors(api.list_bundle_keys())?;
+    // The full key of every entry that matches a single bundle
+    let resolved: HashMap<String, String> = entries
+        .iter()
+        .filter_map(|e| Some((e.key.clone(), key_match::single_match(&all_keys, &e.key)?)))
+        .collect();
+    let prefetched = prefetch_bundles(api, &resolved);
+    // Bundles that couldn't be read in advance count as empty.
+    options.order.sort(&mut entries, |e| {
+        resolved
+            .get(&e.key)
+            .and_then(|key| prefetched.get(key))
+            .map_or(0, |b| b.total_size())
+    });
+    let prefetched = Mutex::new(prefetched);
+    if bulk_options.dry_run {
+        return print_bulk_estimate(api, &all_keys, &prefetched, &entries, options, bulk_options);
+    }
+
+    let client = bundle_download::download_client()?;
+
+    // Each worker reads the metadata of its next bundle and downloads it, so
+    // with more than one job, metadata is fetched while other bundles are
+    // still downloading.
+    let queue = Mutex::new(entries.iter().enumerate());
+    let journal = Mutex::new(journal);
+    let failures = Mutex::new(vec![]);
+    thread::scope(|scope| {
+        for _ in 0..bulk_options.bundle_jobs.max(1) {
+            scope.spawn(|| loop {
+                let Some((idx, entry)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+
+                // With several jobs, the lines of the bundles are mixed, so
+                // each line says which bundle it is about.
+                util::set_status_prefix(None);
+                status!();
+                status!("[{}/{}] Bundle {}", idx + 1, entries.len(), entry.key);
+                if bulk_options.bundle_jobs > 1 {
+                    util::set_status_prefix(Some(entry.key.clone()));
+                }
+
+                let status = match download_bulk_entry(
+                    api,
+                    &all_keys,
+                    &prefetched,
+                    &client,
+                    entry,
+                    options,
+                    bulk_options,
+                ) {
+                    Ok(()) => BundleStatus::Completed,
+                    Err(e) => {
+                        eprintln!("Failed to download bundle {}: {:#}", entry.key, e);
+                        print_error_json(Some(&entry.key), &e);
+                        let status = BundleStatus::Failed {
+                            error: format!("{:#}", e),
+                        };
+                        failures.lock().unwrap().push((&entry.key, e));
+                        status
+                    }
+                };
+
+                // Save after every bundle, so an interrupted run can be continued
+                let mut journal = journal.lock().unwrap();
+                journal.bundles.insert(entry.key.clone(), status);
+                if let Err(e) = journal.save() {
+                    eprintln!("Warning: {:#}", e);
+                }
+            });
+        }
+    });
+
+    let failures = failures.into_inner().unwrap();
+    let records = failures
+        .iter()
+        .map(|(key, e)| Failure::new(key, e))
+        .collect::<Vec<_>>();
+    if let Err(e) = save_failures(&records) {
+        eprintln!("Warning: {:#}", e);
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!();
+    eprintln!("{} of {} bundle(s) failed:", failures.len(), entries.len());
+    for (key, e) in &failures {
+        eprintln!("  {}: {:#}", key, e);
+    }
+
+    eprintln!();
+    eprintln!("Run the same command with --retry-failed to try them again.");
+
+    Err(anyhow!("{} bundle(s) failed to download", failures.len()))
+}
+
+/// Read the bundles of all entries that match a single key with the batch
+/// endpoint, instead of one request per bundle. `resolved` maps entry keys
+/// to these bundle keys. Other entries, and all of them if the batch request
+/// fails, are read one by one later.
+fn prefetch_bundles(
+    api: &HumbleApi,
+    resolved: &HashMap<String, String>,
+) -> HashMap<String, Bundle> {
+    let keys: BTreeSet<&String> = resolved.values().collect();
+    if keys.is_empty() {
+        return HashMap::new();
+    }
+
+    let keys: Vec<String> = keys.into_iter().cloned().collect();
+    match api.read_bundles(&keys) {
+        Ok(bundles) => bundles
+            .into_iter()
+            .map(|b| (b.gamekey.clone(), b))
+            .collect(),
+        Err(e) => {
+            eprintln!("Warning: failed to read the bundles at once: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Read the bundle of a bulk download entry and download its files.
+fn download_bulk_entry(
+    api: &HumbleApi,
+    all_keys: &[String],
+    prefetched: &Mutex<HashMap<String, Bundle>>,
+    client: &reqwest::Client,
+    entry: &BulkEntry,
+    options: &DownloadOptions,
+    bulk_options: &BulkDownloadOptions,
+) -> Result<(), anyhow::Error> {
+    let (bundle, options) = read_bulk_entry(api, all_keys, prefetched, entry, options)?;
+    if skip_gift(&bundle, bulk_options) {
+        return Ok(());
+    }
+    bundle_download::download_bundle_files(&bundle, &options, client)
+}
+
+/// Whether a bundle is a gift that's skipped, since its content belongs to
+/// whoever received it. The reason is printed.
+fn skip_gift(bundle: &Bundle, bulk_options: &BulkDownloadOptions) -> bool {
+    if !bundle.is_gift || bulk_options.include_gifts {
+        return false;
+    }
+
+    status!(
+        "Skipping {}, which was bought as a gift. Use --include-gifts to download it.",
+        bundle.details.human_name
+    );
+    true
+}
+
+/// Read the bundle of a bulk download entry, and the download options with
+/// the entry's settings applied. The bundle is taken from `prefetched` if
+/// it's there.
+fn read_bulk_entry(
+    api: &HumbleApi,
+    all_keys: &[String],
+    prefetched: &Mutex<HashMap<String, Bundle>>,
+    entry: &BulkEntry,
+    options: &DownloadOptions,
+) -> Result<(Bundle, DownloadOptions), anyhow::Error> {
+    let key = find_key(api, all_keys.to_vec(), &entry.key)
+        .context(FailedStep::Metadata)?
+        .ok_or_else(|| anyhow!("no single bundle matches '{}'", entry.key))
+        .context(FailedStep::Metadata)?;
+    let bundle = match prefetched.lock().unwrap().remove(&key) {
+        Some(bundle) => bundle,
+        None => handle_http_errors(api.read_bundle(&key)).context(FailedStep::Metadata)?,
+    };
+
+    let mut options = options.clone();
+    if entry.item_numbers.is_some() {
+        options.item_numbers = entry.item_numbers.clone();
+    }
+    if !entry.formats.is_empty() {
+        options.formats = entry.formats.clone();
+    }
+
+    Ok((bundle, options))
+}
+
+/// Print the number and size of the files a bulk download would fetch, per
+/// bundle and in total.
+fn print_bulk_estimate(
+    api: &HumbleApi,
+    all_keys: &[String],
+    prefetched: &Mutex<HashMap<String, Bundle>>,
+    entries: &[BulkEntry],
+    options: &DownloadOptions,
+    bulk_options: &BulkDownloadOptions,
+) -> Result<(), anyhow::Error> {
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Key", "Name", "Files", "Size"]);
+
+    let (mut total_files, mut total_size, mut failed, mut gifts) = (0, 0, 0, 0);
+    for entry in entries {
+        let (bundle, options) = match read_bulk_entry(api, all_keys, prefetched, entry, options) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("Failed to read bundle {}: {:#}", entry.key, e);
+                failed += 1;
+                continue;
+            }
+        };
+        if skip_gift(&bundle, bulk_options) {
+            gifts += 1;
+            continue;
+        }
+
+        let products = match bundle_download::selected_products(&bundle, &options) {
+            Ok(products) => products,
+            Err(e) => {
+                eprintln!(
+                    "Failed to select the items of bundle {}: {:#}",
+                    entry.key, e
+                );
+                failed += 1;
+                continue;
+            }
+        };
+        let files = products
+            .into_iter()
+            .flat_map(|p| options.product_files(p))
+            .collect::<Vec<_>>();
+        let size = files.iter().map(|i| i.file_size).sum::<u64>();
+
+        builder.push_record([
+            bundle.gamekey.as_str(),
+            bundle.details.human_name.as_str(),
+            files.len().to_string().as_str(),
+            util::humanize_bytes(size).as_str(),
+        ]);
+        total_files += files.len();
+        total_size += size;
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+        .with(Modify::new(Columns::new(2..)).with(Alignment::right()))
+        .to_string();
+    println!();
+    println!("{table}");
+    println!();
+    println!(
+        "Total: {} file(s), {} in {} bundle(s)",
+        total_files,
+        util::humanize_bytes(total_size),
+        entries.len() - failed - gifts
+    );
+
+    if failed > 0 {
+        return Err(anyhow!("{} bundle(s) could not be estimated", failed));
+    }
+    Ok(())
+}
+
+/// Apply the bundle filters of `bulk_options` to `entries`.
+fn select_bundles(
+    api: &HumbleApi,
+    entries: &[BulkEntry],
+    bulk_options: &BulkDownloadOptions,
+) -> Result<Vec<BulkEntry>, anyhow::Error> {
+    let name_filter = bulk_options.name_filter.as_deref();
+    let claimed_filter = bulk_options.claimed_filter.as_deref().unwrap_or("all");
+    if name_filter.is_none() && claimed_filter == "all" {
+        return Ok(entries.to_vec());
+    }
+
+    let bundles = read_all_bundles(api)?;
+
+    // Keys can be partial, like in `download`
+    let selected: Vec<BulkEntry> = entries
+        .iter()
+        .filter(|entry| {
+            let key = entry.key.to_lowercase();
+            bundles.iter().any(|b| {
+                b.gamekey.to_lowercase().starts_with(&key)
+                    && name_filter.is_none_or(|p| util::glob_matches(p, &b.details.human_name))
+                    && bundle_passes_claimed_filter(b, claimed_filter)
+            })
+        })
+        .cloned()
+        .collect();
+
+    println!(
+        "{} of {} bundle(s) match the filters",
+        selected.len(),
+        entries.len()
+    );
+    Ok(selected)
+}
+
+#[test]
+fn test_parse_input() {
+    let content = "
+        # Books
+        abc123
+        def456,Humble Book Bundle: Python
+
+        ,missing key
+        ghi789 ,Other,items=1-3;5, formats=EPUB;pdf
+        jkl012,formats=mobi
+    ";
+
+    let expected = vec![
+        BulkEntry::new("abc123"),
+        BulkEntry::new("def456"),
+        BulkEntry {
+            key: "ghi789".to_owned(),
+            item_numbers: Some("1-3,5".to_owned()),
+            formats: vec!["epub".to_owned(), "pdf".to_owned()],
+        },
+        BulkEntry {
+            key: "jkl012".to_owned(),
+            item_numbers: None,
+            formats: vec!["mobi".to_owned()],
+        },
+    ];
+    assert_eq!(parse_input(content), expected);
+}
+
+#[test]
+fn test_failure_new() {
+    let error = anyhow::anyhow!("disk full").context(FailedStep::Item("Book".to_owned()));
+    let failure = Failure::new("abc123", &error);
+    assert_eq!(failure.item.as_deref(), Some("Book"));
+    assert_eq!(failure.error_class, ErrorClass::Other);
+    assert_eq!(failure.error, "failed to download 'Book': disk full");
+
+    let error = anyhow::Error::new(DownloadError::from(std::io::Error::other("disk full")))
+        .context(FailedStep::Item("Book".to_owned()));
+    assert_eq!(Failure::new("abc123", &error).error_class, ErrorClass::Io);
+
+    let error = anyhow::Error::new(DownloadError::Server(reqwest::StatusCode::BAD_GATEWAY))
+        .context(FailedStep::Item("Book".to_owned()));
+    let failure = Failure::new("abc123", &error);
+    assert_eq!(failure.error_class, ErrorClass::Http);
+    assert_eq!(failure.http_status, Some(502));
+    assert!(failure.retryable);
+
+    let error = anyhow::Error::new(ApiError::Unauthorized);
+    let failure = Failure::from_error(&error);
+    assert_eq!(failure.bundle_key, None);
+    assert_eq!(failure.error_class, ErrorClass::Http);
+    assert_eq!(failure.http_status, Some(401));
+    assert!(!failure.retryable);
+
+    let error = anyhow::anyhow!("not found").context(FailedStep::Metadata);
+    let failure = Failure::new("abc123", &error);
+    assert_eq!(failure.item, None);
+    assert_eq!(failure.error_class, ErrorClass::Metadata);
+}
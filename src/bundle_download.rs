@@ -0,0 +1,997 @@
+use crate::config::get_config;
+use crate::humble_api::HumbleApi;
+use crate::models::{Bundle, ChoicePeriod, DownloadInfo, DownloadOrder, Product};
+#[cfg(test)]
+use crate::models::{DownloadUrl, ProductDownload};
+use crate::settings::{self, NotifyEvent};
+use crate::{archive, bulk, calibre, download, extract, history, hooks, net, notify, rclone};
+use crate::{handle_http_errors, humble_api, product_passes_filters, product_summary};
+use crate::{torrent_client, util};
+use anyhow::{anyhow, Context};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::MultiSelect;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path;
+use std::time::Duration;
+
+/// Options for `download_bundle`.
+#[derive(Clone, Debug, Default)]
+pub struct DownloadOptions {
+    /// Only download these formats (lowercase). Empty means all formats.
+    pub formats: Vec<String>,
+    /// Skip sub-items larger than this many bytes. `0` means no limit.
+    pub max_size: u64,
+    /// Item numbers or ranges as entered by the user, e.g. `1,3-5`.
+    pub item_numbers: Option<String>,
+    /// Only download the sub-items with these machine names, on top of the
+    /// ones of `item_numbers`.
+    pub item_machine_names: Vec<String>,
+    /// Print a shell script instead of downloading anything.
+    pub export_script: bool,
+    /// Unpack downloaded archives into the sub-item directory.
+    pub extract: bool,
+    /// Remove archives after they have been extracted successfully.
+    pub delete_archives: bool,
+    /// Pack the bundle directory into a zip file after downloading.
+    pub archive: bool,
+    /// Remove the bundle directory once it has been archived.
+    pub remove_files: bool,
+    /// Move each downloaded file to this destination (`rclone:remote:path`).
+    pub dest: Option<String>,
+    /// Add downloaded e-books to Calibre via `calibredb`.
+    pub import_calibre: bool,
+    /// Path of the Calibre library to use instead of the default one.
+    pub calibre_library: Option<String>,
+    /// Save each sub-item's cover image next to its files.
+    pub with_covers: bool,
+    /// Write a `<file>.info.json` metadata file next to each downloaded file.
+    pub write_info_json: bool,
+    /// Don't ask which items to download when no item numbers are given.
+    pub yes: bool,
+    /// Put all files in the bundle directory, without a directory per sub-item.
+    pub flat: bool,
+    /// Put the file of a sub-item that has only one file in the bundle
+    /// directory, without a directory for it.
+    pub no_single_dirs: bool,
+    /// Print the absolute path of each downloaded file on stdout, and
+    /// everything else on stderr.
+    pub print_paths: bool,
+    /// Check that partial files match the server's file before resuming them,
+    /// and check the MD5 of finished files.
+    pub verify_resume: bool,
+    /// Skip files that are in the download history, even if they are not in
+    /// the download directory anymore.
+    pub only_new: bool,
+    /// Compute the SHA-256 of downloaded files, and record it next to the
+    /// MD5 that Humble provides.
+    pub sha256: bool,
+    /// Download the torrent of each file instead of the file.
+    pub torrents: bool,
+    /// With `torrents`, download the file itself if it has no torrent.
+    pub torrent_fallback: bool,
+    /// With `torrents`, add each new torrent to the BitTorrent client of the
+    /// settings file.
+    pub push_torrents: bool,
+    /// Download existing files again if they changed on the server.
+    pub refresh: bool,
+    /// What to do with files that already exist.
+    pub if_exists: download::IfExists,
+    /// Order of the sub-items, and of the files of each sub-item.
+    pub order: DownloadOrder,
+    /// When to try a failed download again.
+    pub retry: download::RetryPolicy,
+    /// Template for the names of downloaded files, with the placeholders
+    /// `{filename}`, `{stem}`, `{ext}`, `{format}`, `{product}` and `{machine_name}`.
+    pub name_template: Option<String>,
+}
+
+/// `--name-template` preset that lays out videos for media servers like
+/// Jellyfin and Plex.
+const MEDIA_SERVER_PRESET: &str = "media-server";
+
+impl DownloadOptions {
+    /// Directory of a sub-item inside the bundle directory, or `None` if it
+    /// goes in the bundle directory itself.
+    fn product_dir_name(&self, product: &Product) -> Option<String> {
+        let single_file = self.no_single_dirs && self.product_files(product).len() == 1;
+        // The preset makes its own directories.
+        let media_server = self.name_template.as_deref() == Some(MEDIA_SERVER_PRESET);
+        (!self.flat && !single_file && !media_server)
+            .then(|| util::replace_invalid_chars_in_filename(&product.human_name))
+    }
+
+    /// Files of a sub-item in the selected formats.
+    pub(crate) fn product_files<'a>(&self, product: &'a Product) -> Vec<&'a DownloadInfo> {
+        product
+            .downloads
+            .iter()
+            .flat_map(|d| d.items.iter())
+            .filter(|i| self.formats.is_empty() || self.formats.contains(&i.format.to_lowercase()))
+            .collect()
+    }
+
+    /// Name of a downloaded file, after applying the name template. Names
+    /// that would end up outside the sub-item directory are an error.
+    fn filename(
+        &self,
+        filename: &str,
+        product: &Product,
+        dl_info: &DownloadInfo,
+    ) -> Result<String, anyhow::Error> {
+        let Some(template) = &self.name_template else {
+            return Ok(filename.to_owned());
+        };
+
+        let media = util::parse_media_name(&product.human_name);
+        let template = match template.as_str() {
+            MEDIA_SERVER_PRESET => media.preset_template(),
+            template => template,
+        };
+
+        let (stem, ext) = filename.rsplit_once('.').unwrap_or((filename, ""));
+        let two_digits = |n: Option<u32>| n.map(|n| format!("{:02}", n)).unwrap_or_default();
+        let fields = [
+            ("filename", filename),
+            ("stem", stem),
+            ("ext", ext),
+            ("format", &dl_info.format.to_lowercase()),
+            ("product", &product.human_name),
+            ("machine_name", &product.machine_name),
+            ("title", &media.title),
+            (
+                "year",
+                &media.year.map(|y| y.to_string()).unwrap_or_default(),
+            ),
+            ("season", &two_digits(media.season)),
+            ("episode", &two_digits(media.episode)),
+        ];
+        let name = util::fill_name_template(template, &fields);
+        let inside = path::Path::new(&name)
+            .components()
+            .all(|c| matches!(c, path::Component::Normal(_)));
+        if name.is_empty() || !inside {
+            return Err(anyhow!(
+                "name template gives '{}' for '{}', which is not a relative path",
+                name,
+                product.human_name
+            ));
+        }
+        Ok(name)
+    }
+}
+
+/// Ask the user which of the given products to download. All of them are
+/// selected by default.
+fn select_products<'a>(products: &[&'a Product]) -> Result<Vec<&'a Product>, anyhow::Error> {
+    let items: Vec<String> = products.iter().map(|p| product_summary(p)).collect();
+    let defaults = vec![true; items.len()];
+
+    let selection = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Items to download (Space to toggle, Enter to confirm)")
+        .items(&items)
+        .defaults(&defaults)
+        .interact_opt()?;
+
+    Ok(selection
+        .unwrap_or_default()
+        .into_iter()
+        .map(|idx| products[idx])
+        .collect())
+}
+
+/// Metadata written to the `.info.json` sidecar of a downloaded file.
+#[derive(serde::Serialize)]
+struct FileInfo<'a> {
+    bundle_key: &'a str,
+    bundle_name: &'a str,
+    machine_name: &'a str,
+    human_name: &'a str,
+    format: &'a str,
+    md5: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<&'a str>,
+    file_size: u64,
+    url: &'a str,
+}
+
+/// Download the games of the order of `period`.
+pub fn download_choice(
+    api: &HumbleApi,
+    period: &ChoicePeriod,
+    options: &DownloadOptions,
+) -> Result<(), anyhow::Error> {
+    let choices = api.read_bundle_choices(&period.to_string())?.options;
+    let gamekey = choices.gamekey.ok_or_else(|| {
+        anyhow!(
+            "you are not subscribed to {}, so there is nothing to download",
+            choices.title
+        )
+    })?;
+
+    let bundle = handle_http_errors(api.read_bundle(&gamekey))?;
+    if bundle.products.is_empty() {
+        status!(
+            "No games were chosen in {} yet. Use `choose` to choose them.",
+            choices.title
+        );
+        return Ok(());
+    }
+    download_bundle_files(&bundle, options, &download_client()?)
+}
+
+pub fn download_client() -> Result<reqwest::Client, reqwest::Error> {
+    let http_read_timeout = Duration::from_secs(30);
+    net::client_builder()
+        .read_timeout(http_read_timeout)
+        .build()
+}
+
+/// Download the files of a bundle that has already been read from the API.
+pub fn download_bundle_files(
+    bundle: &Bundle,
+    options: &DownloadOptions,
+    client: &reqwest::Client,
+) -> Result<(), anyhow::Error> {
+    let max_size = options.max_size;
+
+    let products = selected_products(bundle, options)?;
+    if products.is_empty() {
+        status!("Nothing to download");
+        return Ok(());
+    }
+
+    // Let the user confirm what gets fetched, unless the items were already
+    // chosen or we're not running interactively.
+    let mut products = if options.item_numbers.is_none()
+        && options.item_machine_names.is_empty()
+        && !options.yes
+        && !options.export_script
+        && util::interactive()
+    {
+        let selected = select_products(&products)?;
+        if selected.is_empty() {
+            status!("Nothing to download");
+            return Ok(());
+        }
+        selected
+    } else {
+        products
+    };
+
+    options.order.sort(&mut products, |p| {
+        options.product_files(p).iter().map(|d| d.file_size).sum()
+    });
+
+    let bundle_dir_name = util::replace_invalid_chars_in_filename(&bundle.details.human_name);
+
+    if options.export_script {
+        return print_download_script(&bundle.gamekey, &bundle_dir_name, &products, options);
+    }
+
+    let dest = match &options.dest {
+        Some(dest) => Some(rclone::RcloneDest::parse(dest)?),
+        None => None,
+    };
+
+    // Create the bundle directory
+    let bundle_dir = create_dir(&bundle_dir_name)?;
+
+    let mut files = DownloadedFiles::default();
+    if options.only_new {
+        files.history = Some(history::History::from_entries(&history::load()?));
+    }
+    if options.refresh {
+        files.last_downloads = history::last_downloads(history::load()?);
+    }
+
+    for product in products {
+        if max_size > 0 && product.total_size() > max_size {
+            continue;
+        }
+
+        download_product(
+            bundle,
+            product,
+            options,
+            client,
+            &bundle_dir,
+            &dest,
+            &mut files,
+        )
+        .with_context(|| bulk::FailedStep::Item(product.human_name.clone()))?;
+    }
+
+    if !files.missing_torrents.is_empty() {
+        status!();
+        status!(
+            "{} file(s) have no torrent. Use --torrent-fallback to download them directly:",
+            files.missing_torrents.len()
+        );
+        for name in &files.missing_torrents {
+            status!("  {}", name);
+        }
+    }
+
+    if files.duplicate_count > 0 {
+        status!();
+        status!(
+            "Skipped {} duplicate file(s), saving {}",
+            files.duplicate_count,
+            util::humanize_bytes(files.duplicate_bytes)
+        );
+    }
+
+    if options.archive {
+        let archive_path = archive::create_bundle_archive(&bundle_dir, &files.manifest)?;
+        status!();
+        status!("Created archive {}", archive_path.display());
+
+        if options.remove_files {
+            fs::remove_dir_all(&bundle_dir)?;
+        }
+    }
+
+    hooks::run(
+        hooks::HookEvent::DownloadComplete,
+        serde_json::json!({
+            "bundle_key": bundle.gamekey,
+            "bundle_name": bundle.details.human_name,
+            "path": path::absolute(&bundle_dir)?,
+            "files": files.manifest,
+        }),
+    );
+
+    notify::notify(
+        NotifyEvent::DownloadFinished,
+        &format!(
+            "Finished downloading {} file(s) from {}",
+            files.manifest.len(),
+            bundle.details.human_name
+        ),
+    );
+
+    Ok(())
+}
+
+/// Products of `bundle` that pass the item number, format and size filters
+/// of `options`.
+pub(crate) fn selected_products<'a>(
+    bundle: &'a Bundle,
+    options: &DownloadOptions,
+) -> Result<Vec<&'a Product>, anyhow::Error> {
+    // To parse the item number ranges, we need to know the max value
+    // for unbounded ranges (e.g. 12-). That's why we parse this argument
+    // after we read the bundle from the API.
+    let item_numbers = if let Some(value) = &options.item_numbers {
+        let ranges = value.split(',').map(str::trim).collect::<Vec<_>>();
+        let numbers = util::union_usize_ranges(&ranges, bundle.products.len())?;
+        // An empty list would select all items
+        if numbers.is_empty() {
+            return Err(anyhow!("'{}' doesn't select any item", value));
+        }
+        numbers
+    } else {
+        vec![]
+    };
+
+    // Machine names are matched exactly, so a typo in a script doesn't go
+    // unnoticed.
+    let machine_names = &options.item_machine_names;
+    if let Some(name) = machine_names
+        .iter()
+        .find(|name| !bundle.products.iter().any(|p| &p.machine_name == *name))
+    {
+        return Err(anyhow!("no item with the machine name '{}'", name));
+    }
+
+    // Note that item numbers entered by user start at 1, while our index
+    // starts as 0.
+    let select_all = item_numbers.is_empty() && machine_names.is_empty();
+    let products = bundle
+        .products
+        .iter()
+        .enumerate()
+        .filter(|&(i, p)| {
+            select_all || item_numbers.contains(&(i + 1)) || machine_names.contains(&p.machine_name)
+        })
+        .map(|(_, p)| p)
+        .filter(|p| product_passes_filters(p, &options.formats, options.max_size))
+        .collect();
+
+    Ok(products)
+}
+
+/// Download the files of one product of `bundle` into `bundle_dir`, and add
+/// them to `files`.
+fn download_product(
+    bundle: &Bundle,
+    product: &Product,
+    options: &DownloadOptions,
+    client: &reqwest::Client,
+    bundle_dir: &path::Path,
+    dest: &Option<rclone::RcloneDest>,
+    files: &mut DownloadedFiles,
+) -> Result<(), anyhow::Error> {
+    let formats = &options.formats;
+    let bundle_dir_name = util::replace_invalid_chars_in_filename(&bundle.details.human_name);
+
+    status!();
+    status!("{}", product.human_name);
+
+    let dir_name = options.product_dir_name(product);
+    let entry_dir = match &dir_name {
+        Some(dir_name) => bundle_dir.join(dir_name),
+        None => bundle_dir.to_owned(),
+    };
+    if !entry_dir.exists() {
+        fs::create_dir(&entry_dir)?;
+    }
+    // Path of a file relative to the bundle directory
+    let relative_path = |name: &str| match &dir_name {
+        Some(dir_name) => format!("{}/{}", dir_name, name),
+        None => name.to_owned(),
+    };
+
+    if options.with_covers {
+        download_cover(client, product, &entry_dir)?;
+    }
+
+    let mut ebooks = vec![];
+
+    let mut dl_infos: Vec<&DownloadInfo> = product
+        .downloads
+        .iter()
+        .flat_map(|d| d.items.iter())
+        .collect();
+    options.order.sort(&mut dl_infos, |d| d.file_size);
+
+    for dl_info in dl_infos {
+        if !formats.is_empty() && !formats.contains(&dl_info.format.to_lowercase()) {
+            status!("Skipping '{}'", dl_info.format);
+            continue;
+        }
+
+        if let Some(history) = &files.history {
+            if history.contains(&bundle.gamekey, &product.machine_name, dl_info) {
+                status!("Skipping '{}', it was downloaded before", dl_info.format);
+                continue;
+            }
+        }
+
+        if let Some(path) = files.duplicate_of(dl_info) {
+            status!(
+                "Skipping '{}', it's the same file as {}",
+                dl_info.format,
+                path.display()
+            );
+            continue;
+        }
+
+        if options.torrents {
+            match dl_info.url.torrent() {
+                Some(url) => {
+                    download_torrent(client, url, product, dl_info, &entry_dir, options)?;
+                    continue;
+                }
+                None if options.torrent_fallback => {
+                    status!(
+                        "  '{}' has no torrent, downloading the file instead",
+                        dl_info.format
+                    );
+                }
+                None => {
+                    status!("  Skipping '{}', it has no torrent", dl_info.format);
+                    files
+                        .missing_torrents
+                        .push(format!("{} ({})", product.human_name, dl_info.format));
+                    continue;
+                }
+            }
+        }
+
+        let filename = match util::extract_filename_from_url(&dl_info.url.web) {
+            Some(name) => name,
+            None => {
+                let f = download::get_remote_filename(client, &dl_info.url.web);
+                util::run_future(f)?.unwrap_or_else(|| {
+                    util::fallback_filename(&product.human_name, &dl_info.format)
+                })
+            }
+        };
+        let filename = options.filename(&filename, product, dl_info)?;
+        let mut filename = files.unique_filename(&entry_dir, &filename, product);
+        if options.if_exists == download::IfExists::Rename {
+            filename = util::unused_filename(&entry_dir, &filename);
+        }
+        let download_path = entry_dir.join(&filename);
+        // Name templates can put files in sub-directories.
+        if let Some(parent) = download_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        files.add_md5(dl_info, &download_path);
+
+        let previous = files
+            .last_downloads
+            .get(&path::absolute(&download_path)?)
+            .map(|e| download::Downloaded {
+                size: e.file_size,
+                etag: e.etag.as_deref(),
+            });
+        let file_options = download::FileOptions {
+            retry: &options.retry,
+            if_exists: options.if_exists,
+            verify: options.verify_resume.then_some(dl_info.md5.as_str()),
+            refresh: options.refresh,
+            previous,
+        };
+        let fresh_url = || fresh_download_url(&bundle.gamekey, &product.machine_name, dl_info);
+        let f = download::download_file(
+            client,
+            &dl_info.url.web,
+            download_path.to_str().unwrap(),
+            &filename,
+            &file_options,
+            &fresh_url,
+        );
+        let started = std::time::Instant::now();
+        let result = util::run_future(f);
+        let seconds = started.elapsed().as_secs_f64();
+        let sha256 = match &result {
+            Ok(_) if options.sha256 => Some(util::file_sha256(&download_path)?),
+            _ => None,
+        };
+
+        history::append(&history::HistoryEntry {
+            time: chrono::Utc::now(),
+            bundle_key: bundle.gamekey.clone(),
+            bundle_name: bundle.details.human_name.clone(),
+            machine_name: product.machine_name.clone(),
+            format: dl_info.format.clone(),
+            md5: dl_info.md5.clone(),
+            sha256: sha256.clone(),
+            etag: result.as_ref().ok().cloned().flatten(),
+            path: path::absolute(&download_path)?,
+            file_size: dl_info.file_size,
+            seconds: Some(seconds),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        })?;
+        result?;
+
+        if options.print_paths {
+            println!("{}", path::absolute(&download_path)?.display());
+        }
+
+        files.manifest.push(archive::ManifestEntry {
+            product: product.human_name.clone(),
+            format: dl_info.format.clone(),
+            path: relative_path(&filename),
+            md5: dl_info.md5.clone(),
+            sha256: sha256.clone(),
+            file_size: dl_info.file_size,
+        });
+
+        let info_path = if options.write_info_json {
+            let info = FileInfo {
+                bundle_key: &bundle.gamekey,
+                bundle_name: &bundle.details.human_name,
+                machine_name: &product.machine_name,
+                human_name: &product.human_name,
+                format: &dl_info.format,
+                md5: &dl_info.md5,
+                sha256: sha256.as_deref(),
+                file_size: dl_info.file_size,
+                url: &dl_info.url.web,
+            };
+            Some(write_info_json(&download_path, &info)?)
+        } else {
+            None
+        };
+
+        if options.extract {
+            extract_download(dl_info, &download_path, &entry_dir, options.delete_archives)?;
+        }
+
+        if options.import_calibre && calibre::is_ebook(&download_path) {
+            ebooks.push(download_path.clone());
+        }
+
+        if let Some(dest) = &dest {
+            let remote_path = format!("{}/{}", bundle_dir_name, relative_path(&filename));
+            dest.upload(&download_path, &remote_path)?;
+            status!("  Moved to {}", dest.remote_path(&remote_path));
+
+            if let Some(info_path) = &info_path {
+                let info_name = info_path.file_name().unwrap().to_string_lossy();
+                let remote_path = format!("{}/{}", bundle_dir_name, relative_path(&info_name));
+                dest.upload(info_path, &remote_path)?;
+            }
+        }
+    }
+
+    if !ebooks.is_empty() {
+        calibre::import_books(&ebooks, options.calibre_library.as_deref())?;
+        status!("  Added to Calibre");
+    }
+
+    Ok(())
+}
+
+/// Read the bundle again to get a newly signed URL for a file. Humble signs
+/// download URLs, so a new one may be served by another CDN edge.
+fn fresh_download_url(
+    bundle_key: &str,
+    machine_name: &str,
+    dl_info: &DownloadInfo,
+) -> Option<String> {
+    let config = get_config().ok()?;
+    let bundle = humble_api(&config).read_bundle(bundle_key).ok()?;
+    let product = bundle
+        .products
+        .iter()
+        .find(|p| p.machine_name == machine_name)?;
+
+    product
+        .downloads
+        .iter()
+        .flat_map(|d| &d.items)
+        .find(|item| {
+            if dl_info.md5.is_empty() {
+                item.format == dl_info.format
+            } else {
+                item.md5 == dl_info.md5
+            }
+        })
+        .map(|item| item.url.web.clone())
+        .filter(|url| *url != dl_info.url.web)
+}
+
+/// Record of the files that are downloaded from a bundle.
+#[derive(Default)]
+struct DownloadedFiles {
+    manifest: Vec<archive::ManifestEntry>,
+    /// Paths used by this run, to tell apart files with the same name.
+    paths: HashSet<path::PathBuf>,
+    /// Path of each file by its MD5, to skip files that are listed more than once.
+    by_md5: HashMap<String, path::PathBuf>,
+    duplicate_count: usize,
+    duplicate_bytes: u64,
+    /// Files that were skipped with `torrents`, since they have no torrent.
+    missing_torrents: Vec<String>,
+    /// Files of earlier runs, to skip them. Only set with `only_new`.
+    history: Option<history::History>,
+    /// The last complete download of each file. Only set with `refresh`.
+    last_downloads: HashMap<path::PathBuf, history::HistoryEntry>,
+}
+
+impl DownloadedFiles {
+    /// A name for `filename` in `dir` that no other file of this run uses.
+    /// If the name is taken, the machine name of `product` is added to it.
+    /// Otherwise a file would be resumed onto a different one.
+    fn unique_filename(&mut self, dir: &path::Path, filename: &str, product: &Product) -> String {
+        let mut name = filename.to_owned();
+        let mut n = 1;
+        while self.paths.contains(&dir.join(&name)) {
+            let suffix = match n {
+                1 => product.machine_name.clone(),
+                n => format!("{}-{}", product.machine_name, n),
+            };
+            name = util::filename_with_suffix(filename, &suffix);
+            n += 1;
+        }
+
+        self.paths.insert(dir.join(&name));
+        name
+    }
+
+    /// The path of a file downloaded before with the same content as
+    /// `dl_info`. Duplicates are counted, to report the savings.
+    fn duplicate_of(&mut self, dl_info: &DownloadInfo) -> Option<&path::Path> {
+        let path = self.by_md5.get(&dl_info.md5)?;
+        self.duplicate_count += 1;
+        self.duplicate_bytes += dl_info.file_size;
+        Some(path)
+    }
+
+    fn add_md5(&mut self, dl_info: &DownloadInfo, path: &path::Path) {
+        // Some files have no MD5, and they can't be compared
+        if !dl_info.md5.is_empty() {
+            self.by_md5.insert(dl_info.md5.clone(), path.to_owned());
+        }
+    }
+}
+
+/// Write `info` to `<file>.info.json` next to `file_path` and return the sidecar's path.
+fn write_info_json(
+    file_path: &path::Path,
+    info: &FileInfo,
+) -> Result<path::PathBuf, anyhow::Error> {
+    let mut info_name = file_path.file_name().unwrap().to_os_string();
+    info_name.push(".info.json");
+
+    let info_path = file_path.with_file_name(info_name);
+    fs::write(&info_path, serde_json::to_string_pretty(info)?)?;
+    Ok(info_path)
+}
+
+/// Save the product's cover image as `cover.<ext>` in `entry_dir`.
+///
+/// Products without an image are skipped, and so are covers that already exist.
+fn download_cover(
+    client: &reqwest::Client,
+    product: &Product,
+    entry_dir: &path::Path,
+) -> Result<(), anyhow::Error> {
+    let Some(url) = product.icon.as_deref().filter(|url| !url.is_empty()) else {
+        return Ok(());
+    };
+
+    let cover_path = entry_dir.join(util::cover_filename(url));
+    if cover_path.exists() {
+        return Ok(());
+    }
+
+    let f = download::download_small_file(client, url, cover_path.to_str().unwrap());
+    util::run_future(f).context("failed to download the cover image")?;
+    status!(
+        "  Saved {}",
+        cover_path.file_name().unwrap().to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Save the torrent of a file into `entry_dir`, unless it's there already.
+fn download_torrent(
+    client: &reqwest::Client,
+    url: &str,
+    product: &Product,
+    dl_info: &DownloadInfo,
+    entry_dir: &path::Path,
+    options: &DownloadOptions,
+) -> Result<(), anyhow::Error> {
+    let filename = util::extract_filename_from_url(url)
+        .filter(|name| name.ends_with(".torrent"))
+        .unwrap_or_else(|| {
+            util::fallback_filename(&product.human_name, &dl_info.format) + ".torrent"
+        });
+    let torrent_path = entry_dir.join(&filename);
+    if torrent_path.exists() {
+        status!("  Nothing to do. {} already exists.", filename);
+        return Ok(());
+    }
+
+    let f = download::download_small_file(client, url, torrent_path.to_str().unwrap());
+    util::run_future(f).with_context(|| format!("failed to download {}", filename))?;
+    status!("  Saved {}", filename);
+
+    if options.push_torrents {
+        let torrents = settings::get_settings()?.torrents.ok_or_else(|| {
+            anyhow!("--push-torrents needs a [torrents] section in ~/.humble-cli.toml")
+        })?;
+        torrent_client::push(&torrents, &torrent_path, &path::absolute(entry_dir)?)?;
+        status!("  Added to {}", torrents.client);
+    }
+    Ok(())
+}
+
+/// Unpack a downloaded file into `dest_dir` if it is a supported archive.
+///
+/// The file is checked against the MD5 reported by Humble Bundle first, so
+/// a corrupted download is never extracted.
+fn extract_download(
+    dl_info: &DownloadInfo,
+    archive_path: &path::Path,
+    dest_dir: &path::Path,
+    delete_archive: bool,
+) -> Result<(), anyhow::Error> {
+    let filename = archive_path.file_name().unwrap().to_string_lossy();
+    let Some(kind) = extract::ArchiveKind::from_filename(&filename) else {
+        return Ok(());
+    };
+
+    if !dl_info.md5.is_empty() {
+        let md5 = util::file_md5(archive_path)?;
+        if !md5.eq_ignore_ascii_case(&dl_info.md5) {
+            status!("  Checksum mismatch for {}. Not extracting.", filename);
+            return Ok(());
+        }
+    }
+
+    extract::extract_archive(&kind, archive_path, dest_dir)
+        .with_context(|| format!("failed to extract '{}'", filename))?;
+    status!("  Extracted {}", filename);
+
+    if delete_archive {
+        fs::remove_file(archive_path)?;
+    }
+
+    Ok(())
+}
+
+/// Print a POSIX shell script that downloads the given products with `curl`.
+///
+/// The script mirrors the directory layout of `download_bundle` and uses
+/// `curl -C -` so an interrupted run can simply be started again.
+fn print_download_script(
+    bundle_key: &str,
+    bundle_dir: &str,
+    products: &[&Product],
+    options: &DownloadOptions,
+) -> Result<(), anyhow::Error> {
+    let history = if options.only_new {
+        Some(history::History::from_entries(&history::load()?))
+    } else {
+        None
+    };
+    print!(
+        "{}",
+        download_script(bundle_key, bundle_dir, products, options, history)?
+    );
+    Ok(())
+}
+
+/// The shell script of `print_download_script`. Files in `history` are left out.
+fn download_script(
+    bundle_key: &str,
+    bundle_dir: &str,
+    products: &[&Product],
+    options: &DownloadOptions,
+    history: Option<history::History>,
+) -> Result<String, anyhow::Error> {
+    use std::fmt::Write;
+
+    let formats = &options.formats;
+    let mut script = String::new();
+    let mut line = |s: String| writeln!(script, "{}", s).unwrap();
+    line("#!/bin/sh".to_owned());
+    line(format!(
+        "# Generated by {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    ));
+    line("# Note: download links are signed by Humble Bundle and expire after a while.".to_owned());
+    line("set -e".to_owned());
+
+    let mut files = DownloadedFiles {
+        history,
+        ..Default::default()
+    };
+    for product in products {
+        let entry_dir = match options.product_dir_name(product) {
+            Some(dir_name) => format!("{}/{}", bundle_dir, dir_name),
+            None => bundle_dir.to_owned(),
+        };
+
+        line(String::new());
+        line(format!("# {}", product.human_name));
+        line(format!("mkdir -p {}", util::shell_quote(&entry_dir)));
+
+        for product_download in product.downloads.iter() {
+            for dl_info in product_download.items.iter() {
+                if !formats.is_empty() && !formats.contains(&dl_info.format.to_lowercase()) {
+                    continue;
+                }
+
+                if let Some(history) = &files.history {
+                    if history.contains(bundle_key, &product.machine_name, dl_info) {
+                        continue;
+                    }
+                }
+
+                let filename =
+                    util::extract_filename_from_url(&dl_info.url.web).unwrap_or_else(|| {
+                        util::fallback_filename(&product.human_name, &dl_info.format)
+                    });
+                let filename = options.filename(&filename, product, dl_info)?;
+                let filename =
+                    files.unique_filename(path::Path::new(&entry_dir), &filename, product);
+                let download_path = format!("{}/{}", entry_dir, filename);
+                if let Some((dir, _)) = filename.rsplit_once('/') {
+                    let dir = format!("{}/{}", entry_dir, dir);
+                    line(format!("mkdir -p {}", util::shell_quote(&dir)));
+                }
+
+                line(format!(
+                    "curl -L --fail --retry 3 -C - -o {} {}",
+                    util::shell_quote(&download_path),
+                    util::shell_quote(&dl_info.url.web)
+                ));
+            }
+        }
+    }
+
+    Ok(script)
+}
+
+fn create_dir(dir: &str) -> Result<path::PathBuf, std::io::Error> {
+    let dir = path::Path::new(dir).to_owned();
+    if !dir.exists() {
+        fs::create_dir(&dir)?;
+    }
+    Ok(dir)
+}
+
+#[test]
+fn test_download_script_only_new() {
+    let file = |md5: &str, name: &str| DownloadInfo {
+        md5: md5.to_owned(),
+        format: "EPUB".to_owned(),
+        file_size: 1000,
+        url: DownloadUrl {
+            web: format!("https://dl.humble.com/{}.epub?t=x", name),
+            bittorrent: String::new(),
+        },
+    };
+    let product = |machine_name: &str, md5: &str| Product {
+        machine_name: machine_name.to_owned(),
+        human_name: machine_name.to_owned(),
+        downloads: vec![ProductDownload {
+            items: vec![file(md5, machine_name)],
+        }],
+        ..Default::default()
+    };
+    let old = product("old", "aaa");
+    let new = product("new", "bbb");
+
+    let history = history::History::from_entries(&[history::HistoryEntry {
+        time: chrono::Utc::now(),
+        bundle_key: "key".to_owned(),
+        bundle_name: "Bundle".to_owned(),
+        machine_name: "old".to_owned(),
+        format: "EPUB".to_owned(),
+        md5: "aaa".to_owned(),
+        sha256: None,
+        etag: None,
+        path: path::PathBuf::from("Bundle/old/old.epub"),
+        file_size: 1000,
+        seconds: None,
+        error: None,
+    }]);
+
+    let options = DownloadOptions::default();
+    let script = download_script("key", "Bundle", &[&old, &new], &options, Some(history)).unwrap();
+    assert!(!script.contains("old.epub"));
+    assert!(script.contains("new.epub"));
+}
+
+#[test]
+fn test_name_template_stays_inside() {
+    let dl_info = DownloadInfo {
+        md5: String::new(),
+        format: "MP4".to_owned(),
+        file_size: 0,
+        url: DownloadUrl {
+            web: String::new(),
+            bittorrent: String::new(),
+        },
+    };
+    let product = |name: &str| Product {
+        human_name: name.to_owned(),
+        ..Default::default()
+    };
+    let options = |template: &str| DownloadOptions {
+        name_template: Some(template.to_owned()),
+        ..Default::default()
+    };
+
+    let media = options(MEDIA_SERVER_PRESET);
+    assert_eq!(
+        media
+            .filename("evo.zip", &product("4x4 Evo 2"), &dl_info)
+            .unwrap(),
+        "4x4 Evo 2/4x4 Evo 2.zip"
+    );
+    assert_eq!(
+        media
+            .filename("show.mp4", &product("Show - S01E02"), &dl_info)
+            .unwrap(),
+        "Show/Season 01/Show - S01E02.mp4"
+    );
+    assert!(media.filename("x.mp4", &product(""), &dl_info).is_err());
+    assert!(options("../{filename}")
+        .filename("x.mp4", &product("x"), &dl_info)
+        .is_err());
+    assert!(options("/tmp/{filename}")
+        .filename("x.mp4", &product("x"), &dl_info)
+        .is_err());
+}
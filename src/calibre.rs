@@ -0,0 +1,55 @@
+use anyhow::{anyhow, Context};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const EBOOK_EXTENSIONS: [&str; 7] = ["epub", "mobi", "azw3", "pdf", "cbz", "cbr", "djvu"];
+
+pub fn is_ebook(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .map(|ext| EBOOK_EXTENSIONS.contains(&ext.as_str()))
+        .unwrap_or(false)
+}
+
+/// Add the given files to a Calibre library using `calibredb`.
+///
+/// All files are expected to be different formats of the same book. Calibre
+/// merges them into a single record and ignores books whose title already
+/// exists in the library.
+pub fn import_books(files: &[PathBuf], library: Option<&str>) -> Result<(), anyhow::Error> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let mut cmd = Command::new("calibredb");
+    cmd.arg("add").arg("--automerge").arg("ignore");
+    if let Some(library) = library {
+        cmd.arg("--with-library").arg(library);
+    }
+    cmd.args(files);
+
+    let output = cmd
+        .output()
+        .context("failed to run calibredb. Is Calibre installed and in PATH?")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("calibredb failed: {}", stderr.trim()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_is_ebook() {
+    let test_data = vec![
+        ("book.epub", true),
+        ("Book.PDF", true),
+        ("game.zip", false),
+        ("README", false),
+    ];
+
+    for (input, expected) in test_data {
+        assert_eq!(is_ebook(Path::new(input)), expected, "{}", input);
+    }
+}
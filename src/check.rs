@@ -0,0 +1,63 @@
+use crate::models::Bundle;
+use crate::settings::NotifyEvent;
+use crate::{hooks, notify, state};
+
+/// Print the changes of `bundles` since the saved state as JSON, then save
+/// the new state, run the hooks and send the notifications.
+pub fn check_library(bundles: &[Bundle]) -> Result<bool, anyhow::Error> {
+    let current = state::LibraryState::from_bundles(bundles);
+    let changes = match state::load()? {
+        Some(previous) => current.changes_since(&previous),
+        None => vec![],
+    };
+    state::save(&current)?;
+
+    let new_bundles = changes
+        .iter()
+        .filter(|c| matches!(c, state::Change::NewBundle { .. }))
+        .count();
+    let new_keys = changes
+        .iter()
+        .filter(|c| matches!(c, state::Change::NewKey { .. }))
+        .count();
+    let summary = serde_json::json!({
+        "new_bundles": new_bundles,
+        "new_keys": new_keys,
+        "changed_keys": changes.len() - new_bundles - new_keys,
+        "changes": changes,
+    });
+    println!("{}", summary);
+
+    for change in &changes {
+        if let state::Change::NewBundle { key, name } = change {
+            hooks::run(
+                hooks::HookEvent::NewBundle,
+                serde_json::json!({ "bundle_key": key, "bundle_name": name }),
+            );
+        }
+    }
+
+    let new_bundle_names: Vec<&str> = changes
+        .iter()
+        .filter_map(|c| match c {
+            state::Change::NewBundle { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    if !new_bundle_names.is_empty() {
+        notify::notify(
+            NotifyEvent::NewBundles,
+            &format!("New bundle(s): {}", new_bundle_names.join(", ")),
+        );
+    }
+
+    let key_change_count = changes.len() - new_bundles;
+    if key_change_count > 0 {
+        notify::notify(
+            NotifyEvent::KeyChanges,
+            &format!("{} key(s) were added or changed", key_change_count),
+        );
+    }
+
+    Ok(!changes.is_empty())
+}
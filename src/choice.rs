@@ -0,0 +1,223 @@
+use crate::humble_api::{ApiError, HumbleApi};
+use crate::models::{Bundle, ChoicePeriod, ClaimStatus, ContentChoiceOptions};
+use crate::{handle_http_errors, DateOptions};
+use anyhow::anyhow;
+use chrono::Datelike;
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+
+pub fn list_choices(api: &HumbleApi, period: &ChoicePeriod) -> Result<(), anyhow::Error> {
+    let choices = api.read_bundle_choices(&period.to_string())?;
+
+    println!();
+    println!("{}", choices.options.title);
+    println!();
+
+    let options = choices.options;
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["#", "Title", "Redeemed", "Available"]);
+
+    let mut counter = 1;
+    let mut all_redeemed = true;
+    let mut lost_count = 0;
+    for (_, game_data) in options.data.game_data.iter() {
+        for tpkd in game_data.tpkds.iter() {
+            let reason = tpkd.unavailable_reason();
+            let available = match reason {
+                Some(reason) => format!("No ({})", reason),
+                None if tpkd.claim_status() == ClaimStatus::No => "Yes".to_owned(),
+                None => String::new(),
+            };
+            builder.push_record([
+                counter.to_string().as_str(),
+                tpkd.human_name.as_str(),
+                tpkd.claim_status().to_string().as_str(),
+                available.as_str(),
+            ]);
+
+            counter += 1;
+
+            if reason.is_some() {
+                lost_count += 1;
+            } else if tpkd.claim_status() == ClaimStatus::No {
+                all_redeemed = false;
+            }
+        }
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(0)).with(Alignment::right()))
+        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+        .to_string();
+
+    println!("{table}");
+
+    if lost_count > 0 {
+        println!("{} unclaimed key(s) can't be claimed anymore.", lost_count);
+    }
+    if !all_redeemed {
+        let url = "https://www.humblebundle.com/membership/home";
+        println!("Visit {url} to redeem your keys.");
+    }
+    Ok(())
+}
+
+/// Choose the games of `plan_choose_all` for `period`.
+pub fn choose_all(
+    api: &HumbleApi,
+    period: &ChoicePeriod,
+    dry_run: bool,
+) -> Result<(), anyhow::Error> {
+    let options = api.read_bundle_choices(&period.to_string())?.options;
+    let Some(gamekey) = options.gamekey.as_deref() else {
+        return Err(anyhow!(
+            "you are not subscribed to {}, so there is nothing to choose",
+            options.title
+        ));
+    };
+    if !options.is_active_content {
+        return Err(anyhow!("{} can't be chosen from anymore", options.title));
+    }
+
+    let plan = options.plan_choose_all();
+    println!();
+    println!("{}", options.title);
+    println!();
+
+    if !plan.choose.is_empty() && !dry_run {
+        let ids: Vec<&str> = plan.choose.iter().map(|&(id, _)| id).collect();
+        handle_http_errors(api.humbler().and_then(|h| {
+            h.choose_content(gamekey, ContentChoiceOptions::PARENT_IDENTIFIER, &ids)
+        }))?;
+    }
+
+    let verb = if dry_run { "Would choose" } else { "Chose" };
+    for (_, game) in &plan.choose {
+        println!("{}: {}", verb, game.title);
+    }
+    for (game, reason) in &plan.skip {
+        println!("Skipped: {} ({})", game.title, reason);
+    }
+    if plan.choose.is_empty() {
+        println!("Nothing to choose");
+    }
+
+    Ok(())
+}
+
+/// Print the membership months among `bundles`, oldest first.
+pub fn list_months(mut bundles: Vec<Bundle>, dates: &DateOptions) -> Result<(), anyhow::Error> {
+    bundles.retain(|b| b.is_membership());
+    bundles.sort_by_key(|b| b.created);
+
+    if bundles.is_empty() {
+        println!("No Humble Choice or Humble Monthly months found.");
+        return Ok(());
+    }
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Key", "Name", "Purchased", "Keys", "Claimed"]);
+
+    for b in &bundles {
+        builder.push_record([
+            b.gamekey.as_str(),
+            b.details.human_name.as_str(),
+            dates.format(&b.created, "%Y-%m-%d").as_str(),
+            b.product_keys().len().to_string().as_str(),
+            b.claim_status().to_string().as_str(),
+        ]);
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+        .with(Modify::new(Columns::single(3)).with(Alignment::right()))
+        .to_string();
+    println!("{table}");
+    println!();
+    println!("{} month(s) found.", bundles.len());
+
+    Ok(())
+}
+
+/// The month Humble Choice replaced Humble Monthly, as `(year, month)`.
+const FIRST_CHOICE_MONTH: (u16, u32) = (2019, 12);
+
+/// Read every month since `FIRST_CHOICE_MONTH` and print the ones with keys.
+pub fn list_all_choices(api: &HumbleApi) -> Result<(), anyhow::Error> {
+    let today = chrono::Local::now().date_naive();
+    let periods =
+        ChoicePeriod::months_between(FIRST_CHOICE_MONTH, (today.year() as u16, today.month()));
+    println!(
+        "Checking {} months. This may take a while...",
+        periods.len()
+    );
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Month", "Title", "Keys", "Claimed", "Unclaimed"]);
+
+    let mut month_count = 0;
+    let mut total_unclaimed = 0;
+    for period in &periods {
+        let choices = match api.read_bundle_choices(&period.to_string()) {
+            Err(ApiError::BundleNotFound | ApiError::NotFound) => continue,
+            result => handle_http_errors(result)?,
+        };
+
+        let statuses: Vec<ClaimStatus> = choices
+            .options
+            .data
+            .game_data
+            .values()
+            .flat_map(|g| &g.tpkds)
+            .map(|t| t.claim_status())
+            .collect();
+        let claimed = statuses.iter().filter(|&s| *s == ClaimStatus::Yes).count();
+        let unclaimed = statuses.iter().filter(|&s| *s == ClaimStatus::No).count();
+
+        // No keys are available in months the user wasn't subscribed to
+        if claimed + unclaimed == 0 {
+            continue;
+        }
+
+        month_count += 1;
+        total_unclaimed += unclaimed;
+        builder.push_record([
+            period.to_string(),
+            choices.options.title,
+            (claimed + unclaimed).to_string(),
+            claimed.to_string(),
+            unclaimed.to_string(),
+        ]);
+    }
+
+    if month_count == 0 {
+        println!("No subscribed Humble Choice months found");
+        return Ok(());
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+        .with(Modify::new(Columns::new(2..)).with(Alignment::right()))
+        .to_string();
+
+    println!();
+    println!("{table}");
+    println!();
+    println!(
+        "{} unclaimed key(s) in {} month(s).",
+        total_unclaimed, month_count
+    );
+
+    if total_unclaimed > 0 {
+        let url = "https://www.humblebundle.com/membership/home";
+        println!("Visit {url} to redeem your keys.");
+    }
+    Ok(())
+}
@@ -2,11 +2,18 @@ use std::path::PathBuf;
 
 use anyhow::{anyhow, Context};
 
-#[derive(Debug)]
 pub struct Config {
     pub session_key: String,
 }
 
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Config")
+            .field("session_key", &crate::util::redact(&self.session_key))
+            .finish()
+    }
+}
+
 pub fn get_config() -> Result<Config, anyhow::Error> {
     let file_name = get_config_file_name()?;
     let session_key = std::fs::read_to_string(&file_name).with_context(|| {
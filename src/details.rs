@@ -0,0 +1,122 @@
+use crate::humble_api::HumbleApi;
+use crate::models::ItemSort;
+use crate::{find_key, handle_http_errors, util, DateOptions};
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+
+/// Read the bundle that `bundle_key` points to and print its details.
+pub fn show_bundle_details(
+    api: &HumbleApi,
+    bundle_key: &str,
+    dates: &DateOptions,
+    sort: Option<ItemSort>,
+) -> Result<(), anyhow::Error> {
+    let bundle_key = match find_key(api, handle_http_errors(api.list_bundle_keys())?, bundle_key)? {
+        Some(key) => key,
+        None => return Ok(()),
+    };
+
+    let bundle = handle_http_errors(api.read_bundle(&bundle_key))?;
+
+    println!();
+    println!("{}", bundle.details.human_name);
+    println!();
+    println!(
+        "Purchased  : {}",
+        dates.format(&bundle.created, "%v %I:%M %p %Z")
+    );
+    println!("Total size : {}", util::humanize_bytes(bundle.total_size()));
+    if let Some(amount) = bundle.amount_spent {
+        println!("Spent      : {}", bundle.format_amount(amount));
+    }
+    if let Some(amount) = bundle.amount_to_charge {
+        println!("Charged    : {}", bundle.format_amount(amount));
+    }
+    if let Some(amount) = bundle.total {
+        println!("Total      : {}", bundle.format_amount(amount));
+    }
+    if let Some(payee) = bundle.payee.as_ref().filter(|p| !p.human_name.is_empty()) {
+        println!("Payee      : {}", payee.human_name);
+    }
+    println!();
+
+    if !bundle.products.is_empty() {
+        let mut builder = tabled::builder::Builder::default();
+        builder.set_header(["#", "Sub-item", "Format", "Total Size"]);
+
+        let mut products: Vec<_> = bundle.products.iter().enumerate().collect();
+        match sort {
+            None => {}
+            Some(ItemSort::Name) => {
+                products.sort_by_cached_key(|(_, p)| p.human_name.to_lowercase())
+            }
+            Some(ItemSort::Size) => {
+                products.sort_by_key(|(_, p)| std::cmp::Reverse(p.total_size()))
+            }
+            Some(ItemSort::Format) => products.sort_by_cached_key(|(_, p)| {
+                (p.formats().to_lowercase(), p.human_name.to_lowercase())
+            }),
+        }
+
+        for (idx, entry) in products {
+            builder.push_record([
+                &(idx + 1).to_string(),
+                &entry.human_name,
+                &entry.formats(),
+                &util::humanize_bytes(entry.total_size()),
+            ]);
+        }
+        let table = builder
+            .build()
+            .with(Style::psql())
+            .with(Modify::new(Columns::single(0)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+            .with(Modify::new(Columns::single(2)).with(Alignment::left()))
+            .with(Modify::new(Columns::single(3)).with(Alignment::right()))
+            .to_string();
+
+        println!("{table}");
+    } else {
+        println!("No items to show.");
+    }
+
+    // Product keys
+    let product_keys = bundle.product_keys();
+    if !product_keys.is_empty() {
+        println!();
+        println!("Keys in this bundle:");
+        println!();
+        let mut builder = tabled::builder::Builder::default();
+        builder.set_header(["#", "Key Name", "Redeemed"]);
+
+        let mut all_redeemed = true;
+        for (idx, entry) in product_keys.iter().enumerate() {
+            builder.push_record([
+                (idx + 1).to_string().as_str(),
+                entry.human_name.as_str(),
+                if entry.redeemed { "Yes" } else { "No" },
+            ]);
+
+            if !entry.redeemed {
+                all_redeemed = false;
+            }
+        }
+
+        let table = builder
+            .build()
+            .with(Style::psql())
+            .with(Modify::new(Columns::single(0)).with(Alignment::right()))
+            .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+            .with(Modify::new(Columns::single(2)).with(Alignment::center()))
+            .to_string();
+
+        println!("{table}");
+
+        if !all_redeemed {
+            let url = "https://www.humblebundle.com/home/keys";
+            println!("Visit {url} to redeem your keys.");
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,11 @@
+use crate::util;
+use chrono::{DateTime, Utc};
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use reqwest::Client;
 use std::cmp::min;
 use std::fs::File;
-use std::io::{Seek, Write};
+use std::io::{Read, Seek, Write};
 use std::time::Duration;
 
 #[derive(Debug, thiserror::Error)]
@@ -14,40 +16,206 @@ pub enum DownloadError {
     #[error(transparent)]
     IO(#[from] std::io::Error),
 
+    #[error("the server failed with {0}")]
+    Server(reqwest::StatusCode),
+
+    #[error("the connection closed after {0} of {1} bytes")]
+    Incomplete(u64, u64),
+
     #[error("{0}")]
     Generic(String),
 }
 
+/// How often and how long to wait before a failed download is tried again.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub delay: Duration,
+    /// Double the delay after each retry, with some random jitter.
+    pub backoff: bool,
+}
+
+/// What to do when the file to download already exists.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum IfExists {
+    /// Leave the file alone, whatever its size.
+    Skip,
+    /// Continue a partial file, and skip a complete one.
+    #[default]
+    Resume,
+    /// Download the whole file again.
+    Overwrite,
+    /// Download to a new name next to the existing file.
+    Rename,
+}
+
+impl TryFrom<&str> for IfExists {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "skip" => Ok(IfExists::Skip),
+            "resume" => Ok(IfExists::Resume),
+            "overwrite" => Ok(IfExists::Overwrite),
+            "rename" => Ok(IfExists::Rename),
+            _ => Err(format!("invalid --if-exists value: {}", value)),
+        }
+    }
+}
+
+/// Options for a single file of `download_file`.
+pub struct FileOptions<'a> {
+    pub retry: &'a RetryPolicy,
+    /// `Rename` is up to the caller, by picking a `path` that doesn't exist.
+    pub if_exists: IfExists,
+    /// Compare a partial file with the server's file before resuming it, and
+    /// check the finished file against this MD5 if it's not empty.
+    pub verify: Option<&'a str>,
+    /// Download a complete file again if it changed on the server.
+    pub refresh: bool,
+    /// The last complete download of the file, from the history. With
+    /// `refresh`, it tells a complete file from a partial one.
+    pub previous: Option<Downloaded<'a>>,
+}
+
+/// A file as it was when it was last downloaded completely.
+#[derive(Clone, Copy, Debug)]
+pub struct Downloaded<'a> {
+    pub size: u64,
+    pub etag: Option<&'a str>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            delay: Duration::from_secs(5),
+            backoff: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The default policy, with the values of the settings file applied.
+    pub fn from_settings() -> Result<Self, anyhow::Error> {
+        let settings = crate::settings::get_settings()?.download;
+        let mut policy = Self::default();
+        if let Some(retries) = settings.retries {
+            policy.retries = retries;
+        }
+        if let Some(seconds) = settings.retry_delay {
+            policy.delay = Duration::from_secs(seconds);
+        }
+        policy.backoff = settings.backoff;
+        Ok(policy)
+    }
+
+    /// The delay before retry number `retry`, starting at 0.
+    fn delay(&self, retry: u32) -> Duration {
+        if !self.backoff {
+            return self.delay;
+        }
+
+        let delay = self.delay.saturating_mul(2u32.saturating_pow(retry));
+        let jitter = fastrand::u64(0..=delay.as_millis() as u64 / 4);
+        delay + Duration::from_millis(jitter)
+    }
+}
+
 impl DownloadError {
+    /// Whether the error may go away by trying again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            // `is_request` is left out, as it also covers requests that can't
+            // be built, like a bad URL.
+            DownloadError::Network(e) => {
+                e.is_connect() || e.is_timeout() || e.is_body() || e.is_decode()
+            }
+            DownloadError::IO(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::Interrupted
+            ),
+            DownloadError::Server(status) => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            DownloadError::Incomplete(..) => true,
+            DownloadError::Generic(_) => false,
+        }
+    }
+
     fn from_string(s: String) -> Self {
         DownloadError::Generic(s)
     }
 }
 
+/// Download `url` into `path`, and resume a partial file if there is one.
+/// Returns the ETag of the server's file, if it has one.
+///
+/// If the server keeps failing, `fresh_url` is called once to get a newly
+/// signed URL for the same file, which may be served by another CDN edge.
 pub async fn download_file(
     client: &Client,
     url: &str,
     path: &str,
     title: &str,
-) -> Result<(), DownloadError> {
-    const RETRY_SECONDS: u64 = 5;
-    let mut retries = 3;
+    options: &FileOptions<'_>,
+    fresh_url: &(dyn Fn() -> Option<String> + Sync),
+) -> Result<Option<String>, DownloadError> {
+    let retry = options.retry;
+    let mut url = url.to_owned();
+    let mut attempt = 0;
+    let mut refreshed = false;
 
-    loop {
-        let res = _download_file(client, url, path, title).await;
+    // Only before the first attempt, so retries still resume.
+    if std::path::Path::new(path).exists() {
+        match options.if_exists {
+            IfExists::Skip => {
+                status!("  Skipping {}, the file already exists", title);
+                return Ok(None);
+            }
+            IfExists::Overwrite => std::fs::remove_file(path)?,
+            IfExists::Resume | IfExists::Rename => {}
+        }
+    }
 
-        retries -= 1;
-        if retries < 0 {
-            return res;
+    // Partial files are still resumed, so only a complete file can be stale.
+    if options.refresh && std::path::Path::new(path).exists() {
+        let remote = get_remote_file(client, &url).await?;
+        if remote.changed_since(path, options.previous)? {
+            status!("  The file changed on the server. Downloading it again.");
+            std::fs::remove_file(path)?;
         }
+    }
+
+    loop {
+        let res = _download_file(client, &url, path, title, options).await;
 
         match res {
-            Err(DownloadError::Network(ref net_err))
-                if net_err.is_connect() || net_err.is_timeout() =>
-            {
-                println!("  Will retry in {} seconds...", RETRY_SECONDS);
-                tokio::time::sleep(Duration::from_secs(RETRY_SECONDS)).await;
-                continue;
+            Ok(etag) => {
+                check_md5(path, title, options.verify.unwrap_or_default())?;
+                return Ok(etag);
+            }
+            Err(ref e) if e.is_transient() && attempt < retry.retries => {
+                // The next attempt resumes from the bytes already written.
+                let delay = retry.delay(attempt);
+                status!("  {}. Will retry in {} seconds...", e, delay.as_secs());
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(DownloadError::Server(_)) if !refreshed => {
+                refreshed = true;
+                // Reading the bundle uses the blocking API client.
+                let Some(new_url) = tokio::task::block_in_place(fresh_url) else {
+                    return res;
+                };
+                status!("  Retrying with a new download link...");
+                url = new_url;
+                attempt = 0;
             }
             _ => return res,
         };
@@ -59,13 +227,25 @@ async fn _download_file(
     url: &str,
     path: &str,
     title: &str,
-) -> Result<(), DownloadError> {
+    options: &FileOptions<'_>,
+) -> Result<Option<String>, DownloadError> {
     let (mut file, mut downloaded) = open_file_for_write(path)?;
-    let total_size = get_content_length(client, url).await?;
+    let remote = get_remote_file(client, url).await?;
+    let total_size = remote.size;
+
+    if options.verify.is_some()
+        && downloaded > 0
+        && downloaded < total_size
+        && !prefix_matches(client, url, path, downloaded).await?
+    {
+        status!("  The partial file is different from the one on the server. Starting over.");
+        file.set_len(0)?;
+        downloaded = 0;
+    }
 
     if downloaded >= total_size {
-        println!("  Nothing to do. File already exists.");
-        return Ok(());
+        status!("  Nothing to do. File already exists.");
+        return Ok(remote.etag);
     }
 
     // Start the download
@@ -74,23 +254,80 @@ async fn _download_file(
         .header("Range", format!("bytes={}-", downloaded))
         .send()
         .await?;
+    check_server_error(&res)?;
+
+    // A server that ignores the range sends the whole file again.
+    if downloaded > 0 && res.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        status!("  The server can't resume this download. Starting over.");
+        file.set_len(0)?;
+        downloaded = 0;
+    }
 
     let mut stream = res.bytes_stream();
 
     let pb = get_progress_bar(total_size);
-    pb.set_message(format!("Downloading {}", title));
+    pb.set_message(util::with_status_prefix(format!("Downloading {}", title)));
 
     while let Some(chunk) = stream.next().await {
         let chunk = chunk?;
-        let _ = file.write(&chunk)?;
+        file.write_all(&chunk)?;
 
         downloaded = min(downloaded + (chunk.len() as u64), total_size);
         pb.set_position(downloaded);
     }
 
     pb.finish_and_clear();
-    println!("  Downloaded {}", title);
-    Ok(())
+    if downloaded < total_size {
+        return Err(DownloadError::Incomplete(downloaded, total_size));
+    }
+    status!("  Downloaded {}", title);
+    Ok(remote.etag)
+}
+
+/// Compare the start of a partial file with the same bytes on the server.
+/// Returns `true` if the server doesn't support ranges, as there's nothing
+/// to compare then.
+async fn prefix_matches(
+    client: &Client,
+    url: &str,
+    path: &str,
+    downloaded: u64,
+) -> Result<bool, DownloadError> {
+    const PREFIX_SIZE: u64 = 64 * 1024;
+    let size = min(downloaded, PREFIX_SIZE);
+
+    let res = client
+        .get(url)
+        .header("Range", format!("bytes=0-{}", size - 1))
+        .send()
+        .await?;
+    check_server_error(&res)?;
+    if res.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        return Ok(true);
+    }
+    let remote = res.bytes().await?;
+
+    let mut local = vec![0; size as usize];
+    File::open(path)?.read_exact(&mut local)?;
+    Ok(remote[..] == local[..])
+}
+
+/// Remove a finished file if its MD5 is not `expected`.
+fn check_md5(path: &str, title: &str, expected: &str) -> Result<(), DownloadError> {
+    if expected.is_empty() {
+        return Ok(());
+    }
+
+    let md5 = util::file_md5(std::path::Path::new(path))?;
+    if md5.eq_ignore_ascii_case(expected) {
+        return Ok(());
+    }
+
+    std::fs::remove_file(path)?;
+    Err(DownloadError::from_string(format!(
+        "checksum mismatch for {}. The file was removed, try downloading it again.",
+        title
+    )))
 }
 
 fn open_file_for_write(path: &str) -> Result<(File, u64), std::io::Error> {
@@ -109,15 +346,139 @@ fn open_file_for_write(path: &str) -> Result<(File, u64), std::io::Error> {
     }
 }
 
-async fn get_content_length(client: &Client, url: &str) -> Result<u64, DownloadError> {
-    let res = client.get(url).send().await?;
-    res.content_length().ok_or_else(|| {
+/// Download a small file (e.g. an image) in one go, without progress or resume support.
+pub async fn download_small_file(
+    client: &Client,
+    url: &str,
+    path: &str,
+) -> Result<(), DownloadError> {
+    let bytes = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Ask the server for the file name of `url`, using the `Content-Disposition`
+/// header of the response.
+pub async fn get_remote_filename(
+    client: &Client,
+    url: &str,
+) -> Result<Option<String>, DownloadError> {
+    let res = client.head(url).send().await?;
+    let filename = res
+        .headers()
+        .get(reqwest::header::CONTENT_DISPOSITION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(util::filename_from_content_disposition);
+    Ok(filename)
+}
+
+/// What the server tells about a file before it's downloaded.
+struct RemoteFile {
+    size: u64,
+    etag: Option<String>,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl RemoteFile {
+    /// Whether the local file at `path` is a complete copy of an older
+    /// release of this file. `previous` is its last complete download.
+    fn changed_since(
+        &self,
+        path: &str,
+        previous: Option<Downloaded>,
+    ) -> Result<bool, DownloadError> {
+        let metadata = std::fs::metadata(path)?;
+        let local_modified: DateTime<Utc> = metadata.modified()?.into();
+        Ok(self.is_newer_than(metadata.len(), local_modified, previous))
+    }
+
+    fn is_newer_than(
+        &self,
+        local_size: u64,
+        local_modified: DateTime<Utc>,
+        previous: Option<Downloaded>,
+    ) -> bool {
+        // Without a record of the download, a file smaller than the server's
+        // may be a partial one.
+        let complete = match previous {
+            Some(previous) => local_size == previous.size,
+            None => local_size >= self.size,
+        };
+        if !complete {
+            return false;
+        }
+        if local_size != self.size {
+            return true;
+        }
+
+        if let (Some(etag), Some(previous_etag)) = (&self.etag, previous.and_then(|p| p.etag)) {
+            return etag != previous_etag;
+        }
+        self.last_modified
+            .is_some_and(|last_modified| last_modified > local_modified)
+    }
+}
+
+/// Read the size and date of a file with a HEAD request. If the server
+/// doesn't answer it properly, a GET is sent instead, without reading its body.
+async fn get_remote_file(client: &Client, url: &str) -> Result<RemoteFile, DownloadError> {
+    let content_length = |res: &reqwest::Response| {
+        res.headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+
+    let res = client.head(url).send().await?;
+    check_server_error(&res)?;
+    let (res, size) = match content_length(&res) {
+        Some(size) if res.status().is_success() => (res, Some(size)),
+        _ => {
+            let res = client.get(url).send().await?;
+            check_server_error(&res)?;
+            let size = content_length(&res).or(res.content_length());
+            (res, size)
+        }
+    };
+    let size = size.ok_or_else(|| {
         DownloadError::from_string(format!("Failed to get content length from '{}'", &url))
+    })?;
+
+    let etag = res
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let last_modified = res
+        .headers()
+        .get(reqwest::header::LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        .map(|date| date.with_timezone(&Utc));
+
+    Ok(RemoteFile {
+        size,
+        etag,
+        last_modified,
     })
 }
 
+fn check_server_error(res: &reqwest::Response) -> Result<(), DownloadError> {
+    let status = res.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(DownloadError::Server(status));
+    }
+    Ok(())
+}
+
 fn get_progress_bar(total_size: u64) -> ProgressBar {
-    let pb = ProgressBar::new(total_size);
+    let pb = util::progress().add(ProgressBar::new(total_size));
     let pb_template =
         "  {msg}\n  {spinner:.green} [{elapsed}] [{bar}] {bytes} / {total_bytes} ({bytes_per_sec})";
 
@@ -129,3 +490,62 @@ fn get_progress_bar(total_size: u64) -> ProgressBar {
     );
     pb
 }
+
+#[test]
+fn test_retry_delay() {
+    let mut policy = RetryPolicy {
+        retries: 3,
+        delay: Duration::from_secs(2),
+        backoff: false,
+    };
+    assert_eq!(policy.delay(2), Duration::from_secs(2));
+
+    policy.backoff = true;
+    let delay = policy.delay(2);
+    assert!(delay >= Duration::from_secs(8) && delay <= Duration::from_secs(10));
+}
+
+#[test]
+fn test_is_transient() {
+    use std::io::{Error, ErrorKind};
+
+    assert!(DownloadError::Server(reqwest::StatusCode::BAD_GATEWAY).is_transient());
+    assert!(DownloadError::Server(reqwest::StatusCode::SERVICE_UNAVAILABLE).is_transient());
+    assert!(DownloadError::Server(reqwest::StatusCode::TOO_MANY_REQUESTS).is_transient());
+    assert!(DownloadError::Incomplete(10, 20).is_transient());
+    assert!(DownloadError::IO(Error::from(ErrorKind::ConnectionReset)).is_transient());
+    assert!(DownloadError::IO(Error::from(ErrorKind::UnexpectedEof)).is_transient());
+
+    assert!(!DownloadError::IO(Error::from(ErrorKind::PermissionDenied)).is_transient());
+    assert!(!DownloadError::Generic("checksum mismatch".to_owned()).is_transient());
+}
+
+#[test]
+fn test_remote_file_is_newer() {
+    let remote = RemoteFile {
+        size: 100,
+        etag: Some("\"v2\"".to_owned()),
+        last_modified: None,
+    };
+    let now = Utc::now();
+    let previous = |size, etag| Some(Downloaded { size, etag });
+
+    // Partial files are resumed
+    assert!(!remote.is_newer_than(40, now, None));
+    assert!(!remote.is_newer_than(40, now, previous(80, None)));
+    // Complete files of another size or ETag are stale
+    assert!(remote.is_newer_than(80, now, previous(80, None)));
+    assert!(remote.is_newer_than(120, now, None));
+    assert!(remote.is_newer_than(100, now, previous(100, Some("\"v1\""))));
+    assert!(!remote.is_newer_than(100, now, previous(100, Some("\"v2\""))));
+    assert!(!remote.is_newer_than(100, now, None));
+}
+
+#[test]
+fn test_if_exists_from_str() {
+    assert_eq!(IfExists::try_from("skip"), Ok(IfExists::Skip));
+    assert_eq!(IfExists::try_from("Resume"), Ok(IfExists::Resume));
+    assert_eq!(IfExists::try_from("overwrite"), Ok(IfExists::Overwrite));
+    assert_eq!(IfExists::try_from("rename"), Ok(IfExists::Rename));
+    assert!(IfExists::try_from("replace").is_err());
+}
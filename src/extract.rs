@@ -0,0 +1,65 @@
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Detect the archive type from a file name. Returns `None` if the file
+    /// is not an archive we know how to extract.
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        let filename = filename.to_lowercase();
+        if filename.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extract the archive at `archive_path` into `dest_dir`.
+///
+/// Entries that would end up outside of `dest_dir` (e.g. `../foo`) are
+/// rejected by the underlying libraries.
+pub fn extract_archive(
+    kind: &ArchiveKind,
+    archive_path: &Path,
+    dest_dir: &Path,
+) -> Result<(), anyhow::Error> {
+    let file = File::open(archive_path)?;
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            archive.extract(dest_dir)?;
+        }
+        ArchiveKind::TarGz => {
+            let mut archive = tar::Archive::new(GzDecoder::new(file));
+            archive.unpack(dest_dir)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_archive_kind_from_filename() {
+    let test_data = vec![
+        ("game.zip", Some(ArchiveKind::Zip)),
+        ("Game.ZIP", Some(ArchiveKind::Zip)),
+        ("game-linux.tar.gz", Some(ArchiveKind::TarGz)),
+        ("game.tgz", Some(ArchiveKind::TarGz)),
+        ("book.epub", None),
+        ("zip", None),
+    ];
+
+    for (input, expected) in test_data {
+        assert_eq!(ArchiveKind::from_filename(input), expected, "{}", input);
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::{anyhow, Context};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Lowercase file formats found in the library the last time all bundles
+/// were read. They are only used to complete `--format` values.
+pub type Formats = BTreeSet<String>;
+
+/// Read the cached formats. An empty list is returned if there is no cache,
+/// or it can't be read.
+pub fn load() -> Formats {
+    get_cache_file_name()
+        .ok()
+        .and_then(|file_name| std::fs::read_to_string(file_name).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(formats: &Formats) -> Result<(), anyhow::Error> {
+    let file_name = get_cache_file_name()?;
+    std::fs::write(&file_name, serde_json::to_string_pretty(formats)?)
+        .with_context(|| format!("failed to write `{}`", file_name.display()))
+}
+
+fn get_cache_file_name() -> anyhow::Result<PathBuf> {
+    let mut home = dirs::home_dir().ok_or_else(|| anyhow!("cannot find the home directory"))?;
+    home.push(".humble-cli-formats.json");
+    Ok(home)
+}
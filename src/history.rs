@@ -0,0 +1,264 @@
+use crate::models::DownloadInfo;
+use crate::{util, DateOptions};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+
+/// A file that was downloaded, as one line of the history file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub time: DateTime<Utc>,
+    pub bundle_key: String,
+    pub bundle_name: String,
+    pub machine_name: String,
+    pub format: String,
+    pub md5: String,
+    /// Computed locally with `download --sha256`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// ETag of the server's file, to find changed files with `--refresh`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    pub path: PathBuf,
+    pub file_size: u64,
+    /// How long the download took.
+    #[serde(default)]
+    pub seconds: Option<f64>,
+    /// Why the download failed. Files that failed are recorded too, so they
+    /// can be found with `history --failed`.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Whether the bundle key starts with `bundle`, or the bundle name
+    /// matches it as a glob pattern.
+    pub fn matches_bundle(&self, bundle: &str) -> bool {
+        self.bundle_key
+            .to_lowercase()
+            .starts_with(&bundle.to_lowercase())
+            || crate::util::glob_matches(bundle, &self.bundle_name)
+    }
+
+    /// Average download speed in bytes per second.
+    pub fn speed(&self) -> Option<u64> {
+        self.seconds
+            .filter(|&s| s > 0.0 && self.error.is_none())
+            .map(|s| (self.file_size as f64 / s) as u64)
+    }
+}
+
+/// The files that were downloaded before, to skip them with `--only-new`.
+#[derive(Debug, Default)]
+pub struct History {
+    by_md5: HashSet<(String, String)>,
+    by_format: HashSet<(String, String, String)>,
+}
+
+impl History {
+    pub fn from_entries(entries: &[HistoryEntry]) -> Self {
+        let mut history = Self::default();
+        for e in entries.iter().filter(|e| e.error.is_none()) {
+            if !e.md5.is_empty() {
+                history
+                    .by_md5
+                    .insert((e.bundle_key.clone(), e.md5.to_lowercase()));
+            }
+            history.by_format.insert((
+                e.bundle_key.clone(),
+                e.machine_name.clone(),
+                e.format.to_lowercase(),
+            ));
+        }
+        history
+    }
+
+    /// Whether a file of a bundle was downloaded before. Files are compared
+    /// by MD5, or by sub-item and format if there is no MD5.
+    pub fn contains(&self, bundle_key: &str, machine_name: &str, dl_info: &DownloadInfo) -> bool {
+        if !dl_info.md5.is_empty() {
+            return self
+                .by_md5
+                .contains(&(bundle_key.to_owned(), dl_info.md5.to_lowercase()));
+        }
+
+        self.by_format.contains(&(
+            bundle_key.to_owned(),
+            machine_name.to_owned(),
+            dl_info.format.to_lowercase(),
+        ))
+    }
+}
+
+/// The last successful download of each file, by its path.
+pub fn last_downloads(entries: Vec<HistoryEntry>) -> HashMap<PathBuf, HistoryEntry> {
+    entries
+        .into_iter()
+        .filter(|e| e.error.is_none())
+        .map(|e| (e.path.clone(), e))
+        .collect()
+}
+
+/// Read all entries of the history file. Lines that can't be read are
+/// skipped, so a partly written line doesn't make the history unusable.
+pub fn load() -> Result<Vec<HistoryEntry>, anyhow::Error> {
+    let file_name = get_history_file_name()?;
+    if !file_name.exists() {
+        return Ok(vec![]);
+    }
+
+    let file = std::fs::File::open(&file_name)
+        .with_context(|| format!("failed to read `{}`", file_name.display()))?;
+    let entries = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+    Ok(entries)
+}
+
+/// Add an entry to the end of the history file.
+pub fn append(entry: &HistoryEntry) -> Result<(), anyhow::Error> {
+    let file_name = get_history_file_name()?;
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file_name)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("failed to write `{}`", file_name.display()))
+}
+
+fn get_history_file_name() -> anyhow::Result<PathBuf> {
+    let mut home = dirs::home_dir().ok_or_else(|| anyhow!("cannot find the home directory"))?;
+    home.push(".humble-cli-history.jsonl");
+    Ok(home)
+}
+
+/// Filters of `show_history`.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryOptions {
+    /// Only files downloaded on or after this day.
+    pub since: Option<chrono::NaiveDate>,
+    /// A bundle key prefix, or a glob pattern for bundle names.
+    pub bundle: Option<String>,
+    /// Only downloads that failed.
+    pub failed: bool,
+}
+
+/// Print the history entries that pass the filters of `options`.
+pub fn show(options: &HistoryOptions, dates: &DateOptions) -> Result<(), anyhow::Error> {
+    let entries: Vec<_> = load()?
+        .into_iter()
+        .filter(|e| {
+            options
+                .since
+                .is_none_or(|since| e.time.date_naive() >= since)
+        })
+        .filter(|e| {
+            options
+                .bundle
+                .as_deref()
+                .is_none_or(|bundle| e.matches_bundle(bundle))
+        })
+        .filter(|e| !options.failed || e.error.is_some())
+        .collect();
+
+    if entries.is_empty() {
+        println!("No downloads found.");
+        return Ok(());
+    }
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Time", "Bundle", "Format", "Size", "Speed", "Path"]);
+
+    for e in &entries {
+        let speed = match (&e.error, e.speed()) {
+            (Some(_), _) => "failed".to_owned(),
+            (None, Some(speed)) => format!("{}/s", util::humanize_bytes(speed)),
+            (None, None) => "".to_owned(),
+        };
+        let path = match &e.error {
+            Some(error) => format!("{} ({})", e.path.display(), error),
+            None => e.path.display().to_string(),
+        };
+        builder.push_record([
+            dates.format(&e.time.naive_utc(), "%Y-%m-%d %H:%M"),
+            e.bundle_name.clone(),
+            e.format.clone(),
+            util::humanize_bytes(e.file_size),
+            speed,
+            path,
+        ]);
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(3)).with(Alignment::right()))
+        .with(Modify::new(Columns::single(4)).with(Alignment::right()))
+        .to_string();
+    println!("{table}");
+
+    let downloaded: Vec<_> = entries.iter().filter(|e| e.error.is_none()).collect();
+    println!();
+    println!(
+        "{} file(s) downloaded, {} in total. {} failed.",
+        downloaded.len(),
+        util::humanize_bytes(downloaded.iter().map(|e| e.file_size).sum()),
+        entries.len() - downloaded.len()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_history_contains() {
+    let entry = |md5: &str, format: &str| HistoryEntry {
+        time: Utc::now(),
+        bundle_key: "abc123".to_owned(),
+        bundle_name: "Bundle".to_owned(),
+        machine_name: "book".to_owned(),
+        format: format.to_owned(),
+        md5: md5.to_owned(),
+        sha256: None,
+        etag: None,
+        path: PathBuf::from("/tmp/book.pdf"),
+        file_size: 1000,
+        seconds: None,
+        error: None,
+    };
+    let failed = HistoryEntry {
+        error: Some("timed out".to_owned()),
+        ..entry("", "MOBI")
+    };
+    let history = History::from_entries(&[entry("ABCDEF", "PDF"), entry("", "EPUB"), failed]);
+
+    let dl_info = |md5: &str, format: &str| DownloadInfo {
+        md5: md5.to_owned(),
+        format: format.to_owned(),
+        file_size: 1000,
+        url: crate::models::DownloadUrl {
+            web: "".to_owned(),
+            bittorrent: "".to_owned(),
+        },
+    };
+    assert!(history.contains("abc123", "book", &dl_info("abcdef", "pdf")));
+    assert!(!history.contains("other", "book", &dl_info("abcdef", "pdf")));
+    assert!(!history.contains("abc123", "book", &dl_info("012345", "pdf")));
+    assert!(history.contains("abc123", "book", &dl_info("", "epub")));
+    assert!(!history.contains("abc123", "book", &dl_info("", "mobi")));
+
+    let timed = HistoryEntry {
+        seconds: Some(2.0),
+        ..entry("", "PDF")
+    };
+    assert_eq!(timed.speed(), Some(500));
+}
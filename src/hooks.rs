@@ -0,0 +1,76 @@
+use crate::settings;
+use anyhow::{anyhow, Context};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Copy, Clone, Debug)]
+pub enum HookEvent {
+    NewBundle,
+    DownloadComplete,
+    Failure,
+}
+
+/// Run the command configured for `event`, with `payload` as JSON on its
+/// stdin. The payload gets an `event` field with the name of the event.
+///
+/// Like notifications, hooks are best-effort: problems are reported on
+/// stderr, but never fail the command that ran them.
+pub fn run(event: HookEvent, mut payload: serde_json::Value) {
+    if let Some(object) = payload.as_object_mut() {
+        object.insert("event".to_owned(), event.name().into());
+    }
+
+    if let Err(e) = try_run(event, &payload) {
+        eprintln!("Warning: the {} hook failed: {:#}", event.name(), e);
+    }
+}
+
+impl HookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::NewBundle => "new_bundle",
+            HookEvent::DownloadComplete => "download_complete",
+            HookEvent::Failure => "failure",
+        }
+    }
+}
+
+fn try_run(event: HookEvent, payload: &serde_json::Value) -> Result<(), anyhow::Error> {
+    let hooks = settings::get_settings()?.hooks;
+    let command = match event {
+        HookEvent::NewBundle => hooks.on_new_bundle,
+        HookEvent::DownloadComplete => hooks.on_download_complete,
+        HookEvent::Failure => hooks.on_failure,
+    };
+    let Some(command) = command else {
+        return Ok(());
+    };
+
+    let mut child = shell_command(&command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run `{}`", command))?;
+
+    // The hook may exit without reading its stdin, so a broken pipe is fine.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = writeln!(stdin, "{}", payload);
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow!("`{}` exited with {}", command, status));
+    }
+    Ok(())
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
@@ -1,7 +1,19 @@
+mod humbler;
+
+pub use humbler::Humbler;
+
 use crate::models::*;
+use crate::net;
 use futures_util::future;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, Response};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use reqwest::Url;
 use scraper::Selector;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -9,72 +21,271 @@ pub enum ApiError {
     #[error(transparent)]
     NetworkError(#[from] reqwest::Error),
 
-    // #[error("cannot parse the response")]
-    #[error(transparent)]
-    DeserializeError(#[from] serde_json::Error),
+    #[error("unauthorized request (401). Is the session key correct?")]
+    Unauthorized,
+
+    #[error("not found (404). Is the bundle key correct?")]
+    NotFound,
+
+    #[error("too many requests (429){}", .retry_after.map(|s| format!(", retry after {} second(s)", s)).unwrap_or_default())]
+    RateLimited {
+        /// Seconds to wait, from the `Retry-After` header.
+        retry_after: Option<u64>,
+    },
+
+    #[error("server error ({status})")]
+    ServerError { status: StatusCode, body: String },
+
+    #[error("unexpected response ({status})")]
+    UnexpectedStatus { status: StatusCode, body: String },
+
+    #[error("cannot parse the response: {source}")]
+    ParseError {
+        /// The start of the response, to see what was received instead.
+        snippet: String,
+        source: serde_json::Error,
+    },
 
     #[error("cannot find any data")]
     BundleNotFound,
+
+    #[error("the request was blocked by Humble's bot protection after {attempts} attempt(s). Try again later")]
+    Blocked { attempts: u32 },
+
+    /// A write request that Humble refused, with its reason.
+    #[error("the request was rejected: {0}")]
+    Rejected(String),
+}
+
+impl ApiError {
+    fn from_status(status: StatusCode, headers: &HeaderMap, body: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ApiError::Unauthorized,
+            StatusCode::NOT_FOUND => ApiError::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+                retry_after: headers
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse().ok()),
+            },
+            s if s.is_server_error() => ApiError::ServerError { status, body },
+            _ => ApiError::UnexpectedStatus { status, body },
+        }
+    }
+}
+
+fn base_url() -> Url {
+    BASE_URL.parse().unwrap()
+}
+
+/// Return the body of a response, or the error that its status stands for.
+fn read_response(res: Response) -> Result<String, ApiError> {
+    let status = res.status();
+    if !status.is_success() {
+        let headers = res.headers().clone();
+        return Err(ApiError::from_status(
+            status,
+            &headers,
+            res.text().unwrap_or_default(),
+        ));
+    }
+
+    Ok(res.text()?)
+}
+
+async fn read_response_async(res: reqwest::Response) -> Result<String, ApiError> {
+    let status = res.status();
+    if !status.is_success() {
+        let headers = res.headers().clone();
+        return Err(ApiError::from_status(
+            status,
+            &headers,
+            res.text().await.unwrap_or_default(),
+        ));
+    }
+
+    Ok(res.text().await?)
 }
 
+/// Read the keys of all pages of `/user/order`. Large accounts get their
+/// orders in pages, while others get all of them on every page.
+///
+/// Pages are read, starting at 0, until a page has no new keys, so a server
+/// that ignores the page number costs one extra request.
+fn read_all_pages<F>(mut read_page: F) -> Result<Vec<String>, ApiError>
+where
+    F: FnMut(usize) -> Result<Vec<GameKey>, ApiError>,
+{
+    // A safety net against a server that keeps returning new keys
+    const MAX_PAGES: usize = 1000;
+
+    let mut seen = HashSet::new();
+    let mut keys = vec![];
+    for page in 0..MAX_PAGES {
+        let new_keys: Vec<String> = read_page(page)?
+            .into_iter()
+            .map(|g| g.gamekey)
+            .filter(|k| seen.insert(k.clone()))
+            .collect();
+
+        if new_keys.is_empty() {
+            break;
+        }
+        keys.extend(new_keys);
+    }
+
+    Ok(keys)
+}
+
+fn parse_json<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    const SNIPPET_LENGTH: usize = 200;
+
+    serde_json::from_str(body).map_err(|source| ApiError::ParseError {
+        snippet: body.chars().take(SNIPPET_LENGTH).collect(),
+        source,
+    })
+}
+
+const BASE_URL: &str = "https://www.humblebundle.com";
+
+const SESSION_COOKIE: &str = "_simpleauth_sess";
+
+type SessionListener = dyn Fn(&str) + Send + Sync;
+
 pub struct HumbleApi {
-    auth_key: String,
+    /// Cookies of all requests, so a session that Humble refreshes is used by
+    /// the following requests.
+    jar: Arc<Jar>,
+    session_key: Mutex<String>,
+    on_session_refresh: Option<Box<SessionListener>>,
+}
+
+impl std::fmt::Debug for HumbleApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HumbleApi")
+            .field("session_key", &crate::util::redact(&self.session_key()))
+            .finish_non_exhaustive()
+    }
+}
+
+/// A `Cookie` header with the session key. It's marked as sensitive, so it's
+/// hidden when requests are printed.
+fn session_cookie_header(cookies: &str) -> Result<HeaderValue, ApiError> {
+    let mut value = HeaderValue::from_str(cookies)
+        .map_err(|_| ApiError::Rejected("the session key has invalid characters".to_owned()))?;
+    value.set_sensitive(true);
+    Ok(value)
 }
 
 impl HumbleApi {
     pub fn new(auth_key: &str) -> Self {
+        let jar = Jar::default();
+        jar.add_cookie_str(
+            &format!("{}={}; Path=/", SESSION_COOKIE, auth_key),
+            &base_url(),
+        );
+
         Self {
-            auth_key: auth_key.to_owned(),
+            jar: Arc::new(jar),
+            session_key: Mutex::new(auth_key.to_owned()),
+            on_session_refresh: None,
         }
     }
 
+    /// Call `f` with the new session key whenever Humble refreshes the
+    /// session, e.g. to save it.
+    pub fn on_session_refresh(mut self, f: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_session_refresh = Some(Box::new(f));
+        self
+    }
+
+    /// The current session key, which can differ from the one the API was
+    /// created with if Humble refreshed it.
+    pub fn session_key(&self) -> String {
+        self.session_key.lock().unwrap().clone()
+    }
+
+    fn client(&self) -> Result<Client, ApiError> {
+        Ok(net::blocking_client_builder()
+            .cookie_provider(self.jar.clone())
+            .build()?)
+    }
+
+    fn async_client(&self) -> Result<reqwest::Client, ApiError> {
+        Ok(net::client_builder()
+            .cookie_provider(self.jar.clone())
+            .build()?)
+    }
+
+    /// Look for a session key that Humble sent back in a response.
+    fn check_session(&self) {
+        let Some(cookies) = self.jar.cookies(&base_url()) else {
+            return;
+        };
+
+        let mut session_key = self.session_key.lock().unwrap();
+        let refreshed = cookies
+            .to_str()
+            .unwrap_or_default()
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|&(name, value)| name == SESSION_COOKIE && value != *session_key);
+
+        if let Some((_, value)) = refreshed {
+            *session_key = value.to_owned();
+            if let Some(f) = &self.on_session_refresh {
+                f(value);
+            }
+        }
+    }
+
+    /// Start a session for Humble's write endpoints.
+    pub fn humbler(&self) -> Result<Humbler, ApiError> {
+        Humbler::new(&self.session_key())
+    }
+
     pub fn list_bundle_keys(&self) -> Result<Vec<String>, ApiError> {
-        let client = Client::new();
+        let client = self.client()?;
 
-        let res = client
-            .get("https://www.humblebundle.com/api/v1/user/order")
-            .header(reqwest::header::ACCEPT, "application/json")
-            .header(
-                "cookie".to_owned(),
-                format!("_simpleauth_sess={}", self.auth_key),
-            )
-            .send()?
-            .error_for_status()?;
-
-        let game_keys = res
-            .json::<Vec<GameKey>>()?
-            .into_iter()
-            .map(|g| g.gamekey)
-            .collect();
+        read_all_pages(|page| {
+            let res = client
+                .get("https://www.humblebundle.com/api/v1/user/order")
+                .header(reqwest::header::ACCEPT, "application/json")
+                .query(&[("page", page)])
+                .send()?;
+            self.check_session();
 
-        Ok(game_keys)
+            parse_json(&read_response(res)?)
+        })
     }
 
     pub fn list_bundles(&self) -> Result<Vec<Bundle>, ApiError> {
-        const CHUNK_SIZE: usize = 10;
-
-        let client = reqwest::Client::new();
         let game_keys = self.list_bundle_keys()?;
+        let mut bundles = self.read_bundles(&game_keys)?;
+        bundles.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+        Ok(bundles)
+    }
+
+    /// Read several bundles with the batch endpoint, a few per request.
+    /// Bundles are returned in no particular order, and keys that Humble
+    /// doesn't know are left out.
+    pub fn read_bundles(&self, keys: &[String]) -> Result<Vec<Bundle>, ApiError> {
+        const CHUNK_SIZE: usize = 10;
 
-        let runtime = tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()
-            .expect("cannot build the tokio runtime");
+        let client = self.async_client()?;
 
-        let futures = game_keys
+        let futures = keys
             .chunks(CHUNK_SIZE)
             .map(|keys| self.read_bundles_data(&client, keys));
 
         // Collect the Vec<Result<_,_>> into Result<Vec<_>, _>. This will automatically stop when an error is seen.
         // See https://doc.rust-lang.org/rust-by-example/error/iter_result.html#fail-the-entire-operation-with-collect
-        let result: Result<Vec<Vec<Bundle>>, _> = runtime
-            .block_on(future::join_all(futures))
-            .into_iter()
-            .collect();
+        let result: Result<Vec<Vec<Bundle>>, _> =
+            crate::util::run_future(future::join_all(futures))
+                .into_iter()
+                .collect();
 
-        let mut bundles: Vec<_> = result?.into_iter().flatten().collect();
-        bundles.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
-        Ok(bundles)
+        Ok(result?.into_iter().flatten().collect())
     }
 
     async fn read_bundles_data(
@@ -89,17 +300,13 @@ impl HumbleApi {
         let res = client
             .get("https://www.humblebundle.com/api/v1/orders")
             .header(reqwest::header::ACCEPT, "application/json")
-            .header(
-                "cookie".to_owned(),
-                format!("_simpleauth_sess={}", self.auth_key),
-            )
             .query(&query_params)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+        self.check_session();
 
-        let product_map = res.json::<BundleMap>().await?;
-        Ok(product_map.into_values().collect())
+        let product_map = parse_json::<BundleMap>(&read_response_async(res).await?)?;
+        Ok(product_map.0.into_values().collect())
     }
 
     pub fn read_bundle(&self, product_key: &str) -> Result<Bundle, ApiError> {
@@ -108,18 +315,14 @@ impl HumbleApi {
             product_key
         );
 
-        let client = Client::new();
+        let client = self.client()?;
         let res = client
             .get(url)
             .header(reqwest::header::ACCEPT, "application/json")
-            .header(
-                "cookie".to_owned(),
-                format!("_simpleauth_sess={}", self.auth_key),
-            )
-            .send()?
-            .error_for_status()?;
+            .send()?;
+        self.check_session();
 
-        res.json::<Bundle>().map_err(|e| e.into())
+        parse_json(&read_response(res)?)
     }
 
     /// Read Bundle Choices for the give month and year.
@@ -129,17 +332,11 @@ impl HumbleApi {
     pub fn read_bundle_choices(&self, when: &str) -> Result<HumbleChoice, ApiError> {
         let url = format!("https://www.humblebundle.com/membership/{}", when);
 
-        let client = Client::new();
-        let res = client
-            .get(url)
-            .header(
-                "cookie".to_owned(),
-                format!("_simpleauth_sess={}", self.auth_key),
-            )
-            .send()?
-            .error_for_status()?;
-
-        let html = res.text()?;
+        let client = self.client()?;
+        let res = client.get(url).send()?;
+        self.check_session();
+
+        let html = read_response(res)?;
         self.parse_bundle_choices(&html)
     }
 
@@ -157,9 +354,71 @@ impl HumbleApi {
             return Err(ApiError::BundleNotFound);
         }
 
-        let script = scripts.get(0).unwrap();
+        let script = scripts.first().unwrap();
         let txt = script.inner_html();
-        let obj: HumbleChoice = serde_json::from_str(&txt)?;
-        Ok(obj)
+        parse_json(&txt)
     }
 }
+
+#[test]
+fn test_api_error_from_status() {
+    let mut headers = HeaderMap::new();
+    headers.insert(reqwest::header::RETRY_AFTER, "30".parse().unwrap());
+    assert!(matches!(
+        ApiError::from_status(StatusCode::TOO_MANY_REQUESTS, &headers, String::new()),
+        ApiError::RateLimited {
+            retry_after: Some(30)
+        }
+    ));
+
+    let headers = HeaderMap::new();
+    assert!(matches!(
+        ApiError::from_status(StatusCode::UNAUTHORIZED, &headers, String::new()),
+        ApiError::Unauthorized
+    ));
+    assert!(matches!(
+        ApiError::from_status(StatusCode::BAD_GATEWAY, &headers, "oops".to_owned()),
+        ApiError::ServerError { body, .. } if body == "oops"
+    ));
+}
+
+#[test]
+fn test_read_all_pages() {
+    let pages = [
+        include_str!("../tests/fixtures/user_order_page_0.json"),
+        include_str!("../tests/fixtures/user_order_page_1.json"),
+        include_str!("../tests/fixtures/user_order_page_2.json"),
+    ];
+
+    let mut requested = vec![];
+    let keys = read_all_pages(|page| {
+        requested.push(page);
+        parse_json(pages[page])
+    })
+    .unwrap();
+    assert_eq!(keys, ["aaaa1111", "bbbb2222", "cccc3333", "dddd4444"]);
+    assert_eq!(requested, [0, 1, 2]);
+
+    // A server without pagination returns the same keys for every page
+    let keys = read_all_pages(|_| parse_json(pages[0])).unwrap();
+    assert_eq!(keys, ["aaaa1111", "bbbb2222", "cccc3333"]);
+}
+
+#[test]
+fn test_check_session() {
+    let refreshed = Arc::new(Mutex::new(vec![]));
+    let api = HumbleApi::new("old-key").on_session_refresh({
+        let refreshed = refreshed.clone();
+        move |key| refreshed.lock().unwrap().push(key.to_owned())
+    });
+
+    api.check_session();
+    assert!(refreshed.lock().unwrap().is_empty());
+
+    // What a Set-Cookie header of a response does
+    api.jar
+        .add_cookie_str("_simpleauth_sess=new-key; Path=/", &base_url());
+    api.check_session();
+    assert_eq!(api.session_key(), "new-key");
+    assert_eq!(*refreshed.lock().unwrap(), ["new-key"]);
+}
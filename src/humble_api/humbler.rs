@@ -0,0 +1,303 @@
+//! Humble's write endpoints, under `/humbler`.
+//!
+//! These need a CSRF token next to the session cookie. The token is read from
+//! the `csrf_cookie` that Humble sets on any page.
+//!
+//! The endpoints sit behind Cloudflare, which sometimes blocks requests that
+//! don't look like they come from a browser. Requests are sent with the
+//! headers of a browser, and blocked requests are retried after a random delay.
+
+use super::{parse_json, read_response, session_cookie_header, ApiError, BASE_URL};
+use crate::net;
+use reqwest::blocking::Client;
+use reqwest::header::{self, HeaderMap, HeaderValue, SET_COOKIE};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::time::Duration;
+
+const USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:131.0) Gecko/20100101 Firefox/131.0";
+
+pub struct Humbler {
+    client: Client,
+    auth_key: String,
+    csrf_token: String,
+    max_attempts: u32,
+}
+
+/// The fields that all `/humbler` responses share.
+#[derive(Debug, Deserialize)]
+struct HumblerResponse<T> {
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    errors: Option<serde_json::Value>,
+    #[serde(flatten)]
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct RedeemKeyData {
+    key: Option<String>,
+    giftkey: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Empty {}
+
+impl Humbler {
+    /// Start a session for the write endpoints, by reading a CSRF token.
+    pub fn new(auth_key: &str) -> Result<Self, ApiError> {
+        let client = net::blocking_client_builder()
+            .user_agent(USER_AGENT)
+            .default_headers(browser_headers())
+            .build()?;
+
+        let res = client
+            .get(format!("{}/home/library", BASE_URL))
+            .header(
+                header::COOKIE,
+                session_cookie_header(&format!("_simpleauth_sess={}", auth_key))?,
+            )
+            .send()?;
+
+        let csrf_token = csrf_token_from_headers(res.headers()).ok_or_else(|| {
+            ApiError::Rejected("cannot find a CSRF token. Is the session key correct?".to_owned())
+        })?;
+
+        Ok(Self {
+            client,
+            auth_key: auth_key.to_owned(),
+            csrf_token,
+            max_attempts: 3,
+        })
+    }
+
+    /// Set how many times a blocked request is sent before giving up. The
+    /// default is 3.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Reveal a key of a bundle and return it. `key_type` is the machine name
+    /// of the key, and `key_index` its position in the bundle.
+    pub fn reveal_key(
+        &self,
+        key_type: &str,
+        bundle_key: &str,
+        key_index: u32,
+    ) -> Result<String, ApiError> {
+        let key_index = key_index.to_string();
+        let data: RedeemKeyData = self.post(
+            "redeemkey",
+            &[
+                ("keytype", key_type),
+                ("key", bundle_key),
+                ("keyindex", &key_index),
+            ],
+        )?;
+
+        data.key
+            .ok_or_else(|| ApiError::Rejected("the response has no key".to_owned()))
+    }
+
+    /// Turn a key into a gift and return the link to share.
+    pub fn create_gift(
+        &self,
+        key_type: &str,
+        bundle_key: &str,
+        key_index: u32,
+    ) -> Result<String, ApiError> {
+        let key_index = key_index.to_string();
+        let data: RedeemKeyData = self.post(
+            "redeemkey",
+            &[
+                ("keytype", key_type),
+                ("key", bundle_key),
+                ("keyindex", &key_index),
+                ("gift", "true"),
+            ],
+        )?;
+
+        data.giftkey
+            .map(|key| format!("{}/gift?key={}", BASE_URL, key))
+            .ok_or_else(|| ApiError::Rejected("the response has no gift key".to_owned()))
+    }
+
+    /// Choose games of a Humble Choice month. `parent_identifier` is the
+    /// identifier of the month's content, and `chosen` those of the games.
+    pub fn choose_content(
+        &self,
+        bundle_key: &str,
+        parent_identifier: &str,
+        chosen: &[&str],
+    ) -> Result<(), ApiError> {
+        let mut form = vec![
+            ("gamekey", bundle_key),
+            ("parent_identifier", parent_identifier),
+        ];
+        form.extend(chosen.iter().map(|&id| ("chosen_identifiers[]", id)));
+
+        let _: Empty = self.post("choosecontent", &form)?;
+        Ok(())
+    }
+
+    /// Post `form` to an endpoint, and retry if the request is blocked.
+    fn post<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        form: &[(&str, &str)],
+    ) -> Result<T, ApiError> {
+        let mut attempt = 1;
+        loop {
+            match self.post_once(endpoint, form) {
+                Err(e) if is_blocked(&e) => {
+                    if attempt >= self.max_attempts {
+                        return Err(ApiError::Blocked { attempts: attempt });
+                    }
+                    std::thread::sleep(retry_delay(attempt, &e));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn post_once<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        form: &[(&str, &str)],
+    ) -> Result<T, ApiError> {
+        let res = self
+            .client
+            .post(format!("{}/humbler/{}", BASE_URL, endpoint))
+            .header(reqwest::header::ACCEPT, "application/json")
+            .header(
+                header::COOKIE,
+                session_cookie_header(&format!(
+                    "_simpleauth_sess={}; csrf_cookie={}",
+                    self.auth_key, self.csrf_token
+                ))?,
+            )
+            .header("CSRF-Prevention-Token", &self.csrf_token)
+            .form(form)
+            .send()?;
+
+        parse_humbler_response(&read_response(res)?)
+    }
+}
+
+fn browser_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::ACCEPT_LANGUAGE,
+        HeaderValue::from_static("en-US,en;q=0.5"),
+    );
+    headers.insert(header::ORIGIN, HeaderValue::from_static(BASE_URL));
+    headers.insert(
+        header::REFERER,
+        HeaderValue::from_static("https://www.humblebundle.com/home/library"),
+    );
+    headers.insert(
+        "X-Requested-With",
+        HeaderValue::from_static("XMLHttpRequest"),
+    );
+    headers
+}
+
+/// Whether the request was blocked by rate limiting or bot protection, or
+/// failed in a way that is worth another try.
+fn is_blocked(error: &ApiError) -> bool {
+    match error {
+        ApiError::RateLimited { .. } => true,
+        ApiError::UnexpectedStatus { status, body } | ApiError::ServerError { status, body } => {
+            let body = body.to_lowercase();
+            (*status == StatusCode::FORBIDDEN || *status == StatusCode::SERVICE_UNAVAILABLE)
+                && (body.contains("cloudflare") || body.contains("just a moment"))
+        }
+        ApiError::NetworkError(e) => e.is_timeout() || e.is_connect(),
+        _ => false,
+    }
+}
+
+/// Wait longer after each attempt, with some randomness so retries don't look
+/// automated. A `Retry-After` from the server is used when there is one.
+fn retry_delay(attempt: u32, error: &ApiError) -> Duration {
+    if let ApiError::RateLimited {
+        retry_after: Some(seconds),
+    } = error
+    {
+        return Duration::from_secs(*seconds);
+    }
+
+    Duration::from_millis(2000 * attempt as u64 + fastrand::u64(0..1500))
+}
+
+fn parse_humbler_response<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    let response: HumblerResponse<T> = parse_json(body)?;
+    if !response.success {
+        let errors = response
+            .errors
+            .map(|e| e.to_string())
+            .unwrap_or_else(|| "no reason given".to_owned());
+        return Err(ApiError::Rejected(errors));
+    }
+
+    Ok(response.data)
+}
+
+fn csrf_token_from_headers(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|cookie| cookie.split(';').next())
+        .find_map(|pair| pair.trim().strip_prefix("csrf_cookie="))
+        .map(str::to_owned)
+}
+
+#[test]
+fn test_csrf_token_from_headers() {
+    let mut headers = HeaderMap::new();
+    headers.append(SET_COOKIE, "_simpleauth_sess=abc; Path=/".parse().unwrap());
+    headers.append(
+        SET_COOKIE,
+        "csrf_cookie=token123; Path=/; Secure".parse().unwrap(),
+    );
+    assert_eq!(
+        csrf_token_from_headers(&headers).as_deref(),
+        Some("token123")
+    );
+
+    assert_eq!(csrf_token_from_headers(&HeaderMap::new()), None);
+}
+
+#[test]
+fn test_parse_humbler_response() {
+    let data: RedeemKeyData =
+        parse_humbler_response(r#"{"success": true, "key": "AAAA-BBBB"}"#).unwrap();
+    assert_eq!(data.key.as_deref(), Some("AAAA-BBBB"));
+
+    let result: Result<Empty, _> =
+        parse_humbler_response(r#"{"success": false, "errors": {"key": ["Invalid key"]}}"#);
+    assert!(matches!(result, Err(ApiError::Rejected(e)) if e.contains("Invalid key")));
+}
+
+#[test]
+fn test_is_blocked() {
+    let challenge = ApiError::UnexpectedStatus {
+        status: StatusCode::FORBIDDEN,
+        body: "<title>Just a moment...</title>".to_owned(),
+    };
+    assert!(is_blocked(&challenge));
+    assert!(is_blocked(&ApiError::RateLimited { retry_after: None }));
+
+    let forbidden = ApiError::UnexpectedStatus {
+        status: StatusCode::FORBIDDEN,
+        body: "{}".to_owned(),
+    };
+    assert!(!is_blocked(&forbidden));
+    assert!(!is_blocked(&ApiError::Unauthorized));
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 // All Humble Bundle bundle keys have this length
 const FULL_KEY_SIZE: usize = 16;
 
@@ -35,6 +37,96 @@ impl KeyMatch {
             .cloned()
             .collect()
     }
+
+    /// Find the keys whose bundle name has all words of `target`, or the
+    /// start of them, ignoring case and punctuation. A name that is equal to
+    /// `target` wins over names that only contain it. `names` maps bundle
+    /// keys to bundle names.
+    pub fn get_name_matches(&self, names: &BTreeMap<String, String>) -> Vec<String> {
+        let target_words = words(&self.target);
+        if target_words.is_empty() {
+            return vec![];
+        }
+
+        let named_keys = self
+            .keys
+            .iter()
+            .filter_map(|k| names.get(k).map(|name| (k, words(name))));
+        let mut exact = vec![];
+        let mut partial = vec![];
+        for (key, name_words) in named_keys {
+            if name_words == target_words {
+                exact.push(key.clone());
+            } else if target_words
+                .iter()
+                .all(|t| name_words.iter().any(|w| w.starts_with(t.as_str())))
+            {
+                partial.push(key.clone());
+            }
+        }
+
+        if exact.len() == 1 {
+            exact
+        } else {
+            exact.into_iter().chain(partial).collect()
+        }
+    }
+}
+
+/// The key in `keys` that `target` matches, like `KeyMatch::get_matches`,
+/// if there is exactly one. Unlike `KeyMatch`, `keys` isn't copied.
+pub fn single_match(keys: &[String], target: &str) -> Option<String> {
+    if KeyMatch::is_full_key(target) {
+        return Some(target.to_owned());
+    }
+
+    let lowercase_target = target.to_lowercase();
+    let mut matches = keys
+        .iter()
+        .filter(|k| k.to_lowercase().starts_with(&lowercase_target));
+    match (matches.next(), matches.next()) {
+        (Some(key), None) => Some(key.clone()),
+        _ => None,
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// A key of a bundle, given as `<bundle-key>:<number>`. The number is the
+/// one shown in the keys table of `details`, starting at 1. The bundle key
+/// can be partial.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeySelector {
+    pub bundle_key: String,
+    pub key_number: usize,
+}
+
+impl TryFrom<&str> for KeySelector {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || {
+            format!(
+                "invalid key selector, expected <bundle-key>:<number>: {}",
+                value
+            )
+        };
+        let (bundle_key, number) = value.rsplit_once(':').ok_or_else(invalid)?;
+        let key_number = number.parse::<usize>().map_err(|_| invalid())?;
+        if bundle_key.is_empty() || key_number == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            bundle_key: bundle_key.to_owned(),
+            key_number,
+        })
+    }
 }
 
 #[test]
@@ -75,3 +167,54 @@ fn test_multiple_matches() {
         vec!["1aaa".to_owned(), "1aXXX".to_owned()]
     );
 }
+
+#[test]
+fn test_name_matches() {
+    let keys = vec!["1aaa".to_owned(), "2bbb".to_owned(), "3ccc".to_owned()];
+    let names: BTreeMap<_, _> = [
+        ("1aaa", "Humble Book Bundle: Python by O'Reilly"),
+        ("2bbb", "Humble Book Bundle: Python"),
+        ("3ccc", "Humble Software Bundle: Python"),
+    ]
+    .into_iter()
+    .map(|(k, n)| (k.to_owned(), n.to_owned()))
+    .collect();
+
+    let matches = |target: &str| KeyMatch::new(keys.clone(), target).get_name_matches(&names);
+    assert_eq!(
+        matches("humble book bundle python"),
+        vec!["2bbb".to_owned()]
+    );
+    assert_eq!(
+        matches("book python"),
+        vec!["1aaa".to_owned(), "2bbb".to_owned()]
+    );
+    assert_eq!(matches("python o'reil"), vec!["1aaa".to_owned()]);
+    assert!(matches("games").is_empty());
+    assert!(matches(":").is_empty());
+}
+
+#[test]
+fn test_key_selector() {
+    let selector = KeySelector::try_from("abc123:2").unwrap();
+    assert_eq!(selector.bundle_key, "abc123");
+    assert_eq!(selector.key_number, 2);
+
+    assert!(KeySelector::try_from("abc123").is_err());
+    assert!(KeySelector::try_from("abc123:0").is_err());
+    assert!(KeySelector::try_from(":1").is_err());
+    assert!(KeySelector::try_from("abc123:x").is_err());
+}
+
+#[test]
+fn test_single_match_fn() {
+    let keys = vec!["1aaa".to_owned(), "1aXXX".to_owned(), "2bbbb".to_owned()];
+
+    assert_eq!(single_match(&keys, "2B"), Some("2bbbb".to_owned()));
+    assert_eq!(single_match(&keys, "1a"), None);
+    assert_eq!(single_match(&keys, "3"), None);
+    assert_eq!(
+        single_match(&keys, "ABCDEFGHIJKLMNOP"),
+        Some("ABCDEFGHIJKLMNOP".to_owned())
+    );
+}
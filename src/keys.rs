@@ -0,0 +1,128 @@
+use crate::humble_api::HumbleApi;
+use crate::key_match::KeySelector;
+use crate::models::{Bundle, ProductKey, Tpk, HUMBLE_KEYS_URL};
+use crate::{find_key, handle_http_errors, DateOptions};
+use anyhow::{anyhow, Context};
+
+/// Read the key that `selector` points to. `None` is returned if the bundle
+/// is not found.
+fn read_selected_key(
+    api: &HumbleApi,
+    selector: &KeySelector,
+) -> Result<Option<Tpk>, anyhow::Error> {
+    let Some(bundle_key) = find_key(
+        api,
+        handle_http_errors(api.list_bundle_keys())?,
+        &selector.bundle_key,
+    )?
+    else {
+        return Ok(None);
+    };
+
+    let bundle = handle_http_errors(api.read_bundle(&bundle_key))?;
+    let key_count = bundle.tpkd_dict.all_tpks.len();
+    let key = bundle
+        .tpkd_dict
+        .all_tpks
+        .into_iter()
+        .nth(selector.key_number - 1)
+        .ok_or_else(|| {
+            anyhow!(
+                "bundle '{}' has {} key(s), there is no key #{}",
+                bundle.details.human_name,
+                key_count,
+                selector.key_number
+            )
+        })?;
+    Ok(Some(key))
+}
+
+/// Print the key that `selector` points to, and its QR code with `qr`.
+pub fn show_key(api: &HumbleApi, selector: &KeySelector, qr: bool) -> Result<(), anyhow::Error> {
+    let Some(key) = read_selected_key(api, selector)? else {
+        return Ok(());
+    };
+
+    let Some(value) = &key.redeemed_key_val else {
+        return Err(anyhow!(
+            "'{}' is not revealed yet. Reveal it on {}",
+            key.human_name,
+            HUMBLE_KEYS_URL
+        ));
+    };
+
+    println!("{}", key.human_name);
+    println!();
+    if qr {
+        let code = qrcode::QrCode::new(value).context("failed to create the QR code")?;
+        let image = code
+            .render::<qrcode::render::unicode::Dense1x2>()
+            .quiet_zone(true)
+            .build();
+        println!("{}", image);
+        println!();
+    }
+    println!("{}", value);
+    Ok(())
+}
+
+/// Open the redeem page of the key that `selector` points to.
+pub fn open_key(api: &HumbleApi, selector: &KeySelector) -> Result<(), anyhow::Error> {
+    let Some(key) = read_selected_key(api, selector)? else {
+        return Ok(());
+    };
+
+    if !key.is_redeemed() {
+        println!("'{}' is not revealed yet.", key.human_name);
+    }
+
+    let url = key.redeem_url();
+    println!("Opening {}", url);
+    open::that(&url).with_context(|| format!("failed to open {}", url))
+}
+
+/// Print the unclaimed keys of `bundles`, oldest purchases first.
+pub fn report_unclaimed_keys(
+    mut bundles: Vec<Bundle>,
+    dates: &DateOptions,
+) -> Result<(), anyhow::Error> {
+    bundles.sort_by_key(|b| b.created);
+
+    let mut bundle_count = 0;
+    let mut key_count = 0;
+    for b in &bundles {
+        let unclaimed: Vec<ProductKey> = b
+            .product_keys()
+            .into_iter()
+            .filter(|k| !k.redeemed)
+            .collect();
+        if unclaimed.is_empty() {
+            continue;
+        }
+
+        bundle_count += 1;
+        key_count += unclaimed.len();
+
+        println!(
+            "{}  {} ({}), {} unclaimed key(s)",
+            dates.format(&b.created, "%Y-%m-%d"),
+            b.details.human_name,
+            b.gamekey,
+            unclaimed.len()
+        );
+        for k in unclaimed {
+            println!("    - {}", k.human_name);
+        }
+        println!();
+    }
+
+    if key_count == 0 {
+        println!("All keys are claimed");
+    } else {
+        println!(
+            "{} unclaimed key(s) in {} bundle(s).",
+            key_count, bundle_count
+        );
+    }
+    Ok(())
+}
@@ -1,423 +1,676 @@
+/// Print a status message of a download. It goes to stderr instead of stdout
+/// if `util::set_status_to_stderr` was called.
+macro_rules! status {
+    () => {
+        $crate::util::print_status(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::util::print_status(format_args!($($arg)*))
+    };
+}
+
+mod archive;
+mod bulk;
+mod bundle_download;
+mod calibre;
+mod check;
+mod choice;
 mod config;
+mod details;
 mod download;
+mod extract;
+mod format_cache;
+mod history;
+mod hooks;
 mod humble_api;
 mod key_match;
+mod keys;
+mod list;
 mod models;
+mod name_cache;
+mod net;
+mod notify;
+mod opds;
+mod pick;
+mod rclone;
+mod receipts;
+mod release;
+mod report;
+mod search;
+mod serve;
+mod settings;
+mod state;
+mod stats;
+mod torrent_client;
+mod tui;
 mod util;
+mod verify;
 
 pub mod prelude {
     pub use crate::auth;
+    pub use crate::auth_wizard;
+    pub use crate::check_library;
+    pub use crate::choose_all;
     pub use crate::download_bundle;
+    pub use crate::download_bundles;
+    pub use crate::download_choice;
+    pub use crate::export_receipts;
+    pub use crate::export_report;
+    pub use crate::generate_opds;
+    pub use crate::library_bundle_keys;
+    pub use crate::library_formats;
+    pub use crate::list_all_humble_choices;
     pub use crate::list_bundles;
+    pub use crate::list_formats;
     pub use crate::list_humble_choices;
+    pub use crate::list_membership_history;
+    pub use crate::notify_failure;
+    pub use crate::open_key;
+    pub use crate::pick;
+    pub use crate::print_error_json;
+    pub use crate::print_version;
+    pub use crate::read_bulk_input;
+    pub use crate::redact_session_key;
+    pub use crate::report_unclaimed_keys;
     pub use crate::search;
+    pub use crate::serve_library;
     pub use crate::show_bundle_details;
-
-    pub use crate::humble_api::{ApiError, HumbleApi};
+    pub use crate::show_history;
+    pub use crate::show_key;
+    pub use crate::show_stats;
+    pub use crate::tui;
+    pub use crate::verify_downloads;
+    pub use crate::DateOptions;
+
+    pub use crate::bulk::{BulkDownloadOptions, BulkEntry};
+    pub use crate::bundle_download::DownloadOptions;
+    pub use crate::download::{IfExists, RetryPolicy};
+    pub use crate::history::HistoryOptions;
+    pub use crate::humble_api::{ApiError, HumbleApi, Humbler};
+    pub use crate::key_match::KeySelector;
     pub use crate::models::*;
+    pub use crate::net::{set_net_options, IpVersion, NetOptions};
+    pub use crate::receipts::ReceiptFormat;
+    pub use crate::report::ReportFormat;
+    pub use crate::search::{SearchOptions, SEARCH_FIELDS};
     pub use crate::util::byte_string_to_number;
+    pub use crate::util::set_errors_json;
+    pub use crate::util::set_non_interactive;
 }
 
 use anyhow::{anyhow, Context};
 use config::{get_config, set_config, Config};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Password};
 use humble_api::{ApiError, HumbleApi};
 use key_match::KeyMatch;
 use prelude::*;
+use settings::NotifyEvent;
 use std::fs;
 use std::path;
-use std::time::Duration;
-use tabled::settings::object::Columns;
-use tabled::settings::Alignment;
-use tabled::settings::Merge;
-use tabled::settings::Modify;
-use tabled::settings::Style;
-
 
+/// Check the session key with the API, then save it, so a wrong or truncated
+/// key is never saved. A whole pasted cookie or `Cookie` header is accepted
+/// too.
 pub fn auth(session_key: &str) -> Result<(), anyhow::Error> {
-    set_config(Config {
-        session_key: session_key.to_owned(),
+    let session_key = util::clean_session_key(session_key);
+    if session_key.is_empty() {
+        return Err(anyhow!("the session key is empty"));
+    }
+
+    let config = Config { session_key };
+    println!("Checking the session key...");
+    let keys = handle_http_errors(humble_api(&config).list_bundle_keys())
+        .context("the session key doesn't work, so it was not saved")?;
+    set_config(config)?;
+    println!(
+        "The session key works. Found {} bundle(s) in your library.",
+        keys.len()
+    );
+    Ok(())
+}
+
+/// Walk the user through finding the session key in their web browser, then
+/// save it once it has been checked against the API.
+pub fn auth_wizard() -> Result<(), anyhow::Error> {
+    ensure_interactive("auth --wizard")?;
+    println!("humble-cli needs the value of the `_simpleauth_sess` cookie to access your library.");
+    println!();
+    println!("1. Log in to https://www.humblebundle.com in your web browser.");
+    println!("2. Open the developer tools with F12 (Cmd+Option+I on macOS), then:");
+    println!("   - Chrome, Edge, Brave: Application tab > Cookies > https://www.humblebundle.com");
+    println!("   - Firefox: Storage tab > Cookies > https://www.humblebundle.com");
+    println!("   - Safari: enable the Develop menu first, then Storage tab > Cookies");
+    println!("3. Copy the value of the `_simpleauth_sess` cookie and paste it below.");
+    println!();
+
+    let theme = ColorfulTheme::default();
+    loop {
+        let input = Password::with_theme(&theme)
+            .with_prompt("Session key")
+            .allow_empty_password(true)
+            .interact()?;
+
+        let session_key = util::clean_session_key(&input);
+        if session_key.is_empty() {
+            return Err(anyhow!("no session key entered"));
+        }
+
+        println!("Checking the session key...");
+        let api = HumbleApi::new(&session_key);
+        match handle_http_errors(api.list_bundle_keys()) {
+            Ok(keys) => {
+                set_config(Config { session_key })?;
+                println!("Done! Found {} bundle(s) in your library.", keys.len());
+                return Ok(());
+            }
+            Err(e) => {
+                println!("{}", e);
+                let retry = Confirm::with_theme(&theme)
+                    .with_prompt("Try another key?")
+                    .default(true)
+                    .interact()?;
+                if !retry {
+                    return Err(anyhow!("the session key was not saved"));
+                }
+            }
+        }
+    }
+}
+
+/// Fail early for commands that can't do anything without asking the user.
+fn ensure_interactive(command: &str) -> Result<(), anyhow::Error> {
+    if util::interactive() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "`{}` asks questions, so it needs a terminal and can't be used with --yes",
+        command
+    ))
+}
+
+/// The API client for the saved session. A session that Humble refreshes is
+/// saved right away, so it doesn't expire in the middle of a long run.
+fn humble_api(config: &Config) -> HumbleApi {
+    HumbleApi::new(&config.session_key).on_session_refresh(|session_key| {
+        let config = Config {
+            session_key: session_key.to_owned(),
+        };
+        if let Err(e) = set_config(config) {
+            eprintln!("Warning: failed to save the refreshed session key: {:#}", e);
+        }
     })
 }
 
 pub fn handle_http_errors<T>(input: Result<T, ApiError>) -> Result<T, anyhow::Error> {
     match input {
         Ok(val) => Ok(val),
-        Err(ApiError::NetworkError(e)) if e.is_status() => match e.status().unwrap() {
-            reqwest::StatusCode::UNAUTHORIZED => Err(anyhow!(
-                "Unauthorized request (401). Is the session key correct?"
-            )),
-            reqwest::StatusCode::NOT_FOUND => Err(anyhow!(
-                "Bundle not found (404). Is the bundle key correct?"
-            )),
-            s => Err(anyhow!("failed with status: {}", s)),
-        },
-        Err(e) => Err(anyhow!("failed: {}", e)),
+        Err(e @ (ApiError::NetworkError(_) | ApiError::BundleNotFound)) => {
+            Err(anyhow!("failed: {}", e))
+        }
+        Err(e) => Err(e.into()),
     }
 }
 
 pub fn list_humble_choices(period: &ChoicePeriod) -> Result<(), anyhow::Error> {
     let config = get_config()?;
-    let api = HumbleApi::new(&config.session_key);
-
-    let choices = api.read_bundle_choices(&period.to_string())?;
+    choice::list_choices(&humble_api(&config), period)
+}
 
-    println!();
-    println!("{}", choices.options.title);
-    println!();
+/// Choose all games of a Humble Choice month that can still be chosen, up to
+/// the month's limit. With `dry_run`, only print what would be chosen.
+pub fn choose_all(period: &ChoicePeriod, dry_run: bool) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    choice::choose_all(&humble_api(&config), period, dry_run)
+}
 
-    let options = choices.options;
+/// List the Humble Choice and Humble Monthly months in the library, oldest
+/// first, so months that were never opened can be found.
+pub fn list_membership_history(dates: &DateOptions) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
 
-    let mut builder = tabled::builder::Builder::default();
-    builder.set_header(["#", "Title", "Redeemed"]);
+    let bundles = read_all_bundles(&api)?;
+    choice::list_months(bundles, dates)
+}
 
-    let mut counter = 1;
-    let mut all_redeemed = true;
-    for (_, game_data) in options.data.game_data.iter() {
-        for tpkd in game_data.tpkds.iter() {
-            builder.push_record([
-                counter.to_string().as_str(),
-                tpkd.human_name.as_str(),
-                tpkd.claim_status().to_string().as_str(),
-            ]);
+/// Show a summary of the keys of every Humble Choice month the user was
+/// subscribed to, to find months with unclaimed keys.
+pub fn list_all_humble_choices() -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    choice::list_all_choices(&humble_api(&config))
+}
 
-            counter += 1;
+/// How purchase dates are shown. The API gives them in UTC, and they are
+/// shown in the local time zone unless `utc` is set.
+#[derive(Clone, Debug, Default)]
+pub struct DateOptions {
+    /// A `strftime`-like format. `None` means the default of the command.
+    pub format: Option<String>,
+    pub utc: bool,
+}
 
-            if tpkd.claim_status() == ClaimStatus::No {
-                all_redeemed = false;
-            }
+impl DateOptions {
+    fn format(&self, date: &chrono::NaiveDateTime, default_format: &str) -> String {
+        let format = self.format.as_deref().unwrap_or(default_format);
+        let date = date.and_utc();
+        if self.utc {
+            date.format(format).to_string()
+        } else {
+            date.with_timezone(&chrono::Local)
+                .format(format)
+                .to_string()
         }
     }
+}
 
-    let table = builder
-        .build()
-        .with(Style::psql())
-        .with(Modify::new(Columns::single(0)).with(Alignment::right()))
-        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
-        .to_string();
-
-    println!("{table}");
+pub fn search(keywords: &str, options: &SearchOptions) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
 
-    if !all_redeemed {
-        let url = "https://www.humblebundle.com/membership/home";
-        println!("Visit {url} to redeem your keys.");
-    }
-    Ok(())
+    let bundles = read_all_bundles(&api)?;
+    search::search(&bundles, keywords, options)
 }
 
-pub fn search(keywords: &str, match_mode: MatchMode) -> Result<(), anyhow::Error> {
+/// List the bundles in the library. Store purchases are only listed with
+/// `include_store`.
+pub fn list_bundles(
+    id_only: bool,
+    claimed_filter: &str,
+    include_store: bool,
+    group_by_series: bool,
+    dates: &DateOptions,
+) -> Result<(), anyhow::Error> {
     let config = get_config()?;
-    let api = HumbleApi::new(&config.session_key);
+    let api = humble_api(&config);
+
+    list::list_bundles(
+        &api,
+        id_only,
+        claimed_filter,
+        include_store,
+        group_by_series,
+        dates,
+    )
+}
 
-    let keywords = keywords.to_lowercase();
-    let keywords: Vec<&str> = keywords.split(" ").collect();
+pub fn export_report(format: ReportFormat, out: Option<&str>) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
 
-    let bundles = handle_http_errors(api.list_bundles())?;
-    type BundleItem<'a> = (&'a Bundle, String);
-    let mut search_result: Vec<BundleItem> = vec![];
+    let bundles = read_all_bundles(&api)?;
+    let report = report::render(format, &bundles);
 
-    for b in &bundles {
-        for p in &b.products {
-            if p.name_matches(&keywords, &match_mode) {
-                search_result.push((b, p.human_name.to_owned()));
-            }
+    match out {
+        Some(path) => {
+            fs::write(path, report).with_context(|| format!("failed to write to '{}'", path))?;
+            println!("Report for {} bundle(s) written to {}", bundles.len(), path);
         }
+        None => print!("{}", report),
     }
 
-    if search_result.is_empty() {
-        println!("Nothing found");
-        return Ok(());
-    }
+    Ok(())
+}
 
-    let mut builder = tabled::builder::Builder::default();
-    builder.set_header(["Key", "Name", "Sub Item"]);
-    for record in search_result {
-        builder.push_record([
-            record.0.gamekey.as_str(),
-            record.0.details.human_name.as_str(),
-            record.1.as_str(),
-        ]);
+/// Write an OPDS catalog of the e-books in `library_dir` to `opds.xml` in
+/// that directory, so e-reader apps can browse and download them.
+pub fn generate_opds(library_dir: &str) -> Result<(), anyhow::Error> {
+    let library_dir = path::Path::new(library_dir);
+    let books = opds::find_books(library_dir)
+        .with_context(|| format!("failed to read '{}'", library_dir.display()))?;
+    if books.is_empty() {
+        return Err(anyhow!(
+            "no e-books found. Download them with --write-info-json first."
+        ));
     }
 
-    let table = builder
-        .build()
-        .with(Style::psql())
-        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
-        .with(Modify::new(Columns::single(2)).with(Alignment::left()))
-        .with(Merge::vertical())
-        .to_string();
-
-    println!("{table}");
+    let feed_path = library_dir.join("opds.xml");
+    fs::write(&feed_path, opds::render_feed(&books, chrono::Utc::now()))
+        .with_context(|| format!("failed to write to '{}'", feed_path.display()))?;
+    println!(
+        "Catalog of {} file(s) written to {}",
+        books.len(),
+        feed_path.display()
+    );
     Ok(())
 }
 
-pub fn list_bundles(id_only: bool, claimed_filter: &str) -> Result<(), anyhow::Error> {
+/// Serve the cached library and the downloaded files in `library_dir` over
+/// HTTP, so other devices can browse and download them.
+pub fn serve_library(library_dir: &str, address: &str, port: u16) -> Result<(), anyhow::Error> {
+    serve::serve(path::Path::new(library_dir), address, port)
+        .with_context(|| format!("failed to serve '{}'", library_dir))
+}
+
+/// Export all orders with their date, amount and reference, e.g. for
+/// personal accounting.
+pub fn export_receipts(format: ReceiptFormat, out: Option<&str>) -> Result<(), anyhow::Error> {
     let config = get_config()?;
-    let api = HumbleApi::new(&config.session_key);
-
-    // If no filter is required, we can do a single call
-    // and finish quickly. Otherwise we will need to fetch
-    // all bundle data and filter them.
-    if id_only && claimed_filter == "all" {
-        let ids = handle_http_errors(api.list_bundle_keys())?;
-        for id in ids {
-            println!("{}", id);
+    let api = humble_api(&config);
+
+    let bundles = read_all_bundles(&api)?;
+    let receipts = receipts::render(format, &bundles)?;
+
+    match out {
+        Some(path) => {
+            fs::write(path, receipts).with_context(|| format!("failed to write to '{}'", path))?;
+            println!(
+                "Receipts for {} order(s) written to {}",
+                bundles.len(),
+                path
+            );
         }
-
-        return Ok(());
+        None => print!("{}", receipts),
     }
 
-    let mut bundles = handle_http_errors(api.list_bundles())?;
+    Ok(())
+}
 
-    if claimed_filter != "all" {
-        let claimed = claimed_filter == "yes";
-        bundles.retain(|b| {
-            let status = b.claim_status();
-            status == ClaimStatus::Yes && claimed || status == ClaimStatus::No && !claimed
-        });
-    }
+/// Show the files in the download history, oldest first.
+pub fn show_history(options: &HistoryOptions, dates: &DateOptions) -> Result<(), anyhow::Error> {
+    history::show(options, dates)
+}
 
-    if id_only {
-        for b in bundles {
-            println!("{}", b.gamekey);
-        }
+/// Check the files in the download history against their SHA-256, or MD5 if
+/// there's none, `jobs` files at a time.
+pub fn verify_downloads(bundle: Option<&str>, jobs: usize) -> Result<(), anyhow::Error> {
+    verify::verify_downloads(bundle, jobs)
+}
 
-        return Ok(());
+/// Check whether a product passes the format and size filters. Empty
+/// `formats` or a `max_size` of `0` disable the respective filter.
+fn product_passes_filters(product: &Product, formats: &[String], max_size: u64) -> bool {
+    (max_size == 0 || product.total_size() < max_size)
+        && (formats.is_empty() || util::str_vectors_intersect(&product.formats_as_vec(), formats))
+}
+
+/// Check whether a bundle passes the claimed filter, which is one of `all`, `yes` or `no`.
+fn bundle_passes_claimed_filter(bundle: &Bundle, claimed_filter: &str) -> bool {
+    if claimed_filter == "all" {
+        return true;
     }
 
-    println!("{} bundle(s) found.\n", bundles.len());
+    let claimed = claimed_filter == "yes";
+    let status = bundle.claim_status();
+    status == ClaimStatus::Yes && claimed || status == ClaimStatus::No && !claimed
+}
 
-    if bundles.is_empty() {
-        return Ok(());
+/// Print totals for the whole library: bundles, items, size, keys and the
+/// size of the purchases in each year. With `by_format`, the size of each
+/// file format is shown too. With `largest`, that many of the biggest
+/// sub-items are listed.
+pub fn show_stats(by_format: bool, largest: Option<usize>) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
+
+    let bundles = read_all_bundles(&api)?;
+    stats::show_stats(&bundles, by_format, largest)
+}
+
+/// Compare the library with the state saved by the previous run and print
+/// the changes as JSON. Returns `true` if anything changed.
+///
+/// The first run only saves the current state.
+pub fn check_library() -> Result<bool, anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
+
+    let bundles = read_all_bundles(&api)?;
+    check::check_library(&bundles)
+}
+
+/// Send a notification and run the `on_failure` hook about a failed command,
+/// if enabled in the settings.
+pub fn notify_failure(error: &anyhow::Error) {
+    notify::notify(
+        NotifyEvent::Failure,
+        &redact_session_key(&format!("{} failed: {:#}", clap::crate_name!(), error)),
+    );
+    hooks::run(
+        hooks::HookEvent::Failure,
+        serde_json::json!({ "error": redact_session_key(&format!("{:#}", error)) }),
+    );
+}
+
+/// Print a failure as a single-line JSON object on stderr, if enabled with
+/// `--errors-json`.
+pub fn print_error_json(bundle_key: Option<&str>, error: &anyhow::Error) {
+    if !util::errors_json() {
+        return;
     }
 
-    let mut builder = tabled::builder::Builder::default();
-    builder.set_header(["Key", "Name", "Size", "Claimed"]);
+    let mut failure = match bundle_key {
+        Some(key) => bulk::Failure::new(key, error),
+        None => bulk::Failure::from_error(error),
+    };
+    failure.error = redact_session_key(&failure.error);
+    if let Ok(json) = serde_json::to_string(&failure) {
+        eprintln!("{}", json);
+    }
+}
 
-    for p in bundles {
-        builder.push_record([
-            p.gamekey.as_str(),
-            p.details.human_name.as_str(),
-            util::humanize_bytes(p.total_size()).as_str(),
-            p.claim_status().to_string().as_str(),
-        ]);
+/// Mask the saved session key wherever it appears in `text`, e.g. an error
+/// that is printed or sent somewhere.
+pub fn redact_session_key(text: &str) -> String {
+    match get_config() {
+        Ok(config) if !config.session_key.is_empty() => {
+            text.replace(&config.session_key, &util::redact(&config.session_key))
+        }
+        _ => text.to_owned(),
     }
+}
+
+/// List unclaimed keys grouped by bundle, oldest purchases first.
+pub fn report_unclaimed_keys(dates: &DateOptions) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
 
-    let table = builder
-        .build()
-        .with(Style::psql())
-        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
-        .with(Modify::new(Columns::single(2)).with(Alignment::right()))
-        .to_string();
-    println!("{table}");
+    let bundles = read_all_bundles(&api)?;
+    keys::report_unclaimed_keys(bundles, dates)
+}
 
+/// List all file formats in the library with the number of files and their
+/// total size.
+pub fn list_formats() -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
+
+    let bundles = read_all_bundles(&api)?;
+    stats::list_formats(&bundles);
     Ok(())
 }
 
-fn find_key(all_keys: Vec<String>, key_to_find: &str) -> Option<String> {
-    let key_match = KeyMatch::new(all_keys, key_to_find);
-    let keys = key_match.get_matches();
+/// Let the user pick a bundle (and optionally one of its items) with a fuzzy
+/// finder, then show its details or download it.
+pub fn pick() -> Result<(), anyhow::Error> {
+    ensure_interactive("pick")?;
+    let config = get_config()?;
+    let api = humble_api(&config);
 
-    match keys.len() {
-        1 => Some(keys[0].clone()),
-        0 => {
-            eprintln!("No bundle matches '{}'", key_to_find);
-            None
-        }
-        _ => {
-            eprintln!("More than one bundle matches '{}':", key_to_find);
-            for key in keys {
-                eprintln!("{}", key);
-            }
-            None
-        }
+    let bundles = read_all_bundles(&api)?;
+    pick::pick(&bundles)
+}
+
+/// One-line description of a sub-item for interactive prompts.
+fn product_summary(product: &Product) -> String {
+    format!(
+        "{} [{}] {}",
+        product.human_name,
+        product.formats(),
+        util::humanize_bytes(product.total_size())
+    )
+}
+
+/// All file formats found in the library, lowercase and sorted. They come
+/// from the cache, so the list is empty until the bundles have been read once.
+pub fn library_formats() -> Vec<String> {
+    format_cache::load().into_iter().collect()
+}
+
+/// Read all bundles, and remember the file formats in them for completing
+/// `--format` values.
+fn read_all_bundles(api: &HumbleApi) -> Result<Vec<Bundle>, anyhow::Error> {
+    let bundles = handle_http_errors(api.list_bundles())?;
+    let formats = bundles
+        .iter()
+        .flat_map(|b| &b.products)
+        .flat_map(|p| p.formats_as_vec())
+        .map(str::to_lowercase)
+        .collect();
+    if let Err(e) = format_cache::save(&formats) {
+        eprintln!("Warning: {:#}", e);
     }
+    Ok(bundles)
 }
 
-pub fn show_bundle_details(bundle_key: &str) -> Result<(), anyhow::Error> {
+/// Print the current version and, if `check` is set, whether a newer release
+/// is available on GitHub.
+pub fn print_version(check: bool) -> Result<(), anyhow::Error> {
+    release::print_version(check)
+}
+
+/// Browse the library in a terminal UI, mark items and download them.
+pub fn tui() -> Result<(), anyhow::Error> {
+    ensure_interactive("tui")?;
     let config = get_config()?;
-    let api = crate::HumbleApi::new(&config.session_key);
+    let api = humble_api(&config);
 
-    let bundle_key = match find_key(handle_http_errors(api.list_bundle_keys())?, bundle_key) {
-        Some(key) => key,
-        None => return Ok(()),
-    };
+    let bundles = read_all_bundles(&api)?;
+    tui::run(bundles)
+}
 
-    let bundle = handle_http_errors(api.read_bundle(&bundle_key))?;
+/// Print the value of a revealed key, optionally as a QR code that can be
+/// scanned from a phone or console.
+pub fn show_key(selector: &KeySelector, qr: bool) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    keys::show_key(&humble_api(&config), selector, qr)
+}
 
-    println!();
-    println!("{}", bundle.details.human_name);
-    println!();
-    println!("Purchased  : {}", bundle.created.format("%v %I:%M %p"));
-    println!("Total size : {}", util::humanize_bytes(bundle.total_size()));
-    println!();
+/// Open the page where a key is redeemed in the browser.
+pub fn open_key(selector: &KeySelector) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    keys::open_key(&humble_api(&config), selector)
+}
 
-    if !bundle.products.is_empty() {
-        let mut builder = tabled::builder::Builder::default();
-        builder.set_header(["#", "Sub-item", "Format", "Total Size"]);
-
-        for (idx, entry) in bundle.products.iter().enumerate() {
-            builder.push_record([
-                &(idx + 1).to_string(),
-                &entry.human_name,
-                &entry.formats(),
-                &util::humanize_bytes(entry.total_size()),
-            ]);
-        }
-        let table = builder
-            .build()
-            .with(Style::psql())
-            .with(Modify::new(Columns::single(0)).with(Alignment::right()))
-            .with(Modify::new(Columns::single(1)).with(Alignment::left()))
-            .with(Modify::new(Columns::single(2)).with(Alignment::left()))
-            .with(Modify::new(Columns::single(3)).with(Alignment::right()))
-            .to_string();
-
-        println!("{table}");
-    } else {
-        println!("No items to show.");
+/// Find the single bundle that `key_to_find` points to. It can be a bundle
+/// key, the start of one, or words of a bundle name. If no single bundle
+/// matches, the reason is printed and `None` is returned.
+fn find_key(
+    api: &HumbleApi,
+    all_keys: Vec<String>,
+    key_to_find: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let key_match = KeyMatch::new(all_keys.clone(), key_to_find);
+    let mut keys = key_match.get_matches();
+
+    // Bundle names are only needed if the key doesn't match
+    let mut names = name_cache::BundleNames::new();
+    if keys.is_empty() {
+        names = bundle_names(api, &all_keys)?;
+        keys = key_match.get_name_matches(&names);
     }
 
-    // Product keys
-    let product_keys = bundle.product_keys();
-    if !product_keys.is_empty() {
-        println!();
-        println!("Keys in this bundle:");
-        println!();
-        let mut builder = tabled::builder::Builder::default();
-        builder.set_header(["#", "Key Name", "Redeemed"]);
-
-        let mut all_redeemed = true;
-        for (idx, entry) in product_keys.iter().enumerate() {
-            builder.push_record([
-                (idx + 1).to_string().as_str(),
-                entry.human_name.as_str(),
-                if entry.redeemed { "Yes" } else { "No" },
-            ]);
-
-            if !entry.redeemed {
-                all_redeemed = false;
+    match keys.len() {
+        1 => Ok(Some(keys[0].clone())),
+        0 => {
+            eprintln!("No bundle matches '{}'", key_to_find);
+            Ok(None)
+        }
+        _ => {
+            eprintln!("More than one bundle matches '{}':", key_to_find);
+            for key in keys {
+                match names.get(&key) {
+                    Some(name) => eprintln!("{}  {}", key, name),
+                    None => eprintln!("{}", key),
+                }
             }
+            Ok(None)
         }
+    }
+}
 
-        let table = builder
-            .build()
-            .with(Style::psql())
-            .with(Modify::new(Columns::single(0)).with(Alignment::right()))
-            .with(Modify::new(Columns::single(1)).with(Alignment::left()))
-            .with(Modify::new(Columns::single(2)).with(Alignment::center()))
-            .to_string();
-
-        println!("{table}");
-
-        if !all_redeemed {
-            let url = "https://www.humblebundle.com/home/keys";
-            println!("Visit {url} to redeem your keys.");
-        }
+/// Names of all bundles in `all_keys`. They are read from the cache, and
+/// the cache is updated if it misses any bundle.
+fn bundle_names(
+    api: &HumbleApi,
+    all_keys: &[String],
+) -> Result<name_cache::BundleNames, anyhow::Error> {
+    let names = name_cache::load();
+    if all_keys.iter().all(|k| names.contains_key(k)) {
+        return Ok(names);
     }
 
-    Ok(())
+    let names = read_all_bundles(api)?
+        .into_iter()
+        .map(|b| (b.gamekey, b.details.human_name))
+        .collect();
+    if let Err(e) = name_cache::save(&names) {
+        eprintln!("Warning: {:#}", e);
+    }
+    Ok(names)
 }
 
-pub fn download_bundle(
+/// Show the details of a bundle. Sub-items are sorted by `sort`, and keep
+/// their number for `--item-numbers`.
+pub fn show_bundle_details(
     bundle_key: &str,
-    formats: Vec<String>,
-    max_size: u64,
-    item_numbers: Option<&str>,
+    dates: &DateOptions,
+    sort: Option<ItemSort>,
 ) -> Result<(), anyhow::Error> {
     let config = get_config()?;
+    details::show_bundle_details(&humble_api(&config), bundle_key, dates, sort)
+}
 
-    let api = crate::HumbleApi::new(&config.session_key);
+pub fn download_bundle(bundle_key: &str, options: &DownloadOptions) -> Result<(), anyhow::Error> {
+    util::set_status_to_stderr(options.print_paths);
+    let config = get_config()?;
 
-    let bundle_key = match find_key(handle_http_errors(api.list_bundle_keys())?, bundle_key) {
+    let api = humble_api(&config);
+
+    let bundle_key = match find_key(
+        &api,
+        handle_http_errors(api.list_bundle_keys())?,
+        bundle_key,
+    )? {
         Some(key) => key,
         None => return Ok(()),
     };
 
     let bundle = handle_http_errors(api.read_bundle(&bundle_key))?;
+    bundle_download::download_bundle_files(&bundle, options, &bundle_download::download_client()?)
+}
 
-    // To parse the item number ranges, we need to know the max value
-    // for unbounded ranges (e.g. 12-). That's why we parse this argument
-    // after we read the bundle from the API.
-    let item_numbers = if let Some(value) = item_numbers {
-        let ranges = value.split(',').collect::<Vec<_>>();
-        util::union_usize_ranges(&ranges, bundle.products.len())?
-    } else {
-        vec![]
-    };
-
-    // Filter products based on entered criteria
-    // Note that item numbers entered by user start at 1, while our index
-    // starts as 0.
-    let products = bundle
-        .products
-        .iter()
-        .enumerate()
-        .filter(|&(i, _)| item_numbers.is_empty() || item_numbers.contains(&(i + 1)))
-        .map(|(_, p)| p)
-        .filter(|p| max_size == 0 || p.total_size() < max_size)
-        .filter(|p| {
-            formats.is_empty() || util::str_vectors_intersect(&p.formats_as_vec(), &formats)
-        })
-        .collect::<Vec<_>>();
-
-    if products.is_empty() {
-        println!("Nothing to download");
-        return Ok(());
-    }
-
-    // Create the bundle directory
-    let dir_name = util::replace_invalid_chars_in_filename(&bundle.details.human_name);
-    let bundle_dir = create_dir(&dir_name)?;
-
-    let http_read_timeout = Duration::from_secs(30);
-    let client = reqwest::Client::builder()
-        .read_timeout(http_read_timeout)
-        .build()?;
-
-    for product in products {
-        if max_size > 0 && product.total_size() > max_size {
-            continue;
-        }
-
-        println!();
-        println!("{}", product.human_name);
-
-        let dir_name = util::replace_invalid_chars_in_filename(&product.human_name);
-        let entry_dir = bundle_dir.join(dir_name);
-        if !entry_dir.exists() {
-            fs::create_dir(&entry_dir)?;
-        }
-
-        for product_download in product.downloads.iter() {
-            for dl_info in product_download.items.iter() {
-                if !formats.is_empty() && !formats.contains(&dl_info.format.to_lowercase()) {
-                    println!("Skipping '{}'", dl_info.format);
-                    continue;
-                }
+/// Download the DRM-free games of a Humble Choice month. They are in the
+/// month's order, which only has the games that were chosen.
+pub fn download_choice(
+    period: &ChoicePeriod,
+    options: &DownloadOptions,
+) -> Result<(), anyhow::Error> {
+    util::set_status_to_stderr(options.print_paths);
+    let config = get_config()?;
+    bundle_download::download_choice(&humble_api(&config), period, options)
+}
 
-                let filename = util::extract_filename_from_url(&dl_info.url.web).context(
-                    format!("Cannot get file name from URL '{}'", &dl_info.url.web),
-                )?;
-                let download_path = entry_dir.join(&filename);
-
-                let f = download::download_file(
-                    &client,
-                    &dl_info.url.web,
-                    download_path.to_str().unwrap(),
-                    &filename,
-                );
-                util::run_future(f)?;
-            }
-        }
-    }
+/// Read the bundles to download from a file, one per line, or from stdin if
+/// `path` is `-`.
+pub fn read_bulk_input(path: &str) -> Result<Vec<BulkEntry>, anyhow::Error> {
+    bulk::read_input(path)
+}
 
-    Ok(())
+/// Keys of all bundles in the library.
+pub fn library_bundle_keys() -> Result<Vec<String>, anyhow::Error> {
+    let config = get_config()?;
+    let api = humble_api(&config);
+    handle_http_errors(api.list_bundle_keys())
 }
 
-fn create_dir(dir: &str) -> Result<path::PathBuf, std::io::Error> {
-    let dir = path::Path::new(dir).to_owned();
-    if !dir.exists() {
-        fs::create_dir(&dir)?;
-    }
-    Ok(dir)
+/// Download several bundles. The settings of each entry override `options`.
+/// A failed bundle doesn't stop the others. All failures are reported at the end.
+pub fn download_bundles(
+    entries: &[BulkEntry],
+    options: &DownloadOptions,
+    bulk_options: &BulkDownloadOptions,
+) -> Result<(), anyhow::Error> {
+    let config = get_config()?;
+    bulk::download_bundles(&humble_api(&config), entries, options, bulk_options)
 }
@@ -0,0 +1,130 @@
+use crate::humble_api::HumbleApi;
+use crate::models::Bundle;
+use crate::{
+    bundle_passes_claimed_filter, handle_http_errors, read_all_bundles, util, DateOptions,
+};
+use std::collections::BTreeMap;
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+
+/// Print the bundles that pass the filters, as keys only or as tables.
+pub fn list_bundles(
+    api: &HumbleApi,
+    id_only: bool,
+    claimed_filter: &str,
+    include_store: bool,
+    group_by_series: bool,
+    dates: &DateOptions,
+) -> Result<(), anyhow::Error> {
+    // If no filter is required, we can do a single call
+    // and finish quickly. Otherwise we will need to fetch
+    // all bundle data and filter them.
+    if id_only && claimed_filter == "all" && include_store {
+        let ids = handle_http_errors(api.list_bundle_keys())?;
+        for id in ids {
+            println!("{}", id);
+        }
+
+        return Ok(());
+    }
+
+    let mut bundles = read_all_bundles(api)?;
+
+    bundles.retain(|b| {
+        (include_store || !b.is_store_purchase()) && bundle_passes_claimed_filter(b, claimed_filter)
+    });
+
+    if id_only {
+        for b in bundles {
+            println!("{}", b.gamekey);
+        }
+
+        return Ok(());
+    }
+
+    println!("{} bundle(s) found.\n", bundles.len());
+
+    if bundles.is_empty() {
+        return Ok(());
+    }
+
+    if !group_by_series {
+        let bundles: Vec<_> = bundles.iter().collect();
+        println!("{}", bundles_table(&bundles, dates));
+        print_locked_hint(&bundles);
+        return Ok(());
+    }
+
+    let mut groups: BTreeMap<&str, Vec<&Bundle>> = BTreeMap::new();
+    let mut others = vec![];
+    for b in &bundles {
+        match b.details.series() {
+            Some(series) => groups.entry(series).or_default().push(b),
+            None => others.push(b),
+        }
+    }
+
+    let groups = groups
+        .into_iter()
+        .chain((!others.is_empty()).then_some(("Other", others)));
+    for (series, bundles) in groups {
+        let size: u64 = bundles.iter().map(|b| b.total_size()).sum();
+        println!(
+            "{} ({} bundle(s), {})",
+            series,
+            bundles.len(),
+            util::humanize_bytes(size)
+        );
+        println!("{}", bundles_table(&bundles, dates));
+        println!();
+    }
+    print_locked_hint(&bundles.iter().collect::<Vec<_>>());
+
+    Ok(())
+}
+
+/// Explain the Humble Choice months that are shown as locked.
+fn print_locked_hint(bundles: &[&Bundle]) {
+    let locked_count = bundles.iter().filter(|b| b.is_locked()).count();
+    if locked_count > 0 {
+        println!(
+            "{} Humble Choice month(s) are locked, as no games were chosen yet. \
+            See them with `list-choices <month>` and choose with `choose <month> --all`.",
+            locked_count
+        );
+    }
+}
+
+fn bundles_table(bundles: &[&Bundle], dates: &DateOptions) -> String {
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Key", "Name", "Purchased", "Size", "Claimed", "Keys"]);
+
+    for p in bundles {
+        let name = if p.is_gift {
+            format!("{} (gift)", p.details.human_name)
+        } else {
+            p.details.human_name.clone()
+        };
+        let size = if p.is_locked() {
+            "Locked".to_owned()
+        } else {
+            util::humanize_bytes(p.total_size())
+        };
+        builder.push_record([
+            p.gamekey.as_str(),
+            name.as_str(),
+            dates.format(&p.created, "%Y-%m-%d").as_str(),
+            size.as_str(),
+            p.claim_status().to_string().as_str(),
+            p.key_counts().as_str(),
+        ]);
+    }
+
+    builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+        .with(Modify::new(Columns::single(3)).with(Alignment::right()))
+        .with(Modify::new(Columns::single(5)).with(Alignment::right()))
+        .to_string()
+}
@@ -1,14 +1,21 @@
 use std::io;
+use std::path::PathBuf;
 
 use anyhow::Context;
-use clap::{builder::ValueParser, value_parser, Arg, Command};
+use clap::{builder::ValueParser, value_parser, Arg, ArgMatches, Command};
 use clap_complete::Shell;
 use humble_cli::prelude::*;
 
 fn main() {
     let crate_name = env!("CARGO_PKG_NAME");
     if let Err(e) = run() {
-        eprintln!("{}: {:?}", crate_name, e);
+        notify_failure(&e);
+        eprintln!(
+            "{}: {}",
+            crate_name,
+            redact_session_key(&format!("{:?}", e))
+        );
+        print_error_json(None, &e);
         std::process::exit(1);
     }
 }
@@ -17,11 +24,221 @@ fn parse_choices_period(input: &str) -> Result<ChoicePeriod, anyhow::Error> {
     ChoicePeriod::try_from(input).map_err(|e| anyhow::anyhow!(e))
 }
 
+fn parse_key_selector(input: &str) -> Result<KeySelector, anyhow::Error> {
+    KeySelector::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn key_selector_arg<'help>() -> Arg<'help> {
+    Arg::new("SELECTOR")
+        .required(true)
+        .takes_value(true)
+        .value_parser(ValueParser::new(parse_key_selector))
+        .help("The key as <bundle-key>:<number>, e.g. \"abc123:2\"")
+        .long_help(
+            "The key as <bundle-key>:<number>, e.g. \"abc123:2\". The number is the one \
+            in the keys table of `details`, and the bundle key can be partial.",
+        )
+}
+
 fn parse_match_mode(input: &str) -> Result<MatchMode, anyhow::Error> {
     MatchMode::try_from(input).map_err(|e| anyhow::anyhow!(e))
 }
 
+/// Read the lowercase values of the `format` argument.
+fn get_formats(matches: &ArgMatches) -> Vec<String> {
+    if let Some(values) = matches.values_of("format") {
+        values.map(|f| f.to_lowercase()).collect::<Vec<_>>()
+    } else {
+        vec![]
+    }
+}
+
+/// Read the `max-size` argument in bytes, or `0` if it's not given.
+fn get_max_size(matches: &ArgMatches) -> Result<u64, anyhow::Error> {
+    let max_size = if let Some(byte_str) = matches.value_of("max-size") {
+        byte_string_to_number(byte_str)
+            .context(format!("failed to parse the specified size: {}", byte_str))?
+    } else {
+        0
+    };
+    Ok(max_size)
+}
+
+fn parse_output_format(input: &str) -> Result<OutputFormat, anyhow::Error> {
+    OutputFormat::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_search_sort(input: &str) -> Result<SearchSort, anyhow::Error> {
+    SearchSort::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_if_exists(input: &str) -> Result<IfExists, anyhow::Error> {
+    IfExists::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_download_order(input: &str) -> Result<DownloadOrder, anyhow::Error> {
+    DownloadOrder::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_item_sort(input: &str) -> Result<ItemSort, anyhow::Error> {
+    ItemSort::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_report_format(input: &str) -> Result<ReportFormat, anyhow::Error> {
+    ReportFormat::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_receipt_format(input: &str) -> Result<ReceiptFormat, anyhow::Error> {
+    ReceiptFormat::try_from(input).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_day(input: &str) -> Result<chrono::NaiveDate, anyhow::Error> {
+    chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .with_context(|| format!("invalid date, expected YYYY-MM-DD: {}", input))
+}
+
+fn parse_date_format(input: &str) -> Result<String, anyhow::Error> {
+    use chrono::format::{Item, StrftimeItems};
+
+    if StrftimeItems::new(input).any(|item| item == Item::Error) {
+        anyhow::bail!("invalid date format: {}", input);
+    }
+    Ok(input.to_owned())
+}
+
+/// Arguments that control how purchase dates are shown.
+fn date_args<'help>() -> [Arg<'help>; 2] {
+    [
+        Arg::new("date-format")
+            .long("date-format")
+            .value_name("format")
+            .takes_value(true)
+            .value_parser(ValueParser::new(parse_date_format))
+            .help("Format of purchase dates, e.g. \"%Y-%m-%d %H:%M\"")
+            .long_help(
+                "Format of purchase dates, e.g. \"%Y-%m-%d %H:%M\". \
+                See https://docs.rs/chrono/latest/chrono/format/strftime/ for all specifiers.",
+            ),
+        Arg::new("utc")
+            .long("utc")
+            .help("Show purchase dates in UTC instead of the local time zone"),
+    ]
+}
+
+fn only_new_arg<'help>() -> Arg<'help> {
+    Arg::new("only-new")
+        .long("only-new")
+        .help("Skip files that were downloaded before")
+        .long_help(
+            "Skip files that were downloaded before, even if they were moved or deleted \
+            since. Every downloaded file is recorded in ~/.humble-cli-history.jsonl.",
+        )
+}
+
+fn if_exists_arg<'help>() -> Arg<'help> {
+    Arg::new("if-exists")
+        .long("if-exists")
+        .value_name("action")
+        .takes_value(true)
+        .possible_values(["skip", "resume", "overwrite", "rename"])
+        .default_value("resume")
+        .value_parser(ValueParser::new(parse_if_exists))
+        .help("What to do with files that already exist")
+        .long_help(
+            "What to do with files that already exist:\n\
+            - skip: leave them alone, even if they are incomplete\n\
+            - resume: continue partial files and skip complete ones\n\
+            - overwrite: download them again\n\
+            - rename: download to a new name, e.g. book-1.epub",
+        )
+}
+
+fn order_arg<'help>() -> Arg<'help> {
+    Arg::new("order")
+        .long("order")
+        .value_name("order")
+        .takes_value(true)
+        .possible_values(["as-listed", "smallest-first", "largest-first"])
+        .default_value("as-listed")
+        .value_parser(ValueParser::new(parse_download_order))
+        .help("Download the smallest or the largest files first")
+        .long_help(
+            "Order in which files are downloaded. smallest-first gets many items done early, \
+            while largest-first suits long runs, e.g. overnight. Sub-items are sorted by the \
+            size of their selected files, and the files of each sub-item by their size. \
+            bulk-download also sorts the bundles by their size.",
+        )
+}
+
+/// Arguments that control how failed downloads are retried.
+fn retry_args<'help>() -> [Arg<'help>; 3] {
+    [
+        Arg::new("retries")
+            .long("retries")
+            .value_name("count")
+            .takes_value(true)
+            .value_parser(value_parser!(u32))
+            .help("How many times to retry a failed download [default: 3]"),
+        Arg::new("retry-delay")
+            .long("retry-delay")
+            .value_name("seconds")
+            .takes_value(true)
+            .value_parser(value_parser!(u64))
+            .help("Seconds to wait before retrying a failed download [default: 5]"),
+        Arg::new("backoff")
+            .long("backoff")
+            .help("Double the delay after each retry, with some random jitter"),
+    ]
+}
+
+/// Read the retry arguments. Arguments that are not given come from the
+/// settings file.
+fn get_retry_policy(matches: &ArgMatches) -> Result<RetryPolicy, anyhow::Error> {
+    let mut policy = RetryPolicy::from_settings()?;
+    if let Some(&retries) = matches.get_one::<u32>("retries") {
+        policy.retries = retries;
+    }
+    if let Some(&seconds) = matches.get_one::<u64>("retry-delay") {
+        policy.delay = std::time::Duration::from_secs(seconds);
+    }
+    if matches.is_present("backoff") {
+        policy.backoff = true;
+    }
+    Ok(policy)
+}
+
+fn get_date_options(matches: &ArgMatches) -> DateOptions {
+    DateOptions {
+        format: matches.get_one::<String>("date-format").cloned(),
+        utc: matches.is_present("utc"),
+    }
+}
+
+fn get_net_options(matches: &ArgMatches) -> NetOptions {
+    let ip_version = if matches.is_present("ipv4") {
+        Some(IpVersion::V4)
+    } else if matches.is_present("ipv6") {
+        Some(IpVersion::V6)
+    } else {
+        None
+    };
+
+    NetOptions {
+        ip_version,
+        ca_certs: matches
+            .get_many::<String>("ca-cert")
+            .unwrap_or_default()
+            .map(PathBuf::from)
+            .collect(),
+        insecure: matches.is_present("insecure"),
+    }
+}
+
 fn run() -> Result<(), anyhow::Error> {
+    // Completed `--format` values. The commands borrow them, so they have to
+    // be declared first.
+    let formats: Vec<String>;
+
     let list_subcommand = Command::new("list")
         .about("List all your purchased bundles")
         .visible_alias("ls")
@@ -45,14 +262,35 @@ fn run() -> Result<(), anyhow::Error> {
                 "Show claimed or unclaimed bundles only. \
                     This is useful if you want to know which games or bundles you have not claimed yet."
             )
-    );
+    ).arg(
+        Arg::new("include-store")
+            .long("include-store")
+            .help("Also list store purchases and claimed freebies")
+            .long_help(
+                "Also list store purchases and claimed freebies. They are hidden by default, \
+                so the list only shows bundles and Humble Choice months."
+            )
+    ).arg(
+        Arg::new("group-by")
+            .long("group-by")
+            .value_name("field")
+            .takes_value(true)
+            .possible_values(["series"])
+            .value_parser(value_parser!(String))
+            .help("Group bundles by their series, e.g. Humble Book Bundle or Humble Choice")
+            .long_help(
+                "Group bundles by their series, e.g. Humble Book Bundle or Humble Choice. \
+                    The series is taken from the bundle name. Each group shows its number of \
+                    bundles and total size."
+            )
+    ).args(date_args());
 
     let completion_subcommand = Command::new("completion")
         .about("Generate shell completions")
         .arg(
             Arg::new("SHELL")
                 .help("Shell type to generate completions for")
-                .possible_values(&["bash", "elvish", "fish", "powershell", "zsh"])
+                .possible_values(["bash", "elvish", "fish", "powershell", "zsh"])
                 .takes_value(true)
                 .required(true)
                 .value_parser(value_parser!(Shell)),
@@ -65,19 +303,65 @@ fn run() -> Result<(), anyhow::Error> {
                 .default_value("current")
                 .value_parser(ValueParser::new(parse_choices_period))
                 .help("The month and the year to use for search. For example: 'january-2023'.\nUse 'current' for the current month."),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .help("Show a summary of claimed and unclaimed keys for all months instead")
+                .long_help(
+                    "Show a summary of claimed and unclaimed keys for every Humble Choice month \
+                    you were subscribed to, instead of the choices of a single month. \
+                    This checks each month separately and can take a while.",
+                ),
+        );
+
+    let choose_subcommand = Command::new("choose")
+        .about("Choose the games of a Humble Choice month")
+        .arg(
+            Arg::new("period")
+                .default_value("current")
+                .value_parser(ValueParser::new(parse_choices_period))
+                .help("The month and the year to choose in. For example: 'january-2023'.\nUse 'current' for the current month."),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .required(true)
+                .help("Choose every game that can still be chosen")
+                .long_help(
+                    "Choose every game that can still be chosen, up to the month's limit. \
+                    Games that are chosen already, or whose keys can't be claimed anymore, are \
+                    skipped. What was chosen and skipped is printed.",
+                ),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Only print what would be chosen"),
         );
 
     let auth_subcommand = Command::new("auth")
         .about("Set the authentication session key")
         .long_about(
             "Set the session key used for authentication with Humble Bundle API. \
-            See online documentation on how to find the session key from your web browser.",
+            See online documentation on how to find the session key from your web browser. \
+            The key is checked against the API, and only saved if it works.",
         )
         .arg(
             Arg::new("SESSION-KEY")
-                .required(true)
+                .required_unless_present("wizard")
                 .takes_value(true)
-                .help("Session key that's copied from your web browser"),
+                .help("Session key that's copied from your web browser")
+                .long_help(
+                    "Session key that's copied from your web browser. The whole cookie or \
+                    `Cookie` header can be pasted too, and the key is taken from it.",
+                ),
+        )
+        .arg(
+            Arg::new("wizard")
+                .long("wizard")
+                .conflicts_with("SESSION-KEY")
+                .help("Show step by step instructions and check the key before saving it"),
         );
 
     let details_subcommand = Command::new("details")
@@ -89,12 +373,28 @@ fn run() -> Result<(), anyhow::Error> {
                 .takes_value(true)
                 .help("The key for the bundle which must be shown")
                 .long_help(
-                    "The key for the bundle which must be shown. It can be partially entered.",
+                    "The key for the bundle which must be shown. It can be partially entered, \
+                    or be words of the bundle name, e.g. \"book bundle python\".",
                 ),
-        );
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("order")
+                .takes_value(true)
+                .possible_values(["name", "size", "format"])
+                .value_parser(ValueParser::new(parse_item_sort))
+                .help("Sort the sub-items by name, size (largest first) or format")
+                .long_help(
+                    "Sort the sub-items by name, size (largest first) or format. \
+                    Sub-items keep their number, so it can still be used with \
+                    `download --item-numbers`.",
+                ),
+        )
+        .args(date_args());
 
     let search_subcommand = Command::new("search")
-        .about("Search through all bundle products for keywords")
+        .about("Search through all bundles and their products for keywords")
         .arg(
             Arg::new("KEYWORDS")
                 .required(true)
@@ -111,6 +411,96 @@ fn run() -> Result<(), anyhow::Error> {
                 .default_value("any")
                 .value_parser(ValueParser::new(parse_match_mode))
                 .help("Whether all or any of the keywords should match the name"),
+        )
+        .arg(
+            Arg::new("contains")
+                .long("contains")
+                .help("Match keywords anywhere in the name, not only at the beginning of words")
+                .long_help(
+                    "Match keywords anywhere in the name, not only at the beginning of words. \
+                    By default 'prog' matches 'Programming', but 'gram' doesn't. \
+                    With this flag both of them match.",
+                ),
+        )
+        .arg(
+            Arg::new("fuzzy")
+                .long("fuzzy")
+                .conflicts_with("contains")
+                .help("Tolerate typos in keywords and sort the results by relevance"),
+        )
+        .arg(
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Only show sub-items available in this format")
+                .long_help(
+                    "Only show sub-items available in this format. Formats are case-insensitive and \
+                    this filter can be used several times to specify multiple formats.",
+                ),
+        )
+        .arg(
+            Arg::new("max-size")
+                .short('s')
+                .long("max-size")
+                .takes_value(true)
+                .help("Only show sub-items smaller than this size (e.g. 50MB)"),
+        )
+        .arg(
+            Arg::new("claimed")
+                .long("claimed")
+                .value_name("value")
+                .takes_value(true)
+                .possible_values(["all", "yes", "no"])
+                .default_value("all")
+                .help("Only show bundles with claimed or unclaimed keys"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_name("format")
+                .takes_value(true)
+                .possible_values(["table", "json"])
+                .default_value("table")
+                .value_parser(ValueParser::new(parse_output_format))
+                .help("Output format of the search results"),
+        )
+        .arg(
+            Arg::new("field")
+                .long("field")
+                .value_name("fields")
+                .takes_value(true)
+                .use_value_delimiter(true)
+                .multiple_occurrences(true)
+                .possible_values(SEARCH_FIELDS)
+                .help("Only print these fields, e.g. --field key,item")
+                .long_help(
+                    "Only print these fields, separated by commas. For example: --field key,item\n\n\
+                    With the table output, each result is printed on its own line with the fields \
+                    separated by tabs, which is easy to process in scripts. \
+                    With the JSON output, only these fields are included in each object.",
+                ),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("order")
+                .takes_value(true)
+                .possible_values(["relevance", "name", "size", "date"])
+                .default_value("relevance")
+                .value_parser(ValueParser::new(parse_search_sort))
+                .help("Sort the results by relevance, name, size (largest first) or date (newest first)"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .short('n')
+                .value_name("N")
+                .takes_value(true)
+                .value_parser(clap::value_parser!(usize))
+                .help("Show at most N results"),
         );
 
     let download_subcommand = Command::new("download")
@@ -118,10 +508,25 @@ fn run() -> Result<(), anyhow::Error> {
         .visible_alias("d")
         .arg(
             Arg::new("BUNDLE-KEY")
-                .required(true)
+                .required_unless_present("choice")
+                .conflicts_with("choice")
                 .help("The key for the bundle which must be downloaded")
                 .long_help(
-                    "The key for the bundle which must be downloaded. It can be partially entered."
+                    "The key for the bundle which must be downloaded. It can be partially entered, \
+                    or be words of the bundle name, e.g. \"book bundle python\"."
+                )
+        )
+        .arg(
+            Arg::new("choice")
+                .long("choice")
+                .value_name("period")
+                .takes_value(true)
+                .value_parser(ValueParser::new(parse_choices_period))
+                .help("Download the DRM-free games of a Humble Choice month instead")
+                .long_help(
+                    "Download the DRM-free games of a Humble Choice month instead of a bundle, \
+                    e.g. 'january-2023' or 'current'. Only games that were chosen can be \
+                    downloaded. All other options work as for a bundle."
                 )
         )
         .arg(
@@ -137,10 +542,28 @@ fn run() -> Result<(), anyhow::Error> {
                 '--item-numbers 1,3,5' will download items 1, 3, and 5.\n\
                 '--item number 5-10' will download items 5 to 10 (inclusive)\n\n\
                 When specifying ranges, either the beginning or the end of the range can be omitted.\n\
-                For example, '--item-numbers 10-' will download items 10 to the end.
+                For example, '--item-numbers 10-' will download items 10 to the end.\n\n\
+                A range can have a step: '1-20:2' will download every second item from 1 to 19.\n\
+                Items starting with '!' are excluded: '1-10,!4' will download items 1 to 10 except 4, \
+                and '!4' alone will download all items except 4. 'all' selects all items.
                 "
             )
         )
+        .arg(
+            Arg::new("item-machine-names")
+                .long("item-machine-names")
+                .value_name("names")
+                .takes_value(true)
+                .use_value_delimiter(true)
+                .multiple_occurrences(true)
+                .help("Download only the items with these machine names")
+                .long_help(
+                    "Download only the items with these machine names, e.g. \
+                    'some-book,another-item'. Names must match exactly. Unlike item numbers, \
+                    machine names stay the same, which suits scripts. Items of --item-numbers \
+                    are downloaded too."
+                )
+        )
         .arg(
             Arg::new("format")
                 .short('f')
@@ -163,50 +586,751 @@ fn run() -> Result<(), anyhow::Error> {
                 .long_help(
                     "Filter downloaded items by their maximum size. This will skip any sub-item in a bundle \
                     that exceeds this limit. \
-                    You can use the traditional size units such as KB or MiB. Make sure there is no space \
-                    between the number and the unit. For example 14MB or 4GiB.\n\n\
+                    You can use the traditional size units such as KB or MiB, e.g. 14MB or 4 GiB. \
+                    A number without a unit is in bytes, and 0, 'unlimited' or 'none' means no limit.\n\n\
                     Note: The size limit works on a sub-item level, and not per file. \
                     For example, if you specify a limit of 10 MB and a sub-item has two 6 MB books in it, \
                     this sub-items will not be downloaded, because its total size exceeds the 10 MB limit (12 MB in total)."
                     )
+        )
+        .arg(
+            Arg::new("export-script")
+                .long("export-script")
+                .value_name("type")
+                .takes_value(true)
+                .possible_values(["sh"])
+                .help("Print a shell script that downloads the selected items instead of downloading them")
+                .long_help(
+                    "Print a shell script that downloads the selected items instead of downloading them. \
+                    The script uses `curl` with resume support and creates the same directory layout as a normal download. \
+                    This is useful for running the transfers on a machine where humble-cli is not installed.\n\n\
+                    For example: humble-cli download <key> --export-script sh > download.sh"
+                )
+        )
+        .arg(
+            Arg::new("extract")
+                .long("extract")
+                .help("Unpack downloaded zip and tar.gz archives")
+                .long_help(
+                    "Unpack downloaded zip and tar.gz archives into the sub-item directory. \
+                    Archives are only extracted if their MD5 checksum matches the one reported by Humble Bundle."
+                )
+        )
+        .arg(
+            Arg::new("delete-archives")
+                .long("delete-archives")
+                .requires("extract")
+                .help("Delete archives after extracting them")
+                .long_help(
+                    "Delete archives after extracting them. \
+                    Note that deleted archives will be downloaded again if you run the same download command later."
+                )
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .value_name("type")
+                .takes_value(true)
+                .possible_values(["zip"])
+                .conflicts_with("delete-archives")
+                .help("Pack the bundle directory into a single archive after downloading")
+                .long_help(
+                    "Pack the bundle directory into a single archive after downloading. \
+                    The archive is created next to the bundle directory and contains a `manifest.json` \
+                    file listing the downloaded items with their formats, sizes and MD5 checksums."
+                )
+        )
+        .arg(
+            Arg::new("remove-files")
+                .long("remove-files")
+                .requires("archive")
+                .help("Remove the bundle directory after creating the archive")
+        )
+        .arg(
+            Arg::new("dest")
+                .long("dest")
+                .value_name("destination")
+                .takes_value(true)
+                .conflicts_with_all(&["extract", "archive"])
+                .help("Move downloaded files to an rclone remote")
+                .long_help(
+                    "Move downloaded files to an rclone remote. The value must be in the \
+                    `rclone:remote:path` format, where `remote` is configured in rclone.\n\
+                    Each file is downloaded locally and then moved with `rclone moveto`, keeping \
+                    the same directory layout under the given path.\n\n\
+                    For example: --dest rclone:nas:humble"
+                )
+        )
+        .arg(
+            Arg::new("import-calibre")
+                .long("import-calibre")
+                .conflicts_with("dest")
+                .help("Add downloaded e-books to your Calibre library")
+                .long_help(
+                    "Add downloaded e-books to your Calibre library using `calibredb`. \
+                    All formats of a sub-item are added as a single book, and books whose title \
+                    already exists in the library are skipped."
+                )
+        )
+        .arg(
+            Arg::new("calibre-library")
+                .long("calibre-library")
+                .value_name("path")
+                .takes_value(true)
+                .requires("import-calibre")
+                .help("Path to the Calibre library to import into (default: Calibre's current library)")
+        )
+        .arg(
+            Arg::new("with-covers")
+                .long("with-covers")
+                .help("Save the cover image of each sub-item as `cover.jpg` in its directory")
+        )
+        .arg(
+            Arg::new("write-info-json")
+                .long("write-info-json")
+                .help("Write a `<file>.info.json` metadata file next to each downloaded file")
+                .long_help(
+                    "Write a `<file>.info.json` metadata file next to each downloaded file. \
+                    It contains the bundle key and name, the sub-item's machine name, the format, \
+                    MD5 checksum, size and the URL the file was downloaded from."
+                )
+        )
+        .arg(
+            Arg::new("flat")
+                .long("flat")
+                .conflicts_with("with-covers")
+                .help("Put all files in the bundle directory, without a directory per sub-item")
+                .long_help(
+                    "Put all files in the bundle directory, without a directory per sub-item. \
+                    Files with the same name get the sub-item's machine name added to their name. \
+                    Use --name-template to choose the names yourself."
+                )
+        )
+        .arg(
+            Arg::new("no-single-dirs")
+                .long("no-single-dirs")
+                .conflicts_with("with-covers")
+                .help("Don't create a directory for sub-items that have only one file")
+                .long_help(
+                    "Don't create a directory for sub-items that have only one file. \
+                    Their file is put in the bundle directory instead."
+                )
+        )
+        .arg(
+            Arg::new("name-template")
+                .long("name-template")
+                .value_name("template")
+                .takes_value(true)
+                .help("Template for file names, e.g. \"{product}.{ext}\", or media-server")
+                .long_help(
+                    "Template for the names of downloaded files, e.g. \"{product}.{ext}\". \
+                    These placeholders are available: {filename} (the original name), \
+                    {stem} and {ext} (the original name without and with only its extension), \
+                    {format}, {product} (the sub-item name) and {machine_name}. \
+                    For videos, {title}, {year}, {season} and {episode} are taken from the \
+                    sub-item name, e.g. \"Show - S01E02\" or \"Movie (2019)\". \
+                    A / in the template creates directories.\n\n\
+                    The media-server preset lays out videos for Jellyfin and Plex, without \
+                    sub-item directories: Show/Season 01/Show - S01E02.mp4 for episodes and \
+                    Movie (2019)/Movie (2019).mp4 for other videos."
+                )
+        )
+        .arg(
+            Arg::new("sha256")
+                .long("sha256")
+                .help("Compute the SHA-256 of downloaded files")
+                .long_help(
+                    "Compute the SHA-256 of downloaded files. It's recorded next to the MD5 \
+                    from Humble Bundle in the download history, the archive manifest and \
+                    the .info.json files, and `verify` uses it instead of the MD5."
+                )
+        )
+        .arg(
+            Arg::new("torrents")
+                .long("torrents")
+                .conflicts_with("export-script")
+                .help("Download the torrent of each file instead of the file")
+                .long_help(
+                    "Download the torrent of each file instead of the file itself. Files \
+                    without a torrent are skipped and listed at the end, unless \
+                    --torrent-fallback is given."
+                )
+        )
+        .arg(
+            Arg::new("torrent-fallback")
+                .long("torrent-fallback")
+                .requires("torrents")
+                .help("Download files that have no torrent directly")
+        )
+        .arg(
+            Arg::new("push-torrents")
+                .long("push-torrents")
+                .requires("torrents")
+                .help("Add the torrents to qBittorrent or Transmission")
+                .long_help(
+                    "Add each saved torrent to the qBittorrent or Transmission client of the \
+                    [torrents] section in ~/.humble-cli.toml. The client saves the file in the \
+                    directory it would be downloaded to."
+                )
+        )
+        .arg(
+            Arg::new("refresh")
+                .long("refresh")
+                .help("Download existing files again if they changed on the server")
+                .long_help(
+                    "Download existing files again if they changed on the server. A complete \
+                    file has changed if its size or ETag is different from the server's, or the \
+                    server's Last-Modified date is newer than the local file, which is checked \
+                    with a HEAD request. ETags are recorded in ~/.humble-cli-history.jsonl. \
+                    Unchanged files are skipped, and partial files are resumed."
+                )
+        )
+        .arg(
+            Arg::new("verify-resume")
+                .long("verify-resume")
+                .help("Check partial files before resuming them, and the MD5 of finished files")
+                .long_help(
+                    "Check partial files before resuming them, and the MD5 of finished files. \
+                    The start of a partial file is compared with the file on the server, so a \
+                    partial file of an older version of the file is downloaded again instead \
+                    of being resumed."
+                )
+        )
+        .arg(
+            Arg::new("print-paths")
+                .long("print-paths")
+                .help("Print the absolute path of each downloaded file")
+                .long_help(
+                    "Print the absolute path of each downloaded file on stdout, one per line. \
+                    All other messages go to stderr, so scripts can read the files this \
+                    run produced."
+                )
+        )
+        .arg(only_new_arg())
+        .arg(if_exists_arg())
+        .arg(order_arg())
+        .args(retry_args());
+
+    let check_subcommand = Command::new("check")
+        .about("Check for new bundles and key changes since the last check, e.g. from cron")
+        .long_about(
+            "Check for new bundles and key changes since the last check. \
+            This is meant to be run periodically, e.g. from cron. \
+            The changes are printed as a single line of JSON. \
+            The first run only saves the current state of the library.\n\n\
+            Exit status is 0 when nothing changed, 3 when something changed and 1 on errors.",
+        );
+
+    let keys_subcommand = Command::new("keys")
+        .about("Work with the keys of your bundles")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("open")
+                .about("Open the page where a key is redeemed in the browser")
+                .long_about(
+                    "Open the page where a key is redeemed in the browser. \
+                    Revealed Steam and GOG keys open the store's activation page with the key \
+                    filled in. Other keys open Humble Bundle's keys page.",
+                )
+                .arg(key_selector_arg()),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("List unclaimed keys grouped by bundle, oldest purchases first")
+                .args(date_args()),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("Print the value of a revealed key")
+                .arg(key_selector_arg())
+                .arg(
+                    Arg::new("qr")
+                        .long("qr")
+                        .help("Also show the key as a QR code, to scan it with a phone"),
+                ),
+        );
+
+    let membership_subcommand = Command::new("membership")
+        .about("Work with your Humble Choice and Humble Monthly subscription")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("history")
+                .about("List the subscription months in your library, oldest first")
+                .long_about(
+                    "List the subscription months in your library, oldest first. \
+                    The Claimed column shows months whose keys were never revealed.",
+                )
+                .args(date_args()),
+        );
+
+    let formats_subcommand = Command::new("formats")
+        .about("List all file formats in your library with their number of files and size");
+
+    let stats_subcommand = Command::new("stats")
+        .about("Show totals for your library: items, size, keys and more")
+        .arg(
+            Arg::new("by-format")
+                .long("by-format")
+                .help("Also show the number of files and total size of each format"),
+        )
+        .arg(
+            Arg::new("largest")
+                .long("largest")
+                .value_name("N")
+                .takes_value(true)
+                .value_parser(clap::value_parser!(usize))
+                .help("Also list the N largest sub-items in the library"),
+        );
+
+    let bulk_download_subcommand = Command::new("bulk-download")
+        .about("Download several bundles listed in a file")
+        .long_about(
+            "Download several bundles listed in a file. \
+            Each line of the file holds a bundle key, optionally followed by comma-separated fields. \
+            An `items=` field selects the items to download, like --item-numbers in `download`, \
+            and a `formats=` field overrides --format for that bundle. \
+            Use ; to separate their values. Other fields, like the bundle name, are ignored. \
+            Empty lines and lines starting with # are skipped. For example:\n\n\
+            abcd1234,Humble Book Bundle: Python,items=1-3;5,formats=epub;pdf\n\n\
+            A failed bundle doesn't stop the others. All failures are listed at the end \
+            and written to failures.json in the current directory.",
+        )
+        .arg(
+            Arg::new("INPUT-FILE")
+                .required_unless_present("all")
+                .takes_value(true)
+                .help("File with one bundle key per line, or - to read from stdin")
+                .long_help(
+                    "File with one bundle key per line, or - to read from stdin. For example:\n\n\
+                    humble-cli list --id-only | humble-cli bulk-download -",
+                ),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .conflicts_with("INPUT-FILE")
+                .help("Download all bundles in your library instead of the ones in a file"),
+        )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .help("Skip the bundles that were completed in the previous run")
+                .long_help(
+                    "Skip the bundles that were completed in the previous run. \
+                    Each run keeps a journal of completed and failed bundles in \
+                    .humble-cli-journal.json in the current directory.",
+                ),
+        )
+        .arg(
+            Arg::new("retry-failed")
+                .long("retry-failed")
+                .conflicts_with("continue")
+                .help("Only download the bundles that failed in the previous run"),
+        )
+        .arg(
+            Arg::new("include-gifts")
+                .long("include-gifts")
+                .help("Also download orders that were bought as gifts")
+                .long_help(
+                    "Also download orders that were bought as gifts. They are skipped by \
+                    default, as their content belongs to whoever received them. `list` marks \
+                    them with (gift).",
+                ),
+        )
+        .arg(
+            Arg::new("bundle-jobs")
+                .long("bundle-jobs")
+                .value_name("N")
+                .takes_value(true)
+                .default_value("1")
+                .value_parser(clap::value_parser!(usize))
+                .help("Number of bundles to download at the same time"),
+        )
+        .arg(
+            Arg::new("claimed")
+                .long("claimed")
+                .value_name("value")
+                .takes_value(true)
+                .possible_values(["all", "yes", "no"])
+                .default_value("all")
+                .value_parser(value_parser!(String))
+                .help("Only download bundles with claimed or unclaimed keys")
+                .long_help(
+                    "Only download bundles with claimed or unclaimed keys, like `list --claimed`. \
+                    For example, use `--claimed yes` to archive the bundles whose keys are all redeemed.",
+                ),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Only show the number and size of the files that would be downloaded"),
+        )
+        .arg(
+            Arg::new("name-filter")
+                .long("name-filter")
+                .value_name("pattern")
+                .takes_value(true)
+                .help("Only download bundles whose name matches this pattern")
+                .long_help(
+                    "Only download bundles whose name matches this pattern. \
+                    The pattern is case-insensitive and can use * and ? wildcards, \
+                    e.g. \"Humble Book Bundle*\". Without wildcards, it matches anywhere in the name.",
+                ),
+        )
+        .arg(
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Filter downloaded items by their format"),
+        )
+        .arg(
+            Arg::new("max-size")
+                .short('s')
+                .long("max-size")
+                .takes_value(true)
+                .help("Filter downloaded items by their maximum size"),
+        )
+        .arg(only_new_arg())
+        .arg(if_exists_arg())
+        .arg(order_arg())
+        .args(retry_args());
+
+    let pick_subcommand = Command::new("pick")
+        .about("Interactively pick a bundle to show or download")
+        .long_about(
+            "Interactively pick a bundle to show or download. \
+            Type to fuzzy-search through your bundles, then choose whether to see the \
+            bundle details, download all of it or pick a single item to download.",
+        );
+
+    let tui_subcommand = Command::new("tui")
+        .about("Browse bundles, items and keys in a terminal UI")
+        .long_about(
+            "Browse bundles, items and keys in a terminal UI. \
+            Press / to filter the bundles, Space to mark items and d to download the marked \
+            items (or the selected bundle if nothing is marked).",
+        );
+
+    let version_subcommand = Command::new("version")
+        .about("Print the version, optionally checking for a newer release")
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Check GitHub for a newer release")
+                .long_help(
+                    "Check GitHub for a newer release and show its changelog. \
+                    Humble Bundle changes its API from time to time, which often needs an \
+                    updated release of humble-cli.",
+                ),
+        );
+
+    let report_subcommand = Command::new("report")
+        .about("Export your whole library as a Markdown or HTML document")
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("format")
+                .takes_value(true)
+                .possible_values(["md", "html"])
+                .default_value("md")
+                .value_parser(ValueParser::new(parse_report_format))
+                .help("Format of the report"),
+        )
+        .arg(
+            Arg::new("out")
+                .long("out")
+                .short('o')
+                .value_name("file")
+                .takes_value(true)
+                .help("Write the report to this file instead of the standard output"),
+        );
+
+    let history_subcommand = Command::new("history")
+        .about("Show the files that were downloaded, when, and how fast")
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .value_name("date")
+                .takes_value(true)
+                .value_parser(ValueParser::new(parse_day))
+                .help("Only show files downloaded on or after this day, e.g. 2024-01-31"),
+        )
+        .arg(
+            Arg::new("bundle")
+                .long("bundle")
+                .value_name("bundle")
+                .takes_value(true)
+                .help("Only show files of bundles whose key starts with this, or whose name matches it")
+                .long_help(
+                    "Only show files of bundles whose key starts with this, or whose name \
+                    matches it. Names are matched like --name-filter of bulk-download.",
+                ),
+        )
+        .arg(
+            Arg::new("failed")
+                .long("failed")
+                .help("Only show downloads that failed"),
+        )
+        .args(date_args());
+
+    let verify_subcommand = Command::new("verify")
+        .about("Check the checksums of the files in the download history")
+        .long_about(
+            "Check the checksums of the files in the download history. The SHA-256 from \
+            `download --sha256` is used if there is one, and the MD5 from Humble Bundle \
+            otherwise. Files that are missing or don't match are listed, and the exit \
+            status is 1 if there are any.",
+        )
+        .arg(
+            Arg::new("bundle")
+                .long("bundle")
+                .value_name("bundle")
+                .takes_value(true)
+                .help("Only check files of bundles whose key starts with this, or whose name matches it"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .value_name("count")
+                .takes_value(true)
+                .value_parser(value_parser!(usize))
+                .help("How many files to check at the same time [default: number of CPUs]"),
+        );
+
+    let receipts_subcommand = Command::new("receipts")
+        .about("Work with the receipts of your orders")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("export")
+                .about("Export all orders with their date, amount and reference as CSV or JSON")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("format")
+                        .takes_value(true)
+                        .possible_values(["csv", "json"])
+                        .default_value("csv")
+                        .value_parser(ValueParser::new(parse_receipt_format))
+                        .help("Format of the export"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("file")
+                        .takes_value(true)
+                        .help("Write the receipts to this file instead of the standard output"),
+                ),
+        );
+
+    let opds_subcommand = Command::new("opds")
+        .about("Build an OPDS catalog of your downloaded e-books")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("generate")
+                .about("Write an OPDS catalog of the e-books in a directory to opds.xml in it")
+                .long_about(
+                    "Write an OPDS 1.2 catalog of the e-books in a directory to opds.xml in it, \
+                    so e-reader apps like KOReader can browse them when the directory is \
+                    served over HTTP. Books are found by the .info.json files of \
+                    `download --write-info-json`, and by the manifest.json of unpacked \
+                    `--archive zip` files.",
+                )
+                .arg(
+                    Arg::new("LIBRARY-DIR")
+                        .required(true)
+                        .takes_value(true)
+                        .help("Directory with the downloaded bundles"),
+                ),
+        );
+
+    let serve_subcommand = Command::new("serve")
+        .about("Serve your library and downloaded files over HTTP")
+        .long_about(
+            "Serve your library and downloaded files over HTTP, so other devices on your \
+            network can browse and download them without humble-cli. The server is read-only. \
+            It lists the bundles in the local cache of bundle names and the files of the \
+            download history that are in the library directory, as a web page at / and as \
+            JSON at /api/bundles. Nothing is read from Humble Bundle.",
+        )
+        .arg(
+            Arg::new("LIBRARY-DIR")
+                .takes_value(true)
+                .default_value(".")
+                .help("Directory with the downloaded bundles"),
+        )
+        .arg(
+            Arg::new("port")
+                .long("port")
+                .value_name("port")
+                .takes_value(true)
+                .default_value("8080")
+                .value_parser(value_parser!(u16))
+                .help("Port to listen on"),
+        )
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .value_name("address")
+                .takes_value(true)
+                .default_value("0.0.0.0")
+                .help("Address to listen on. Use 127.0.0.1 to only allow this computer"),
         );
 
     let sub_commands = vec![
         auth_subcommand,
+        check_subcommand,
         list_subcommand,
         list_choices_subcommand,
+        choose_subcommand,
         details_subcommand,
         download_subcommand,
+        bulk_download_subcommand,
         search_subcommand,
+        stats_subcommand,
+        formats_subcommand,
+        history_subcommand,
+        keys_subcommand,
+        membership_subcommand,
+        pick_subcommand,
+        tui_subcommand,
+        report_subcommand,
+        opds_subcommand,
+        serve_subcommand,
+        receipts_subcommand,
         completion_subcommand,
+        verify_subcommand,
+        version_subcommand,
     ];
 
     let crate_name = clap::crate_name!();
 
-    let mut root = clap::Command::new(crate_name)
+    let root = clap::Command::new(crate_name)
         .about("The missing Humble Bundle CLI")
         .version(clap::crate_version!())
         .after_help("Note: `humble-cli -h` prints a short and concise overview while `humble-cli --help` gives all details.")
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("ipv4")
+                .long("ipv4")
+                .short('4')
+                .global(true)
+                .conflicts_with("ipv6")
+                .help("Only connect over IPv4")
+                .long_help(
+                    "Only connect over IPv4. Use this if downloads are slow or fail over IPv6 \
+                    with your network.",
+                ),
+        )
+        .arg(
+            Arg::new("ipv6")
+                .long("ipv6")
+                .short('6')
+                .global(true)
+                .help("Only connect over IPv6"),
+        )
+        .arg(
+            Arg::new("ca-cert")
+                .long("ca-cert")
+                .value_name("file")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .global(true)
+                .help("Also trust the root certificates in this PEM file")
+                .long_help(
+                    "Also trust the root certificates in this PEM file, e.g. of a corporate \
+                    proxy. Can be given more than once.",
+                ),
+        )
+        .arg(
+            Arg::new("insecure")
+                .long("insecure")
+                .global(true)
+                .help("Don't verify TLS certificates")
+                .long_help(
+                    "Don't verify TLS certificates. This makes the connection open to \
+                    eavesdropping, so prefer --ca-cert when possible.",
+                ),
+        )
+        .arg(
+            Arg::new("errors-json")
+                .long("errors-json")
+                .global(true)
+                .help("Also print errors as JSON on stderr")
+                .long_help(
+                    "Also print errors as single-line JSON objects on stderr, with the error \
+                    class, bundle key, item, HTTP status and whether trying again may help. \
+                    bulk-download prints one for every failed bundle.",
+                ),
+        )
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .alias("non-interactive")
+                .global(true)
+                .help("Never ask questions, take the safe default instead")
+                .long_help(
+                    "Never ask questions and take the safe default instead, so commands can \
+                    run in scripts and cron. For example, `download` gets all selected items \
+                    without showing the list first, and an ambiguous bundle name is an error. \
+                    Commands that only work interactively, like `pick`, fail right away. \
+                    Questions are also skipped when the input is not a terminal. \
+                    --non-interactive is the same.",
+                ),
+        )
         .subcommands(sub_commands);
 
     let matches = root.clone().get_matches();
-    return match matches.subcommand() {
+    set_errors_json(matches.is_present("errors-json"));
+    set_non_interactive(matches.is_present("yes"));
+    set_net_options(get_net_options(&matches))?;
+    match matches.subcommand() {
         Some(("completion", sub_matches)) => {
             if let Some(g) = sub_matches.get_one::<Shell>("SHELL").copied() {
+                // Complete `--format` with the formats of the cached library,
+                // if there is one. These are only used for completions, so any
+                // format is still accepted on the command line.
+                formats = library_formats();
+                let format_values: Vec<&str> = formats.iter().map(String::as_str).collect();
+                let mut root = root
+                    .mut_subcommand("download", |c| {
+                        c.mut_arg("format", |a| a.possible_values(format_values.clone()))
+                    })
+                    .mut_subcommand("search", |c| {
+                        c.mut_arg("format", |a| a.possible_values(format_values.clone()))
+                    });
+
                 let crate_name = clap::crate_name!();
                 clap_complete::generate(g, &mut root, crate_name, &mut io::stdout());
             }
             Ok(())
         }
-        Some(("auth", sub_matches)) => {
-            let session_key = sub_matches.value_of("SESSION-KEY").unwrap();
-            auth(session_key)
+        Some(("auth", sub_matches)) => match sub_matches.value_of("SESSION-KEY") {
+            Some(session_key) => auth(session_key),
+            None => auth_wizard(),
+        },
+        Some(("check", _)) => {
+            if check_library()? {
+                std::process::exit(3);
+            }
+            Ok(())
         }
         Some(("details", sub_matches)) => {
             let bundle_key = sub_matches.value_of("BUNDLE-KEY").unwrap();
-            show_bundle_details(bundle_key)
+            show_bundle_details(
+                bundle_key,
+                &get_date_options(sub_matches),
+                sub_matches.get_one::<ItemSort>("sort").copied(),
+            )
         }
         Some(("search", sub_matches)) => {
             let keywords: Vec<String> =
@@ -214,24 +1338,100 @@ fn run() -> Result<(), anyhow::Error> {
             let keywords = keywords.join(" ");
 
             let match_mode: &MatchMode = sub_matches.get_one("mode").unwrap();
-            // let keywords = sub_matches.value_of("KEYWORDS").unwrap();
-            search(&keywords, *match_mode)
+            let match_kind = if sub_matches.is_present("fuzzy") {
+                MatchKind::Fuzzy
+            } else if sub_matches.is_present("contains") {
+                MatchKind::Contains
+            } else {
+                MatchKind::Prefix
+            };
+            let options = SearchOptions {
+                match_mode: *match_mode,
+                match_kind,
+                formats: get_formats(sub_matches),
+                max_size: get_max_size(sub_matches)?,
+                claimed: sub_matches.value_of("claimed").unwrap().to_owned(),
+                output: *sub_matches.get_one::<OutputFormat>("output").unwrap(),
+                fields: sub_matches
+                    .values_of("field")
+                    .map(|values| values.map(str::to_owned).collect())
+                    .unwrap_or_default(),
+                sort: *sub_matches.get_one::<SearchSort>("sort").unwrap(),
+                limit: sub_matches.get_one::<usize>("limit").copied(),
+            };
+            search(&keywords, &options)
         }
         Some(("download", sub_matches)) => {
-            let bundle_key = sub_matches.value_of("BUNDLE-KEY").unwrap();
-            let formats = if let Some(values) = sub_matches.values_of("format") {
-                values.map(|f| f.to_lowercase()).collect::<Vec<_>>()
-            } else {
-                vec![]
+            let options = DownloadOptions {
+                formats: get_formats(sub_matches),
+                max_size: get_max_size(sub_matches)?,
+                item_numbers: sub_matches.value_of("item-numbers").map(str::to_owned),
+                item_machine_names: sub_matches
+                    .values_of("item-machine-names")
+                    .map(|names| names.map(str::to_owned).collect())
+                    .unwrap_or_default(),
+                export_script: sub_matches.is_present("export-script"),
+                extract: sub_matches.is_present("extract"),
+                delete_archives: sub_matches.is_present("delete-archives"),
+                archive: sub_matches.is_present("archive"),
+                remove_files: sub_matches.is_present("remove-files"),
+                dest: sub_matches.value_of("dest").map(str::to_owned),
+                import_calibre: sub_matches.is_present("import-calibre"),
+                calibre_library: sub_matches.value_of("calibre-library").map(str::to_owned),
+                with_covers: sub_matches.is_present("with-covers"),
+                write_info_json: sub_matches.is_present("write-info-json"),
+                yes: sub_matches.is_present("yes"),
+                flat: sub_matches.is_present("flat"),
+                no_single_dirs: sub_matches.is_present("no-single-dirs"),
+                name_template: sub_matches.value_of("name-template").map(str::to_owned),
+                print_paths: sub_matches.is_present("print-paths"),
+                retry: get_retry_policy(sub_matches)?,
+                verify_resume: sub_matches.is_present("verify-resume"),
+                refresh: sub_matches.is_present("refresh"),
+                if_exists: *sub_matches.get_one::<IfExists>("if-exists").unwrap(),
+                order: *sub_matches.get_one::<DownloadOrder>("order").unwrap(),
+                torrents: sub_matches.is_present("torrents"),
+                torrent_fallback: sub_matches.is_present("torrent-fallback"),
+                push_torrents: sub_matches.is_present("push-torrents"),
+                sha256: sub_matches.is_present("sha256"),
+                only_new: sub_matches.is_present("only-new"),
             };
-            let max_size: u64 = if let Some(byte_str) = sub_matches.value_of("max-size") {
-                byte_string_to_number(byte_str)
-                    .context(format!("failed to parse the specified size: {}", byte_str))?
-            } else {
-                0
+            match sub_matches.get_one::<ChoicePeriod>("choice") {
+                Some(period) => download_choice(period, &options),
+                None => download_bundle(sub_matches.value_of("BUNDLE-KEY").unwrap(), &options),
+            }
+        }
+        Some(("bulk-download", sub_matches)) => {
+            let entries = match sub_matches.value_of("INPUT-FILE") {
+                Some(input) => read_bulk_input(input)?,
+                None => library_bundle_keys()?
+                    .iter()
+                    .map(|key| BulkEntry::new(key))
+                    .collect(),
+            };
+            let options = DownloadOptions {
+                formats: get_formats(sub_matches),
+                max_size: get_max_size(sub_matches)?,
+                yes: true,
+                retry: get_retry_policy(sub_matches)?,
+                only_new: sub_matches.is_present("only-new"),
+                if_exists: *sub_matches.get_one::<IfExists>("if-exists").unwrap(),
+                order: *sub_matches.get_one::<DownloadOrder>("order").unwrap(),
+                ..Default::default()
             };
-            let item_numbers = sub_matches.value_of("item-numbers");
-            download_bundle(bundle_key, formats, max_size, item_numbers)
+            let bulk_options = BulkDownloadOptions {
+                name_filter: sub_matches.value_of("name-filter").map(str::to_owned),
+                continue_run: sub_matches.is_present("continue"),
+                retry_failed: sub_matches.is_present("retry-failed"),
+                claimed_filter: sub_matches
+                    .get_one::<String>("claimed")
+                    .filter(|c| *c != "all")
+                    .cloned(),
+                bundle_jobs: *sub_matches.get_one::<usize>("bundle-jobs").unwrap(),
+                dry_run: sub_matches.is_present("dry-run"),
+                include_gifts: sub_matches.is_present("include-gifts"),
+            };
+            download_bundles(&entries, &options, &bulk_options)
         }
         Some(("list", sub_matches)) => {
             let id_only = sub_matches.is_present("id-only");
@@ -239,14 +1439,92 @@ fn run() -> Result<(), anyhow::Error> {
                 .get_one::<String>("claimed")
                 .map(String::as_str)
                 .unwrap_or("all");
-            list_bundles(id_only, claimed_filter)
+            let include_store = sub_matches.is_present("include-store");
+            list_bundles(
+                id_only,
+                claimed_filter,
+                include_store,
+                sub_matches.get_one::<String>("group-by").is_some(),
+                &get_date_options(sub_matches),
+            )
         }
-        Some(("list-choices", sub_matches)) => {
+        Some(("pick", _)) => pick(),
+        Some(("formats", _)) => list_formats(),
+        Some(("keys", sub_matches)) => match sub_matches.subcommand() {
+            Some(("open", sub_matches)) => {
+                open_key(sub_matches.get_one::<KeySelector>("SELECTOR").unwrap())
+            }
+            Some(("report", sub_matches)) => report_unclaimed_keys(&get_date_options(sub_matches)),
+            Some(("show", sub_matches)) => show_key(
+                sub_matches.get_one::<KeySelector>("SELECTOR").unwrap(),
+                sub_matches.is_present("qr"),
+            ),
+            _ => Ok(()),
+        },
+        Some(("membership", sub_matches)) => match sub_matches.subcommand() {
+            Some(("history", sub_matches)) => {
+                list_membership_history(&get_date_options(sub_matches))
+            }
+            _ => Ok(()),
+        },
+        Some(("stats", sub_matches)) => show_stats(
+            sub_matches.is_present("by-format"),
+            sub_matches.get_one::<usize>("largest").copied(),
+        ),
+        Some(("tui", _)) => tui(),
+        Some(("version", sub_matches)) => print_version(sub_matches.is_present("check")),
+        Some(("verify", sub_matches)) => {
+            let jobs = match sub_matches.get_one::<usize>("jobs") {
+                Some(&jobs) => jobs,
+                None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            };
+            verify_downloads(sub_matches.value_of("bundle"), jobs)
+        }
+        Some(("history", sub_matches)) => {
+            let options = HistoryOptions {
+                since: sub_matches.get_one::<chrono::NaiveDate>("since").copied(),
+                bundle: sub_matches.value_of("bundle").map(str::to_owned),
+                failed: sub_matches.is_present("failed"),
+            };
+            show_history(&options, &get_date_options(sub_matches))
+        }
+        Some(("opds", sub_matches)) => match sub_matches.subcommand() {
+            Some(("generate", sub_matches)) => {
+                generate_opds(sub_matches.value_of("LIBRARY-DIR").unwrap())
+            }
+            _ => Ok(()),
+        },
+        Some(("serve", sub_matches)) => serve_library(
+            sub_matches.value_of("LIBRARY-DIR").unwrap(),
+            sub_matches.value_of("bind").unwrap(),
+            *sub_matches.get_one::<u16>("port").unwrap(),
+        ),
+        Some(("receipts", sub_matches)) => match sub_matches.subcommand() {
+            Some(("export", sub_matches)) => {
+                let format: &ReceiptFormat = sub_matches.get_one("format").unwrap();
+                export_receipts(*format, sub_matches.value_of("out"))
+            }
+            _ => Ok(()),
+        },
+        Some(("report", sub_matches)) => {
+            let format: &ReportFormat = sub_matches.get_one("format").unwrap();
+            let out = sub_matches.value_of("out");
+            export_report(*format, out)
+        }
+        Some(("choose", sub_matches)) => {
             let period: &ChoicePeriod = sub_matches.get_one("period").unwrap();
-            list_humble_choices(period)
+            choose_all(period, sub_matches.is_present("dry-run"))
+        }
+        Some(("list-choices", sub_matches)) => {
+            if sub_matches.is_present("all") {
+                list_all_humble_choices()
+            } else {
+                let period: &ChoicePeriod = sub_matches.get_one("period").unwrap();
+                list_humble_choices(period)
+            }
         }
 
         // This shouldn't happen
         _ => Ok(()),
-    };
+    }
 }
@@ -1,49 +1,145 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 
-use chrono::NaiveDateTime;
-use serde::Deserialize;
-use serde_with::{serde_as, VecSkipError};
+use crate::util;
+use chrono::{Datelike, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DefaultOnError, DefaultOnNull, MapSkipError, VecSkipError};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ClaimStatus {
     Yes,
     No,
     NotAvailable,
 }
 
-impl ToString for ClaimStatus {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for ClaimStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
             Self::Yes => "Yes",
             Self::No => "No",
             Self::NotAvailable => "-",
-        }
-        .to_owned()
+        };
+        f.write_str(s)
     }
 }
 
 // ===========================================================================
 // Models related to the purchased Bundles
 // ===========================================================================
-pub type BundleMap = HashMap<String, Bundle>;
+/// Orders by their key. An order that can't be read is skipped, instead of
+/// failing the whole response.
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BundleMap(#[serde_as(as = "MapSkipError<_, _>")] pub HashMap<String, Bundle>);
 
+/// An order in the library. Besides bundles, this can be a store purchase or
+/// a claimed freebie, which have fewer fields.
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Bundle {
     pub gamekey: String,
     pub created: NaiveDateTime,
+    #[serde(default)]
     pub claimed: bool,
 
-    pub tpkd_dict: HashMap<String, serde_json::Value>,
+    /// Amounts paid for the order, in `currency`. They are `None` when the
+    /// order has no such field, e.g. for freebies.
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnError")]
+    pub amount_spent: Option<f64>,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnError")]
+    pub amount_to_charge: Option<f64>,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnError")]
+    pub total: Option<f64>,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnError")]
+    pub currency: Option<String>,
+    /// Who received the payment, e.g. a charity or the publisher.
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnError")]
+    pub payee: Option<Payee>,
+
+    /// The order was bought as a gift, so its content belongs to whoever
+    /// received it.
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnError")]
+    pub is_gift: bool,
+
+    #[serde(default)]
+    pub tpkd_dict: TpkdDict,
 
     #[serde(rename = "product")]
     pub details: BundleDetails,
 
-    #[serde(rename = "subproducts")]
+    #[serde(rename = "subproducts", default)]
     #[serde_as(as = "VecSkipError<_>")]
     pub products: Vec<Product>,
 }
 
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Payee {
+    pub machine_name: String,
+    pub human_name: String,
+}
+
+/// The keys of a bundle, like Steam keys.
+#[serde_as]
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TpkdDict {
+    /// Keys that can't be read are skipped.
+    #[serde(default)]
+    #[serde_as(as = "VecSkipError<_>")]
+    pub all_tpks: Vec<Tpk>,
+}
+
+/// Humble's page that lists all keys of the user.
+pub const HUMBLE_KEYS_URL: &str = "https://www.humblebundle.com/home/keys";
+
+/// A key in a bundle. Missing or `null` fields get their default value, since
+/// the shape of the data differs between kinds of keys.
+#[serde_as]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Tpk {
+    #[serde_as(as = "DefaultOnNull")]
+    pub machine_name: String,
+    #[serde_as(as = "DefaultOnNull")]
+    pub human_name: String,
+    /// Where the key is redeemed, e.g. `steam`.
+    pub key_type: Option<String>,
+    /// The key itself, once it's revealed.
+    pub redeemed_key_val: Option<String>,
+}
+
+impl Tpk {
+    pub fn is_redeemed(&self) -> bool {
+        self.redeemed_key_val.is_some()
+    }
+
+    /// The page where the key is redeemed. Steam and GOG keys that are
+    /// revealed go to the store's own page with the key filled in, and all
+    /// others to Humble's keys page.
+    pub fn redeem_url(&self) -> String {
+        let key = self.redeemed_key_val.as_deref();
+        match (self.key_type.as_deref(), key) {
+            (Some("steam"), Some(key)) => {
+                format!(
+                    "https://store.steampowered.com/account/registerkey?key={}",
+                    key
+                )
+            }
+            (Some("gog"), Some(key)) => format!("https://www.gog.com/redeem/{}", key),
+            _ => HUMBLE_KEYS_URL.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct ProductKey {
     pub redeemed: bool,
     pub human_name: String,
@@ -65,41 +161,114 @@ impl Bundle {
         }
     }
 
-    pub fn product_keys(&self) -> Vec<ProductKey> {
-        let Some(tpks) = self.tpkd_dict.get("all_tpks") else {
-            return vec![];
-        };
-
-        let tpks = tpks.as_array().expect("cannot read all_tpks");
-
-        let mut result = vec![];
-        for tpk in tpks {
-            let redeemed = tpk["redeemed_key_val"].is_string();
-            let human_name = tpk["human_name"].as_str().unwrap_or("").to_owned();
-
-            result.push(ProductKey {
-                redeemed,
-                human_name,
-            });
+    /// The number of redeemed keys and of all keys, e.g. `7/10`. Empty if the
+    /// bundle has no keys.
+    pub fn key_counts(&self) -> String {
+        let product_keys = self.product_keys();
+        if product_keys.is_empty() {
+            return String::new();
         }
 
-        result
+        let redeemed_count = product_keys.iter().filter(|k| k.redeemed).count();
+        format!("{}/{}", redeemed_count, product_keys.len())
+    }
+
+    pub fn product_keys(&self) -> Vec<ProductKey> {
+        self.tpkd_dict
+            .all_tpks
+            .iter()
+            .map(|tpk| ProductKey {
+                redeemed: tpk.is_redeemed(),
+                human_name: tpk.human_name.clone(),
+            })
+            .collect()
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct BundleDetails {
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
     pub machine_name: String,
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
     pub human_name: String,
+    /// Kind of the order, e.g. `bundle` or `storefront`.
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+impl BundleDetails {
+    /// Return the score of a match against the bundle's human name or machine
+    /// name, or `None` if neither of them matches.
+    pub fn match_score(
+        &self,
+        keywords: &[&str],
+        mode: &MatchMode,
+        kind: &MatchKind,
+    ) -> Option<f64> {
+        let human = text_match_score(&self.human_name, keywords, mode, kind);
+        let machine = text_match_score(&self.machine_name, keywords, mode, kind);
+        match (human, machine) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// The series of the bundle, from the prefix Humble gives its names, e.g.
+    /// `Humble Book Bundle` for "Humble Book Bundle: Python by O'Reilly".
+    pub fn series(&self) -> Option<&str> {
+        const SERIES: [&str; 2] = ["Humble Choice", "Humble Monthly"];
+
+        let name = self.human_name.trim();
+        if let Some(series) = SERIES.iter().find(|s| name.contains(*s)) {
+            return Some(series);
+        }
+
+        name.split_once(':')
+            .map(|(prefix, _)| prefix.trim())
+            .filter(|prefix| !prefix.is_empty())
+    }
 }
 
 impl Bundle {
+    /// Whether this order was bought in the store, rather than being a bundle.
+    pub fn is_store_purchase(&self) -> bool {
+        self.details.category.as_deref() == Some("storefront")
+    }
+
+    /// Whether this is a Humble Choice month where no games were chosen yet,
+    /// so it has nothing to download or redeem.
+    pub fn is_locked(&self) -> bool {
+        self.is_membership() && self.products.is_empty() && self.tpkd_dict.all_tpks.is_empty()
+    }
+
+    /// Whether this order is a month of a Humble Choice or Humble Monthly
+    /// subscription.
+    pub fn is_membership(&self) -> bool {
+        let category = self.details.category.as_deref().unwrap_or_default();
+        category.starts_with("subscription")
+            || matches!(
+                self.details.series(),
+                Some("Humble Choice" | "Humble Monthly")
+            )
+    }
+
     pub fn total_size(&self) -> u64 {
         self.products.iter().map(|e| e.total_size()).sum()
     }
+
+    /// Format an amount of this order with its currency, e.g. `12.00 USD`.
+    pub fn format_amount(&self, amount: f64) -> String {
+        match &self.currency {
+            Some(currency) => format!("{:.2} {}", amount, currency),
+            None => format!("{:.2}", amount),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default)]
 pub struct Product {
     pub machine_name: String,
     pub human_name: String,
@@ -107,6 +276,10 @@ pub struct Product {
     #[serde(rename = "url")]
     pub product_details_url: String,
 
+    /// URL of the product's cover image or icon, if there is one.
+    #[serde(default)]
+    pub icon: Option<String>,
+
     /// List of associated downloads with this product.
     ///
     /// Note: Each product usually has one item here.
@@ -129,32 +302,126 @@ impl Product {
         self.formats_as_vec().join(", ")
     }
 
-    pub fn name_matches(&self, keywords: &[&str], mode: &MatchMode) -> bool {
-        let human_name = self.human_name.to_lowercase();
-        let mine: HashSet<&str> = human_name.split(" ").collect();
+    /// Check whether the product name matches the given lowercase keywords.
+    pub fn name_matches(&self, keywords: &[&str], mode: &MatchMode, kind: &MatchKind) -> bool {
+        text_matches(&self.human_name, keywords, mode, kind)
+    }
 
-        let mut kw_matched = 0;
-        for kw in keywords {
-            if !mine.contains(kw) {
-                continue;
-            }
+    /// Score how well the product name matches the given lowercase keywords,
+    /// tolerating typos. Returns `None` if the name doesn't match, otherwise a
+    /// score between 0 and 1 where higher is better.
+    pub fn fuzzy_score(&self, keywords: &[&str], mode: &MatchMode) -> Option<f64> {
+        text_fuzzy_score(&self.human_name, keywords, mode)
+    }
 
-            match mode {
-                MatchMode::Any => return true,
-                MatchMode::All => {
-                    kw_matched += 1;
-                    if kw_matched == keywords.len() {
-                        return true;
-                    }
-                }
-            }
-        }
+    /// Return the score of a match, or `None` if the name doesn't match.
+    ///
+    /// Only fuzzy matches are ranked. All other matches get a score of 1.
+    pub fn match_score(
+        &self,
+        keywords: &[&str],
+        mode: &MatchMode,
+        kind: &MatchKind,
+    ) -> Option<f64> {
+        text_match_score(&self.human_name, keywords, mode, kind)
+    }
+}
+
+fn text_match_score(
+    text: &str,
+    keywords: &[&str],
+    mode: &MatchMode,
+    kind: &MatchKind,
+) -> Option<f64> {
+    match kind {
+        MatchKind::Fuzzy => text_fuzzy_score(text, keywords, mode),
+        _ => text_matches(text, keywords, mode, kind).then_some(1.0),
+    }
+}
 
-        false
+fn text_matches(text: &str, keywords: &[&str], mode: &MatchMode, kind: &MatchKind) -> bool {
+    if let MatchKind::Fuzzy = kind {
+        return text_fuzzy_score(text, keywords, mode).is_some();
     }
+
+    let text = text.to_lowercase();
+    let keyword_matches = |kw: &&str| match kind {
+        MatchKind::Prefix => starts_a_word(&text, kw),
+        MatchKind::Contains => text.contains(kw),
+        MatchKind::Fuzzy => unreachable!(),
+    };
+
+    match mode {
+        MatchMode::Any => keywords.iter().any(keyword_matches),
+        MatchMode::All => keywords.iter().all(keyword_matches),
+    }
+}
+
+fn text_fuzzy_score(text: &str, keywords: &[&str], mode: &MatchMode) -> Option<f64> {
+    if keywords.is_empty() {
+        return None;
+    }
+
+    let text = text.to_lowercase();
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let scores: Vec<f64> = keywords
+        .iter()
+        .map(|kw| {
+            words
+                .iter()
+                .map(|w| keyword_similarity(kw, w))
+                .fold(0.0, f64::max)
+        })
+        .collect();
+
+    let matched = scores.iter().filter(|&&s| s >= FUZZY_THRESHOLD).count();
+    let is_match = match mode {
+        MatchMode::Any => matched > 0,
+        MatchMode::All => matched == keywords.len(),
+    };
+
+    if !is_match {
+        return None;
+    }
+
+    Some(scores.iter().sum::<f64>() / scores.len() as f64)
+}
+
+/// Minimum similarity for a keyword to count as a fuzzy match for a word.
+const FUZZY_THRESHOLD: f64 = 0.75;
+
+/// Similarity of a keyword and a word between 0 and 1, based on their edit distance.
+///
+/// The keyword is compared both with the whole word and with the beginning of
+/// the word, so partially typed words score high as well.
+fn keyword_similarity(keyword: &str, word: &str) -> f64 {
+    let keyword_len = keyword.chars().count();
+    let word_start: String = word.chars().take(keyword_len).collect();
+
+    [word, word_start.as_str()]
+        .iter()
+        .map(|w| {
+            let max_len = keyword_len.max(w.chars().count()).max(1);
+            1.0 - util::edit_distance(keyword, w) as f64 / max_len as f64
+        })
+        .fold(0.0, f64::max)
 }
 
-#[derive(Debug, Deserialize)]
+/// Check whether `needle` appears in `haystack` at the beginning of a word.
+fn starts_a_word(haystack: &str, needle: &str) -> bool {
+    haystack.match_indices(needle).any(|(idx, _)| {
+        haystack[..idx]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric())
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ProductDownload {
     #[serde(rename = "download_struct")]
     pub items: Vec<DownloadInfo>,
@@ -174,7 +441,7 @@ impl ProductDownload {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct DownloadInfo {
     pub md5: String,
 
@@ -186,13 +453,24 @@ pub struct DownloadInfo {
     pub url: DownloadUrl,
 }
 
-#[derive(Debug, Deserialize)]
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct DownloadUrl {
     pub web: String,
+    /// Empty if the file has no torrent.
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
     pub bittorrent: String,
 }
 
-#[derive(Debug, Deserialize)]
+impl DownloadUrl {
+    /// URL of the file's torrent, if it has one.
+    pub fn torrent(&self) -> Option<&str> {
+        Some(self.bittorrent.trim()).filter(|url| !url.is_empty())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GameKey {
     pub gamekey: String,
 }
@@ -200,13 +478,13 @@ pub struct GameKey {
 // ===========================================================================
 // Models related to the Bundle Choices
 // ===========================================================================
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct HumbleChoice {
     #[serde(rename = "contentChoiceOptions")]
     pub options: ContentChoiceOptions,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ContentChoiceOptions {
     #[serde(rename = "contentChoiceData")]
     pub data: ContentChoiceData,
@@ -217,27 +495,120 @@ pub struct ContentChoiceOptions {
     pub is_active_content: bool,
 
     pub title: String,
+
+    /// How many games can be chosen in the month, if there's a limit.
+    #[serde(rename = "MAX_CHOICES", default)]
+    pub max_choices: Option<usize>,
+
+    /// Games already chosen, by the identifier of the month's content.
+    #[serde(rename = "contentChoicesMade", default)]
+    pub choices_made: BTreeMap<String, ChoicesMade>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ChoicesMade {
+    #[serde(default)]
+    pub choices_made: Vec<String>,
+}
+
+impl ContentChoiceOptions {
+    /// Identifier of the month's content, which games are chosen for.
+    pub const PARENT_IDENTIFIER: &'static str = "initial";
+
+    /// Identifiers of the games that were chosen already.
+    pub fn chosen(&self) -> &[String] {
+        self.choices_made
+            .get(Self::PARENT_IDENTIFIER)
+            .map(|c| c.choices_made.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Split the games that aren't chosen yet into the ones to choose, up to
+    /// the month's limit, and the ones to skip with the reason. Games whose
+    /// keys can't be claimed anymore are skipped.
+    pub fn plan_choose_all(&self) -> ChoosePlan<'_> {
+        let chosen = self.chosen();
+        let mut remaining = self
+            .max_choices
+            .map(|max| max.saturating_sub(chosen.len()))
+            .unwrap_or(usize::MAX);
+
+        let mut plan = ChoosePlan::default();
+        for (id, game) in &self.data.game_data {
+            let reason = if chosen.contains(id) {
+                "already chosen"
+            } else if let Some(reason) = game
+                .tpkds
+                .iter()
+                .map(|t| t.unavailable_reason())
+                .reduce(|a, b| a.and(b))
+                .flatten()
+            {
+                reason
+            } else if remaining == 0 {
+                "choice limit reached"
+            } else {
+                remaining -= 1;
+                plan.choose.push((id, game));
+                continue;
+            };
+            plan.skip.push((game, reason));
+        }
+
+        plan
+    }
+}
+
+/// What `choose --all` does with the games of a month.
+#[derive(Debug, Default)]
+pub struct ChoosePlan<'a> {
+    /// Identifier and game.
+    pub choose: Vec<(&'a str, &'a GameData)>,
+    pub skip: Vec<(&'a GameData, &'static str)>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ContentChoiceData {
     pub game_data: BTreeMap<String, GameData>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GameData {
     pub title: String,
     pub tpkds: Vec<Tpkd>,
 }
 
-#[derive(Debug, Deserialize)]
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Tpkd {
     pub gamekey: Option<String>,
     pub human_name: String,
     pub redeemed_key_val: Option<String>,
+    /// The time to claim the key ran out.
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub is_expired: bool,
+    /// All keys of the game were given away.
+    #[serde(default)]
+    #[serde_as(as = "DefaultOnNull")]
+    pub sold_out: bool,
 }
 
 impl Tpkd {
+    /// Why an unclaimed key can't be claimed anymore, or `None` if it can
+    /// still be claimed or was claimed already.
+    pub fn unavailable_reason(&self) -> Option<&'static str> {
+        if self.redeemed_key_val.is_some() {
+            None
+        } else if self.is_expired {
+            Some("expired")
+        } else if self.sold_out {
+            Some("sold out")
+        } else {
+            None
+        }
+    }
+
     pub fn claim_status(&self) -> ClaimStatus {
         let redeemed = self.redeemed_key_val.is_some();
         let is_active = self.gamekey.is_some();
@@ -251,17 +622,58 @@ impl Tpkd {
     }
 }
 
+const MONTH_NAMES: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// The year Humble Monthly, which became Humble Choice, started.
+const FIRST_MONTHLY_YEAR: u16 = 2015;
+
 #[derive(Clone, Debug)]
 pub enum ChoicePeriod {
     Current,
     Date { month: String, year: u16 },
 }
 
-impl ToString for ChoicePeriod {
-    fn to_string(&self) -> String {
+impl ChoicePeriod {
+    /// All months from `from` to `to` (inclusive), both given as `(year, month)`
+    /// where months start at 1.
+    pub fn months_between(from: (u16, u32), to: (u16, u32)) -> Vec<ChoicePeriod> {
+        let mut periods = vec![];
+        let (mut year, mut month) = from;
+        while (year, month) <= to {
+            periods.push(ChoicePeriod::Date {
+                month: MONTH_NAMES[month as usize - 1].to_owned(),
+                year,
+            });
+
+            month += 1;
+            if month > 12 {
+                month = 1;
+                year += 1;
+            }
+        }
+
+        periods
+    }
+}
+
+impl fmt::Display for ChoicePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Current => "home".to_owned(),
-            Self::Date { month, year } => format!("{}-{}", month, year),
+            Self::Current => write!(f, "home"),
+            Self::Date { month, year } => write!(f, "{}-{}", month, year),
         }
     }
 }
@@ -275,28 +687,13 @@ impl TryFrom<&str> for ChoicePeriod {
             return Ok(ChoicePeriod::Current);
         }
 
-        let month_names = vec![
-            "january",
-            "february",
-            "march",
-            "april",
-            "may",
-            "june",
-            "july",
-            "august",
-            "september",
-            "october",
-            "november",
-            "december",
-        ];
-
         let parts: Vec<_> = value.split("-").collect();
         if parts.len() != 2 {
             return Err("invalid format. expected {month name}-{year}".to_owned());
         }
 
         let month = parts[0];
-        if !month_names.contains(&month) {
+        if !MONTH_NAMES.contains(&month) {
             return Err(format!("invalid month: {month}"));
         }
 
@@ -304,8 +701,14 @@ impl TryFrom<&str> for ChoicePeriod {
             .parse()
             .map_err(|e| format!("invalid year value: {}", e))?;
 
-        if year < 2018 || year > 2030 {
-            return Err("years out of 2018-2030 range are not supported".to_owned());
+        // There are no months before Humble Monthly started, or after the
+        // next year's.
+        let last_year = chrono::Local::now().year() as u16 + 1;
+        if !(FIRST_MONTHLY_YEAR..=last_year).contains(&year) {
+            return Err(format!(
+                "years out of {}-{} range are not supported",
+                FIRST_MONTHLY_YEAR, last_year
+            ));
         }
 
         Ok(ChoicePeriod::Date {
@@ -321,6 +724,121 @@ pub enum MatchMode {
     Any,
 }
 
+#[derive(Copy, Clone, Debug)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+impl TryFrom<&str> for OutputFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("invalid output format: {}", value)),
+        }
+    }
+}
+
+/// Order of the search results.
+#[derive(Copy, Clone, Debug)]
+pub enum SearchSort {
+    /// Best matches first, otherwise in bundle order.
+    Relevance,
+    /// By bundle name, then sub-item name.
+    Name,
+    /// Largest first.
+    Size,
+    /// Most recently purchased bundles first.
+    Date,
+}
+
+impl TryFrom<&str> for SearchSort {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "relevance" => Ok(SearchSort::Relevance),
+            "name" => Ok(SearchSort::Name),
+            "size" => Ok(SearchSort::Size),
+            "date" => Ok(SearchSort::Date),
+            _ => Err(format!("invalid sort order: {}", value)),
+        }
+    }
+}
+
+/// Order of the sub-items in `details`.
+#[derive(Copy, Clone, Debug)]
+pub enum ItemSort {
+    /// By sub-item name.
+    Name,
+    /// Largest first.
+    Size,
+    /// By formats, then sub-item name.
+    Format,
+}
+
+impl TryFrom<&str> for ItemSort {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "name" => Ok(ItemSort::Name),
+            "size" => Ok(ItemSort::Size),
+            "format" => Ok(ItemSort::Format),
+            _ => Err(format!("invalid sort order: {}", value)),
+        }
+    }
+}
+
+/// Order in which files are downloaded.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DownloadOrder {
+    /// In the order of the bundle.
+    #[default]
+    AsListed,
+    SmallestFirst,
+    LargestFirst,
+}
+
+impl DownloadOrder {
+    /// Sort `items` by their `size` in this order. Items of the same size
+    /// keep their order.
+    pub fn sort<T>(self, items: &mut [T], size: impl Fn(&T) -> u64) {
+        match self {
+            DownloadOrder::AsListed => {}
+            DownloadOrder::SmallestFirst => items.sort_by_cached_key(|i| size(i)),
+            DownloadOrder::LargestFirst => items.sort_by_cached_key(|i| std::cmp::Reverse(size(i))),
+        }
+    }
+}
+
+impl TryFrom<&str> for DownloadOrder {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "as-listed" => Ok(DownloadOrder::AsListed),
+            "smallest-first" => Ok(DownloadOrder::SmallestFirst),
+            "largest-first" => Ok(DownloadOrder::LargestFirst),
+            _ => Err(format!("invalid download order: {}", value)),
+        }
+    }
+}
+
+/// How a single keyword is compared against a name.
+#[derive(Copy, Clone, Debug)]
+pub enum MatchKind {
+    /// The keyword must match the beginning of a word, e.g. "prog" matches "Programming".
+    Prefix,
+    /// The keyword can appear anywhere in the name.
+    Contains,
+    /// The keyword can be misspelled, e.g. "pyhton" matches "Python".
+    Fuzzy,
+}
+
 impl TryFrom<&str> for MatchMode {
     type Error = String;
 
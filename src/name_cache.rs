@@ -0,0 +1,29 @@
+use anyhow::{anyhow, Context};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Names of the bundles by their key, so a bundle can be found by its name
+/// without reading all bundles every time.
+pub type BundleNames = BTreeMap<String, String>;
+
+/// Read the cached names. An empty list is returned if there is no cache, or
+/// it can't be read.
+pub fn load() -> BundleNames {
+    get_cache_file_name()
+        .ok()
+        .and_then(|file_name| std::fs::read_to_string(file_name).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(names: &BundleNames) -> Result<(), anyhow::Error> {
+    let file_name = get_cache_file_name()?;
+    std::fs::write(&file_name, serde_json::to_string_pretty(names)?)
+        .with_context(|| format!("failed to write `{}`", file_name.display()))
+}
+
+fn get_cache_file_name() -> anyhow::Result<PathBuf> {
+    let mut home = dirs::home_dir().ok_or_else(|| anyhow!("cannot find the home directory"))?;
+    home.push(".humble-cli-names.json");
+    Ok(home)
+}
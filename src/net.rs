@@ -0,0 +1,109 @@
+//! Options that apply to all HTTP clients, for the API and for downloads.
+
+use anyhow::Context;
+use reqwest::Certificate;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static STATE: OnceLock<State> = OnceLock::new();
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NetOptions {
+    /// Only connect over this IP version. Both are used if not set.
+    pub ip_version: Option<IpVersion>,
+    /// PEM files with extra root certificates to trust, e.g. of a proxy.
+    pub ca_certs: Vec<PathBuf>,
+    /// Don't verify certificates at all.
+    pub insecure: bool,
+}
+
+#[derive(Default)]
+struct State {
+    options: NetOptions,
+    certificates: Vec<Certificate>,
+}
+
+impl NetOptions {
+    /// Binding to the unspecified address of a family makes the client only
+    /// connect to addresses of that family.
+    fn local_address(&self) -> Option<IpAddr> {
+        match self.ip_version? {
+            IpVersion::V4 => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpVersion::V6 => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        }
+    }
+}
+
+/// Set the options for all clients that are built after this. Only the
+/// first call has an effect.
+pub fn set_net_options(options: NetOptions) -> Result<(), anyhow::Error> {
+    let mut certificates = vec![];
+    for path in &options.ca_certs {
+        let pem =
+            std::fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let bundle = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("invalid certificate in `{}`", path.display()))?;
+        if bundle.is_empty() {
+            anyhow::bail!("no certificates found in `{}`", path.display());
+        }
+        certificates.extend(bundle);
+    }
+
+    if options.insecure {
+        eprintln!(
+            "Warning: certificates are not verified. Only use --insecure on networks you trust."
+        );
+    }
+
+    let _ = STATE.set(State {
+        options,
+        certificates,
+    });
+    Ok(())
+}
+
+fn state() -> &'static State {
+    STATE.get_or_init(State::default)
+}
+
+pub fn client_builder() -> reqwest::ClientBuilder {
+    let state = state();
+    let mut builder = reqwest::Client::builder()
+        .local_address(state.options.local_address())
+        .danger_accept_invalid_certs(state.options.insecure);
+    for cert in &state.certificates {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    builder
+}
+
+pub fn blocking_client_builder() -> reqwest::blocking::ClientBuilder {
+    let state = state();
+    let mut builder = reqwest::blocking::Client::builder()
+        .local_address(state.options.local_address())
+        .danger_accept_invalid_certs(state.options.insecure);
+    for cert in &state.certificates {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    builder
+}
+
+#[test]
+fn test_local_address() {
+    let options = NetOptions {
+        ip_version: Some(IpVersion::V4),
+        ..Default::default()
+    };
+    assert_eq!(
+        options.local_address(),
+        Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    );
+    assert_eq!(NetOptions::default().local_address(), None);
+}
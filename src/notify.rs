@@ -0,0 +1,38 @@
+use crate::net;
+use crate::settings::{self, NotifyEvent};
+
+/// Post `message` to the configured webhook, if notifications are enabled
+/// for `event`.
+///
+/// Notifications are best-effort: problems are reported on stderr, but never
+/// fail the command that sent them.
+pub fn notify(event: NotifyEvent, message: &str) {
+    if let Err(e) = try_notify(event, message) {
+        eprintln!("Warning: failed to send notification: {:#}", e);
+    }
+}
+
+fn try_notify(event: NotifyEvent, message: &str) -> Result<(), anyhow::Error> {
+    let Some(notify) = settings::get_settings()?.notify else {
+        return Ok(());
+    };
+
+    if !notify.events.contains(&event) {
+        return Ok(());
+    }
+
+    // Discord reads `content` and Slack reads `text`.
+    let body = serde_json::json!({
+        "content": message,
+        "text": message,
+    });
+
+    net::blocking_client_builder()
+        .build()?
+        .post(&notify.webhook)
+        .json(&body)
+        .send()?
+        .error_for_status()?;
+
+    Ok(())
+}
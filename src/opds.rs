@@ -0,0 +1,263 @@
+use crate::archive::{self, ManifestEntry};
+use crate::calibre;
+use crate::report::html_escape as xml_escape;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+
+/// Characters that are escaped in the path segments of links.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// A downloaded e-book file.
+#[derive(Debug, PartialEq)]
+pub struct BookFile {
+    pub bundle_name: String,
+    /// Name of the sub-item, which is usually the book title.
+    pub title: String,
+    /// Path relative to the library directory.
+    pub path: PathBuf,
+}
+
+/// The part of a `<file>.info.json` sidecar that the catalog needs.
+#[derive(Deserialize)]
+struct FileInfo {
+    bundle_name: String,
+    human_name: String,
+}
+
+/// Find the e-books in `library_dir` that have an `.info.json` sidecar, or
+/// are listed in the `manifest.json` of an unpacked bundle archive. Files
+/// outside `library_dir`, e.g. through symlinks or `..` in a manifest, are
+/// left out.
+pub fn find_books(library_dir: &Path) -> Result<Vec<BookFile>, anyhow::Error> {
+    let library_dir = library_dir
+        .canonicalize()
+        .with_context(|| format!("cannot read `{}`", library_dir.display()))?;
+    // Path of an existing e-book relative to the library directory.
+    let relative = |path: &Path| {
+        let path = path.canonicalize().ok()?;
+        let relative = path.strip_prefix(&library_dir).ok()?.to_owned();
+        calibre::is_ebook(&path).then_some(relative)
+    };
+
+    let mut books = BTreeMap::new();
+    for file in archive::list_files(&library_dir)? {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+
+        if let Some(book_name) = name.strip_suffix(".info.json") {
+            let Some(path) = relative(&file.with_file_name(book_name)) else {
+                continue;
+            };
+            let Ok(info) = serde_json::from_slice::<FileInfo>(&std::fs::read(&file)?) else {
+                continue;
+            };
+            books.insert(
+                path.clone(),
+                BookFile {
+                    bundle_name: info.bundle_name,
+                    title: info.human_name,
+                    path,
+                },
+            );
+        } else if name == "manifest.json" {
+            let Some(bundle_dir) = file.parent() else {
+                continue;
+            };
+            let bundle_name = bundle_dir.file_name().unwrap_or_default().to_string_lossy();
+            let Ok(entries) = serde_json::from_slice::<Vec<ManifestEntry>>(&std::fs::read(&file)?)
+            else {
+                continue;
+            };
+            for entry in entries {
+                let inside = Path::new(&entry.path)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)));
+                let Some(path) = inside
+                    .then(|| relative(&bundle_dir.join(&entry.path)))
+                    .flatten()
+                else {
+                    continue;
+                };
+                books.entry(path.clone()).or_insert_with(|| BookFile {
+                    bundle_name: bundle_name.to_string(),
+                    title: entry.product,
+                    path,
+                });
+            }
+        }
+    }
+
+    Ok(books.into_values().collect())
+}
+
+/// Build an OPDS 1.2 acquisition feed with one entry per book. The formats
+/// of the same book are links of one entry. Links are relative to the
+/// library directory, where the feed is expected to be saved.
+pub fn render_feed(books: &[BookFile], updated: DateTime<Utc>) -> String {
+    let mut entries: BTreeMap<(&str, &str), Vec<&Path>> = BTreeMap::new();
+    for book in books {
+        entries
+            .entry((&book.title, &book.bundle_name))
+            .or_default()
+            .push(&book.path);
+    }
+
+    let updated = updated.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(xml, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#).unwrap();
+    writeln!(xml, "  <id>urn:humble-cli:library</id>").unwrap();
+    writeln!(xml, "  <title>Humble Bundle library</title>").unwrap();
+    writeln!(xml, "  <updated>{}</updated>", updated).unwrap();
+    writeln!(
+        xml,
+        r#"  <link rel="self" href="opds.xml" type="application/atom+xml;profile=opds-catalog;kind=acquisition"/>"#
+    )
+    .unwrap();
+
+    for ((title, bundle_name), paths) in entries {
+        let (title, author) = split_author(title);
+        writeln!(xml, "  <entry>").unwrap();
+        writeln!(xml, "    <title>{}</title>", xml_escape(title)).unwrap();
+        writeln!(
+            xml,
+            "    <id>urn:humble-cli:{}</id>",
+            xml_escape(&link(paths[0]))
+        )
+        .unwrap();
+        writeln!(xml, "    <updated>{}</updated>", updated).unwrap();
+        if let Some(author) = author {
+            writeln!(
+                xml,
+                "    <author><name>{}</name></author>",
+                xml_escape(author)
+            )
+            .unwrap();
+        }
+        writeln!(
+            xml,
+            r#"    <content type="text">From {}</content>"#,
+            xml_escape(bundle_name)
+        )
+        .unwrap();
+        for path in paths {
+            writeln!(
+                xml,
+                r#"    <link rel="http://opds-spec.org/acquisition" href="{}" type="{}"/>"#,
+                xml_escape(&link(path)),
+                mime_type(path)
+            )
+            .unwrap();
+        }
+        writeln!(xml, "  </entry>").unwrap();
+    }
+
+    writeln!(xml, "</feed>").unwrap();
+    xml
+}
+
+/// Split "Title by Author" into its parts. Most sub-items only have a title.
+fn split_author(title: &str) -> (&str, Option<&str>) {
+    match title.rsplit_once(" by ") {
+        Some((title, author)) if !title.is_empty() && !author.is_empty() => (title, Some(author)),
+        _ => (title, None),
+    }
+}
+
+pub(crate) fn link(path: &Path) -> String {
+    path.components()
+        .map(|c| utf8_percent_encode(&c.as_os_str().to_string_lossy(), PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "epub" => "application/epub+zip",
+        "pdf" => "application/pdf",
+        "mobi" => "application/x-mobipocket-ebook",
+        "azw3" => "application/vnd.amazon.ebook",
+        "cbz" => "application/vnd.comicbook+zip",
+        "cbr" => "application/vnd.comicbook-rar",
+        "djvu" => "image/vnd.djvu",
+        _ => "application/octet-stream",
+    }
+}
+
+#[test]
+fn test_render_feed() {
+    let book = |title: &str, path: &str| BookFile {
+        bundle_name: "Books & More".to_owned(),
+        title: title.to_owned(),
+        path: PathBuf::from(path),
+    };
+    let books = [
+        book(
+            "Rust in Action by Tim McNamara",
+            "Books/Rust in Action/rust.epub",
+        ),
+        book(
+            "Rust in Action by Tim McNamara",
+            "Books/Rust in Action/rust.pdf",
+        ),
+        book("Go #1", "Books/go.epub"),
+    ];
+    let updated = "2024-01-02T03:04:05Z".parse().unwrap();
+
+    let xml = render_feed(&books, updated);
+    assert_eq!(xml.matches("<entry>").count(), 2);
+    assert!(xml.contains("<title>Rust in Action</title>"));
+    assert!(xml.contains("<author><name>Tim McNamara</name></author>"));
+    assert!(xml.contains(r#"href="Books/Rust%20in%20Action/rust.pdf" type="application/pdf""#));
+    assert!(xml.contains(r#"href="Books/go.epub" type="application/epub+zip""#));
+    assert!(xml.contains("<content type=\"text\">From Books &amp; More</content>"));
+    assert!(xml.contains("<updated>2024-01-02T03:04:05Z</updated>"));
+}
+
+#[test]
+fn test_find_books_stays_in_library() {
+    let root = std::env::temp_dir().join(format!("humble-cli-opds-{}", std::process::id()));
+    let library = root.join("library");
+    let bundle = library.join("Bundle");
+    std::fs::create_dir_all(&bundle).unwrap();
+    std::fs::write(bundle.join("inside.epub"), "").unwrap();
+    std::fs::write(root.join("outside.epub"), "").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(root.join("outside.epub"), bundle.join("link.epub")).unwrap();
+
+    let entry = |path: &str| {
+        serde_json::json!({
+            "product": "Book", "format": "EPUB", "path": path, "md5": "", "file_size": 0
+        })
+    };
+    let manifest = serde_json::json!([
+        entry("inside.epub"),
+        entry("../../outside.epub"),
+        entry(&root.join("outside.epub").to_string_lossy()),
+        entry("link.epub"),
+    ]);
+    std::fs::write(bundle.join("manifest.json"), manifest.to_string()).unwrap();
+
+    let books = find_books(&library);
+    std::fs::remove_dir_all(&root).unwrap();
+    let paths: Vec<_> = books.unwrap().into_iter().map(|b| b.path).collect();
+    assert_eq!(paths, [PathBuf::from("Bundle/inside.epub")]);
+}
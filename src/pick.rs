@@ -0,0 +1,77 @@
+use crate::bundle_download::DownloadOptions;
+use crate::models::Bundle;
+use crate::{download_bundle, product_summary, show_bundle_details, DateOptions};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{FuzzySelect, Select};
+
+/// Ask for one of `bundles` and what to do with it.
+pub fn pick(bundles: &[Bundle]) -> Result<(), anyhow::Error> {
+    if bundles.is_empty() {
+        println!("No bundles found");
+        return Ok(());
+    }
+
+    let theme = ColorfulTheme::default();
+    let bundle_names: Vec<String> = bundles
+        .iter()
+        .map(|b| format!("{} ({})", b.details.human_name, b.gamekey))
+        .collect();
+
+    let Some(idx) = FuzzySelect::with_theme(&theme)
+        .with_prompt("Bundle")
+        .items(&bundle_names)
+        .default(0)
+        .interact_opt()?
+    else {
+        return Ok(());
+    };
+    let bundle = &bundles[idx];
+
+    let actions = [
+        "Show details",
+        "Download all items",
+        "Download a single item",
+    ];
+    let Some(action) = Select::with_theme(&theme)
+        .with_prompt("Action")
+        .items(&actions)
+        .default(0)
+        .interact_opt()?
+    else {
+        return Ok(());
+    };
+
+    match action {
+        0 => show_bundle_details(&bundle.gamekey, &DateOptions::default(), None),
+        1 => {
+            let options = DownloadOptions {
+                yes: true,
+                ..Default::default()
+            };
+            download_bundle(&bundle.gamekey, &options)
+        }
+        _ => {
+            if bundle.products.is_empty() {
+                println!("This bundle has no items to download");
+                return Ok(());
+            }
+
+            let product_names: Vec<String> = bundle.products.iter().map(product_summary).collect();
+
+            let Some(item) = FuzzySelect::with_theme(&theme)
+                .with_prompt("Item")
+                .items(&product_names)
+                .default(0)
+                .interact_opt()?
+            else {
+                return Ok(());
+            };
+
+            let options = DownloadOptions {
+                item_numbers: Some((item + 1).to_string()),
+                ..Default::default()
+            };
+            download_bundle(&bundle.gamekey, &options)
+        }
+    }
+}
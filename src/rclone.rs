@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Context};
+use std::path::Path;
+use std::process::Command;
+
+const PREFIX: &str = "rclone:";
+
+/// An rclone remote used as the final destination of downloaded files.
+///
+/// Files are downloaded locally first and then moved to the remote with
+/// `rclone moveto`, so any backend supported by rclone can be used.
+#[derive(Debug, PartialEq)]
+pub struct RcloneDest {
+    remote: String,
+}
+
+impl RcloneDest {
+    /// Parse a destination in the `rclone:remote:path` format.
+    pub fn parse(dest: &str) -> Result<Self, anyhow::Error> {
+        let remote = dest.strip_prefix(PREFIX).ok_or_else(|| {
+            anyhow!(
+                "unsupported destination '{}'. Expected rclone:remote:path",
+                dest
+            )
+        })?;
+
+        if !remote.contains(':') {
+            return Err(anyhow!(
+                "invalid rclone remote '{}'. Expected remote:path",
+                remote
+            ));
+        }
+
+        Ok(Self {
+            remote: remote.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    /// Full rclone path for a file, given its path relative to the download root.
+    pub fn remote_path(&self, relative_path: &str) -> String {
+        if self.remote.ends_with(':') {
+            format!("{}{}", self.remote, relative_path)
+        } else {
+            format!("{}/{}", self.remote, relative_path)
+        }
+    }
+
+    /// Move `local_path` to `relative_path` under the remote.
+    pub fn upload(&self, local_path: &Path, relative_path: &str) -> Result<(), anyhow::Error> {
+        let remote_path = self.remote_path(relative_path);
+        let status = Command::new("rclone")
+            .arg("moveto")
+            .arg(local_path)
+            .arg(&remote_path)
+            .status()
+            .context("failed to run rclone. Is it installed and in PATH?")?;
+
+        if !status.success() {
+            return Err(anyhow!(
+                "rclone failed to move '{}' to '{}' ({})",
+                local_path.display(),
+                remote_path,
+                status
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_rclone_dest() {
+    let test_data = vec![
+        ("rclone:nas:books", Some("nas:books/Bundle/a.epub")),
+        ("rclone:nas:books/", Some("nas:books/Bundle/a.epub")),
+        ("rclone:gdrive:", Some("gdrive:Bundle/a.epub")),
+        ("rclone:nas", None),
+        ("nas:books", None),
+    ];
+
+    for (input, expected) in test_data {
+        let got = RcloneDest::parse(input).ok();
+        assert_eq!(
+            got.map(|d| d.remote_path("Bundle/a.epub")),
+            expected.map(str::to_owned),
+            "input: {}",
+            input
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::models::Bundle;
+use serde::Serialize;
+
+#[derive(Copy, Clone, Debug)]
+pub enum ReceiptFormat {
+    Csv,
+    Json,
+}
+
+impl TryFrom<&str> for ReceiptFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "csv" => Ok(ReceiptFormat::Csv),
+            "json" => Ok(ReceiptFormat::Json),
+            _ => Err(format!("invalid receipt format: {}", value)),
+        }
+    }
+}
+
+/// One order, with the fields that matter for accounting.
+#[derive(Debug, Serialize)]
+struct Receipt<'a> {
+    date: String,
+    name: &'a str,
+    /// Amount paid, or the total of the order if Humble doesn't say.
+    amount: Option<f64>,
+    currency: Option<&'a str>,
+    /// The order's key, which Humble shows on its receipts.
+    reference: &'a str,
+    payee: Option<&'a str>,
+}
+
+impl<'a> Receipt<'a> {
+    fn new(bundle: &'a Bundle) -> Self {
+        Self {
+            date: bundle.created.format("%Y-%m-%d").to_string(),
+            name: &bundle.details.human_name,
+            amount: bundle.amount_spent.or(bundle.total),
+            currency: bundle.currency.as_deref(),
+            reference: &bundle.gamekey,
+            payee: bundle
+                .payee
+                .as_ref()
+                .map(|p| p.human_name.as_str())
+                .filter(|name| !name.is_empty()),
+        }
+    }
+}
+
+/// Render all orders, oldest first.
+pub fn render(format: ReceiptFormat, bundles: &[Bundle]) -> Result<String, serde_json::Error> {
+    let mut receipts: Vec<_> = bundles.iter().map(Receipt::new).collect();
+    receipts.sort_by(|a, b| a.date.cmp(&b.date));
+
+    match format {
+        ReceiptFormat::Csv => Ok(render_csv(&receipts)),
+        ReceiptFormat::Json => serde_json::to_string_pretty(&receipts).map(|json| json + "\n"),
+    }
+}
+
+fn render_csv(receipts: &[Receipt]) -> String {
+    let mut out = String::from("date,name,amount,currency,reference,payee\n");
+    for r in receipts {
+        let fields = [
+            r.date.clone(),
+            r.name.to_owned(),
+            r.amount.map(|a| format!("{:.2}", a)).unwrap_or_default(),
+            r.currency.unwrap_or_default().to_owned(),
+            r.reference.to_owned(),
+            r.payee.unwrap_or_default().to_owned(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_escape(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quote a CSV field if it has a comma, quote or line break.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[test]
+fn test_csv_escape() {
+    assert_eq!(csv_escape("plain"), "plain");
+    assert_eq!(csv_escape("a, b"), "\"a, b\"");
+    assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+}
+
+#[test]
+fn test_render_csv() {
+    let bundle: Bundle = serde_json::from_str(
+        r#"{
+            "gamekey": "abc123",
+            "created": "2023-05-01T10:00:00",
+            "amount_spent": 12,
+            "currency": "USD",
+            "product": {"machine_name": "x", "human_name": "Bundle, with comma"}
+        }"#,
+    )
+    .unwrap();
+
+    let csv = render(ReceiptFormat::Csv, &[bundle]).unwrap();
+    assert_eq!(
+        csv,
+        "date,name,amount,currency,reference,payee\n\
+        2023-05-01,\"Bundle, with comma\",12.00,USD,abc123,\n"
+    );
+}
@@ -0,0 +1,105 @@
+use crate::net;
+use anyhow::Context;
+use serde::Deserialize;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/smbl64/humble-cli/releases/latest";
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+impl Release {
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+}
+
+/// Fetch the latest published release from GitHub.
+pub fn latest_release() -> Result<Release, reqwest::Error> {
+    net::blocking_client_builder()
+        .build()?
+        .get(LATEST_RELEASE_URL)
+        // GitHub rejects requests without a user agent
+        .header(reqwest::header::USER_AGENT, clap::crate_name!())
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()?
+        .error_for_status()?
+        .json()
+}
+
+/// Check whether version `latest` is newer than `current`. Both are expected
+/// in the `major.minor.patch` format. Pre-release suffixes are ignored.
+pub fn is_newer(current: &str, latest: &str) -> bool {
+    fn parse(version: &str) -> Vec<u64> {
+        version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|n| n.parse().unwrap_or(0))
+            .collect()
+    }
+
+    parse(latest) > parse(current)
+}
+
+/// Print the version, and the latest release if it's newer and `check` is set.
+pub fn print_version(check: bool) -> Result<(), anyhow::Error> {
+    let current = clap::crate_version!();
+    println!("{} {}", clap::crate_name!(), current);
+
+    if !check {
+        return Ok(());
+    }
+
+    let latest = latest_release().context("failed to check for new releases")?;
+    if !is_newer(current, latest.version()) {
+        println!("You are using the latest version.");
+        return Ok(());
+    }
+
+    println!();
+    println!("A new version is available: {}", latest.version());
+    if let Some(body) = latest
+        .body
+        .as_deref()
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+    {
+        // Only show the beginning of the changelog
+        println!();
+        for line in body.lines().take(15) {
+            println!("  {}", line);
+        }
+    }
+    println!();
+    println!("See {}", latest.html_url);
+    Ok(())
+}
+
+#[test]
+fn test_is_newer() {
+    let test_data = vec![
+        ("0.16.5", "0.16.5", false),
+        ("0.16.5", "0.16.6", true),
+        ("0.16.5", "0.17.0", true),
+        ("0.16.5", "1.0.0", true),
+        ("0.16.5", "0.9.0", false),
+        ("0.16.5", "0.16.6-beta", true),
+        ("1.0.0", "0.16.5", false),
+    ];
+
+    for (current, latest, expected) in test_data {
+        assert_eq!(
+            is_newer(current, latest),
+            expected,
+            "{} -> {}",
+            current,
+            latest
+        );
+    }
+}
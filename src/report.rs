@@ -0,0 +1,210 @@
+use crate::models::Bundle;
+use crate::util;
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl TryFrom<&str> for ReportFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => Err(format!("invalid report format: {}", value)),
+        }
+    }
+}
+
+/// Render the whole library as a single document.
+pub fn render(format: ReportFormat, bundles: &[Bundle]) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(bundles),
+        ReportFormat::Html => render_html(bundles),
+    }
+}
+
+fn library_size(bundles: &[Bundle]) -> String {
+    util::humanize_bytes(bundles.iter().map(|b| b.total_size()).sum())
+}
+
+fn render_markdown(bundles: &[Bundle]) -> String {
+    // Writing to a String never fails, so the results are ignored.
+    let mut out = String::new();
+    let _ = writeln!(out, "# Humble Bundle Library\n");
+    let _ = writeln!(
+        out,
+        "{} bundle(s), {} in total.\n",
+        bundles.len(),
+        library_size(bundles)
+    );
+
+    for b in bundles {
+        let _ = writeln!(
+            out,
+            "- [{}](#{})",
+            md_escape(&b.details.human_name),
+            b.gamekey.to_lowercase()
+        );
+    }
+
+    for b in bundles {
+        let _ = writeln!(out);
+        let _ = writeln!(out, "## {}\n", md_escape(&b.details.human_name));
+        let _ = writeln!(out, "<a id=\"{}\"></a>\n", b.gamekey.to_lowercase());
+        let _ = writeln!(out, "- Key: `{}`", b.gamekey);
+        let _ = writeln!(out, "- Purchased: {}", b.created.format("%Y-%m-%d"));
+        let _ = writeln!(out, "- Size: {}", util::humanize_bytes(b.total_size()));
+        let _ = writeln!(out, "- Claimed: {}", b.claim_status());
+
+        if !b.products.is_empty() {
+            let _ = writeln!(out, "\n| # | Sub-item | Format | Size |");
+            let _ = writeln!(out, "|--:|---|---|--:|");
+            for (idx, p) in b.products.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} |",
+                    idx + 1,
+                    md_escape(&p.human_name),
+                    md_escape(&p.formats()),
+                    util::humanize_bytes(p.total_size())
+                );
+            }
+        }
+
+        let keys = b.product_keys();
+        if !keys.is_empty() {
+            let _ = writeln!(out, "\n| Key Name | Redeemed |");
+            let _ = writeln!(out, "|---|:-:|");
+            for k in keys {
+                let redeemed = if k.redeemed { "Yes" } else { "No" };
+                let _ = writeln!(out, "| {} | {} |", md_escape(&k.human_name), redeemed);
+            }
+        }
+    }
+
+    out
+}
+
+fn render_html(bundles: &[Bundle]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>");
+    let _ = writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">");
+    let _ = writeln!(out, "<title>Humble Bundle Library</title>");
+    let _ = writeln!(
+        out,
+        "<style>body {{ font-family: sans-serif; }} \
+        table {{ border-collapse: collapse; }} \
+        td, th {{ border: 1px solid #ccc; padding: 2px 8px; }}</style>"
+    );
+    let _ = writeln!(out, "</head>\n<body>");
+    let _ = writeln!(out, "<h1>Humble Bundle Library</h1>");
+    let _ = writeln!(
+        out,
+        "<p>{} bundle(s), {} in total.</p>",
+        bundles.len(),
+        library_size(bundles)
+    );
+
+    let _ = writeln!(out, "<ul>");
+    for b in bundles {
+        let _ = writeln!(
+            out,
+            "<li><a href=\"#{}\">{}</a></li>",
+            html_escape(&b.gamekey),
+            html_escape(&b.details.human_name)
+        );
+    }
+    let _ = writeln!(out, "</ul>");
+
+    for b in bundles {
+        let _ = writeln!(
+            out,
+            "<h2 id=\"{}\">{}</h2>",
+            html_escape(&b.gamekey),
+            html_escape(&b.details.human_name)
+        );
+        let _ = writeln!(out, "<ul>");
+        let _ = writeln!(
+            out,
+            "<li>Key: <code>{}</code></li>",
+            html_escape(&b.gamekey)
+        );
+        let _ = writeln!(out, "<li>Purchased: {}</li>", b.created.format("%Y-%m-%d"));
+        let _ = writeln!(
+            out,
+            "<li>Size: {}</li>",
+            util::humanize_bytes(b.total_size())
+        );
+        let _ = writeln!(out, "<li>Claimed: {}</li>", b.claim_status());
+        let _ = writeln!(out, "</ul>");
+
+        if !b.products.is_empty() {
+            let _ = writeln!(
+                out,
+                "<table>\n<tr><th>#</th><th>Sub-item</th><th>Format</th><th>Size</th></tr>"
+            );
+            for (idx, p) in b.products.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    idx + 1,
+                    html_escape(&p.human_name),
+                    html_escape(&p.formats()),
+                    util::humanize_bytes(p.total_size())
+                );
+            }
+            let _ = writeln!(out, "</table>");
+        }
+
+        let keys = b.product_keys();
+        if !keys.is_empty() {
+            let _ = writeln!(
+                out,
+                "<h3>Keys</h3>\n<table>\n<tr><th>Key Name</th><th>Redeemed</th></tr>"
+            );
+            for k in keys {
+                let redeemed = if k.redeemed { "Yes" } else { "No" };
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td></tr>",
+                    html_escape(&k.human_name),
+                    redeemed
+                );
+            }
+            let _ = writeln!(out, "</table>");
+        }
+    }
+
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
+
+fn md_escape(input: &str) -> String {
+    input.replace('|', "\\|")
+}
+
+pub(crate) fn html_escape(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn test_html_escape() {
+    assert_eq!(
+        html_escape("<b>Tom & \"Jerry\"</b>"),
+        "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;"
+    );
+}
+
+#[test]
+fn test_md_escape() {
+    assert_eq!(md_escape("A | B"), "A \\| B");
+}
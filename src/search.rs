@@ -0,0 +1,243 @@
+use crate::models::{Bundle, MatchKind, MatchMode, OutputFormat, Product, SearchSort};
+use crate::{bundle_passes_claimed_filter, product_passes_filters};
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+
+/// Options for `search`.
+#[derive(Debug)]
+pub struct SearchOptions {
+    pub match_mode: MatchMode,
+    pub match_kind: MatchKind,
+    /// Only show sub-items with any of these formats (lowercase). Empty means all formats.
+    pub formats: Vec<String>,
+    /// Only show sub-items smaller than this many bytes. `0` means no limit.
+    pub max_size: u64,
+    /// Only show bundles whose keys are claimed (`yes`), unclaimed (`no`) or `all`.
+    pub claimed: String,
+    pub output: OutputFormat,
+    /// Fields to print, from `SEARCH_FIELDS`. Empty means the default table
+    /// or all fields in JSON output.
+    pub fields: Vec<String>,
+    pub sort: SearchSort,
+    /// Show at most this many results. `None` means no limit.
+    pub limit: Option<usize>,
+}
+
+/// Fields of a search result that can be selected with `--field`.
+pub const SEARCH_FIELDS: [&str; 12] = [
+    "key", "name", "item", "sub-item", "match", "format", "size", "spent", "charged", "total",
+    "currency", "payee",
+];
+
+pub fn search(
+    bundles: &[Bundle],
+    keywords: &str,
+    options: &SearchOptions,
+) -> Result<(), anyhow::Error> {
+    let keywords = keywords.to_lowercase();
+    let keywords: Vec<&str> = keywords.split_whitespace().collect();
+    let match_mode = &options.match_mode;
+    let match_kind = &options.match_kind;
+    let passes_filters =
+        |p: &Product| product_passes_filters(p, &options.formats, options.max_size);
+
+    let mut search_result: Vec<SearchHit> = vec![];
+
+    for b in bundles {
+        if !bundle_passes_claimed_filter(b, &options.claimed) {
+            continue;
+        }
+
+        // A bundle is only shown if it has something that passes the filters
+        let bundle_score = b.details.match_score(&keywords, match_mode, match_kind);
+        if let Some(score) = bundle_score.filter(|_| b.products.iter().any(passes_filters)) {
+            search_result.push(SearchHit {
+                bundle: b,
+                product: None,
+                score,
+            });
+        }
+
+        for (idx, p) in b.products.iter().enumerate() {
+            if !passes_filters(p) {
+                continue;
+            }
+
+            if let Some(score) = p.match_score(&keywords, match_mode, match_kind) {
+                search_result.push(SearchHit {
+                    bundle: b,
+                    product: Some((idx + 1, p)),
+                    score,
+                });
+            }
+        }
+    }
+
+    // Fuzzy matches are shown best first. Other matches all have the same
+    // score, so the stable sort keeps them in bundle order.
+    search_result.sort_by(|a, b| b.score.total_cmp(&a.score));
+    match options.sort {
+        SearchSort::Relevance => {}
+        SearchSort::Name => search_result.sort_by_cached_key(|hit| {
+            (
+                hit.bundle.details.human_name.to_lowercase(),
+                hit.product.map(|(_, p)| p.human_name.to_lowercase()),
+            )
+        }),
+        SearchSort::Size => search_result.sort_by_key(|hit| std::cmp::Reverse(hit.size())),
+        SearchSort::Date => search_result.sort_by_key(|hit| std::cmp::Reverse(hit.bundle.created)),
+    }
+
+    if let Some(limit) = options.limit {
+        search_result.truncate(limit);
+    }
+
+    let fields: Vec<&str> = if options.fields.is_empty() {
+        SEARCH_FIELDS.to_vec()
+    } else {
+        options.fields.iter().map(String::as_str).collect()
+    };
+
+    if let OutputFormat::Json = options.output {
+        let records: Vec<_> = search_result
+            .iter()
+            .map(|hit| {
+                fields
+                    .iter()
+                    .map(|&f| (f.to_owned(), hit.field(f)))
+                    .collect::<serde_json::Map<_, _>>()
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+
+    if !options.fields.is_empty() {
+        for hit in &search_result {
+            let values: Vec<String> = fields
+                .iter()
+                .map(|&f| match hit.field(f) {
+                    serde_json::Value::String(s) => s,
+                    serde_json::Value::Null => String::new(),
+                    v => v.to_string(),
+                })
+                .collect();
+            println!("{}", values.join("\t"));
+        }
+        return Ok(());
+    }
+
+    if search_result.is_empty() {
+        println!("Nothing found");
+        return Ok(());
+    }
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Key", "Name", "#", "Sub Item", "Match"]);
+    let mut prev_key = "";
+    for hit in &search_result {
+        let (item_number, sub_item, match_type) = match hit.product {
+            Some((n, p)) => (n.to_string(), p.human_name.as_str(), "Sub-item"),
+            None => ("-".to_owned(), "-", "Bundle"),
+        };
+
+        // Only show the bundle once for consecutive rows of the same bundle
+        let (key, name) = if hit.bundle.gamekey == prev_key {
+            ("", "")
+        } else {
+            (
+                hit.bundle.gamekey.as_str(),
+                hit.bundle.details.human_name.as_str(),
+            )
+        };
+        prev_key = &hit.bundle.gamekey;
+
+        builder.push_record([key, name, item_number.as_str(), sub_item, match_type]);
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(1)).with(Alignment::left()))
+        .with(Modify::new(Columns::single(2)).with(Alignment::right()))
+        .with(Modify::new(Columns::single(3)).with(Alignment::left()))
+        .to_string();
+
+    println!("{table}");
+
+    print_download_hints(&search_result);
+    Ok(())
+}
+
+/// Print a `download` command for every bundle in the search result, selecting
+/// the matched sub-items.
+fn print_download_hints(search_result: &[SearchHit]) {
+    // Keep the bundles in the order they first appear in the result
+    let mut hints: Vec<(&str, Vec<usize>)> = vec![];
+    for hit in search_result {
+        let Some((item_number, _)) = hit.product else {
+            continue;
+        };
+
+        match hints.iter_mut().find(|(key, _)| *key == hit.bundle.gamekey) {
+            Some((_, numbers)) => numbers.push(item_number),
+            None => hints.push((&hit.bundle.gamekey, vec![item_number])),
+        }
+    }
+
+    if hints.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("To download the matched items, run:");
+    for (key, mut numbers) in hints {
+        numbers.sort();
+        let numbers = numbers
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        println!("  {} download {} -i {}", clap::crate_name!(), key, numbers);
+    }
+}
+
+/// A bundle or one of its sub-items that matched a search.
+struct SearchHit<'a> {
+    bundle: &'a Bundle,
+    /// Item number and the matched sub-item, or `None` if the bundle itself matched.
+    product: Option<(usize, &'a Product)>,
+    score: f64,
+}
+
+impl SearchHit<'_> {
+    /// Value of one of the `SEARCH_FIELDS` for structured output.
+    fn field(&self, name: &str) -> serde_json::Value {
+        use serde_json::Value;
+
+        match (name, self.product) {
+            ("key", _) => Value::from(self.bundle.gamekey.as_str()),
+            ("name", _) => Value::from(self.bundle.details.human_name.as_str()),
+            ("item", Some((n, _))) => Value::from(n),
+            ("sub-item", Some((_, p))) => Value::from(p.human_name.as_str()),
+            ("match", Some(_)) => Value::from("sub-item"),
+            ("match", None) => Value::from("bundle"),
+            ("format", Some((_, p))) => Value::from(p.formats()),
+            ("size", _) => Value::from(self.size()),
+            ("spent", _) => Value::from(self.bundle.amount_spent),
+            ("charged", _) => Value::from(self.bundle.amount_to_charge),
+            ("total", _) => Value::from(self.bundle.total),
+            ("currency", _) => Value::from(self.bundle.currency.as_deref()),
+            ("payee", _) => Value::from(self.bundle.payee.as_ref().map(|p| p.human_name.as_str())),
+            _ => Value::Null,
+        }
+    }
+
+    /// Size of the matched sub-item, or the whole bundle.
+    fn size(&self) -> u64 {
+        match self.product {
+            Some((_, p)) => p.total_size(),
+            None => self.bundle.total_size(),
+        }
+    }
+}
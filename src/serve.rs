@@ -0,0 +1,248 @@
+use crate::history::{self, HistoryEntry};
+use crate::name_cache;
+use crate::opds;
+use crate::report::html_escape;
+use crate::util;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+
+/// A bundle in the cached library, with the files that were downloaded from it.
+#[derive(Debug, Serialize)]
+struct ServedBundle {
+    key: String,
+    name: String,
+    files: Vec<ServedFile>,
+}
+
+#[derive(Debug, Serialize)]
+struct ServedFile {
+    machine_name: String,
+    format: String,
+    size: u64,
+    /// Link to download the file from the server.
+    url: String,
+}
+
+/// Serve the cached bundle names and the downloaded files in `library_dir`
+/// over HTTP until the process is stopped. Nothing is read from Humble, and
+/// nothing can be changed through the server.
+pub fn serve(library_dir: &Path, address: &str, port: u16) -> Result<(), anyhow::Error> {
+    let library_dir = library_dir.canonicalize()?;
+    let listener = TcpListener::bind((address, port))?;
+    println!(
+        "Serving {} on http://{}:{}/ (Ctrl+C to stop)",
+        library_dir.display(),
+        address,
+        port
+    );
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let library_dir = library_dir.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &library_dir) {
+                eprintln!("Warning: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, library_dir: &Path) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are not needed, but are read so the client isn't cut off.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next());
+    let Some(target) = target.filter(|_| method == "GET") else {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    };
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+
+    match path {
+        "/" => {
+            let html = render_index(&library(library_dir));
+            respond(
+                &mut stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                html.as_bytes(),
+            )
+        }
+        "/api/bundles" => {
+            let json = serde_json::to_vec_pretty(&library(library_dir))?;
+            respond(&mut stream, "200 OK", "application/json", &json)
+        }
+        _ => match path.strip_prefix("/files/").and_then(file_path) {
+            Some(relative) => send_file(&mut stream, library_dir, &relative),
+            None => respond(&mut stream, "404 Not Found", "text/plain", b"Not found"),
+        },
+    }
+}
+
+/// The cached bundles with their downloaded files that are still in
+/// `library_dir`, sorted by name.
+fn library(library_dir: &Path) -> Vec<ServedBundle> {
+    let mut bundles: BTreeMap<String, ServedBundle> = name_cache::load()
+        .into_iter()
+        .map(|(key, name)| {
+            let bundle = ServedBundle {
+                key: key.clone(),
+                name,
+                files: vec![],
+            };
+            (key, bundle)
+        })
+        .collect();
+
+    let entries = history::load().unwrap_or_default();
+    for (_, entry) in history::last_downloads(entries) {
+        let Some(url) = file_url(library_dir, &entry) else {
+            continue;
+        };
+        bundles
+            .entry(entry.bundle_key.clone())
+            .or_insert_with(|| ServedBundle {
+                key: entry.bundle_key.clone(),
+                name: entry.bundle_name.clone(),
+                files: vec![],
+            })
+            .files
+            .push(ServedFile {
+                machine_name: entry.machine_name,
+                format: entry.format,
+                size: entry.file_size,
+                url,
+            });
+    }
+
+    let mut bundles: Vec<_> = bundles.into_values().collect();
+    for bundle in bundles.iter_mut() {
+        bundle.files.sort_by(|a, b| a.url.cmp(&b.url));
+    }
+    bundles.sort_by_key(|b| b.name.to_lowercase());
+    bundles
+}
+
+/// Link of a downloaded file, if it still exists in `library_dir`.
+fn file_url(library_dir: &Path, entry: &HistoryEntry) -> Option<String> {
+    let path = entry.path.canonicalize().ok()?;
+    let relative = path.strip_prefix(library_dir).ok()?;
+    path.is_file()
+        .then(|| format!("/files/{}", opds::link(relative)))
+}
+
+/// The relative path of a file link, or `None` if it would leave the
+/// library directory.
+fn file_path(encoded: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(encoded).decode_utf8().ok()?;
+    let path = PathBuf::from(decoded.as_ref());
+    let inside = path.components().all(|c| matches!(c, Component::Normal(_)));
+    (inside && !encoded.is_empty()).then_some(path)
+}
+
+fn send_file(stream: &mut TcpStream, library_dir: &Path, relative: &Path) -> io::Result<()> {
+    // Symlinks may point out of the library directory.
+    let path = library_dir
+        .join(relative)
+        .canonicalize()
+        .ok()
+        .filter(|path| path.starts_with(library_dir) && path.is_file());
+    let Some(path) = path else {
+        return respond(stream, "404 Not Found", "text/plain", b"Not found");
+    };
+
+    let mut file = File::open(&path)?;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        opds::mime_type(&path),
+        file.metadata()?.len()
+    )?;
+    io::copy(&mut file, stream)?;
+    Ok(())
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn render_index(bundles: &[ServedBundle]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "<!DOCTYPE html>");
+    let _ = writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">");
+    let _ = writeln!(out, "<title>Humble Bundle Library</title>");
+    let _ = writeln!(out, "<style>body {{ font-family: sans-serif; }}</style>");
+    let _ = writeln!(out, "</head>\n<body>");
+    let _ = writeln!(out, "<h1>Humble Bundle Library</h1>");
+    let _ = writeln!(
+        out,
+        "<p>{} bundle(s). Also available as <a href=\"/api/bundles\">JSON</a>.</p>",
+        bundles.len()
+    );
+
+    for b in bundles {
+        let _ = writeln!(out, "<h2>{}</h2>", html_escape(&b.name));
+        if b.files.is_empty() {
+            let _ = writeln!(out, "<p>No downloaded files.</p>");
+            continue;
+        }
+        let _ = writeln!(out, "<ul>");
+        for f in &b.files {
+            let name = f.url.rsplit('/').next().unwrap_or_default();
+            let name = percent_decode_str(name).decode_utf8_lossy();
+            let _ = writeln!(
+                out,
+                "<li><a href=\"{}\">{}</a> ({}, {})</li>",
+                html_escape(&f.url),
+                html_escape(&name),
+                html_escape(&f.format),
+                util::humanize_bytes(f.size)
+            );
+        }
+        let _ = writeln!(out, "</ul>");
+    }
+
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
+
+#[test]
+fn test_file_path() {
+    assert_eq!(
+        file_path("Bundle/My%20Book/book.epub"),
+        Some(PathBuf::from("Bundle/My Book/book.epub"))
+    );
+    assert_eq!(file_path("../secret"), None);
+    assert_eq!(file_path("%2e%2e/secret"), None);
+    assert_eq!(file_path("Bundle/%2E%2E/%2E%2E/secret"), None);
+    assert_eq!(file_path("%2Fetc%2Fpasswd"), None);
+    assert_eq!(file_path(""), None);
+}
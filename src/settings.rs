@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Optional settings, read from `~/.humble-cli.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub notify: Option<NotifySettings>,
+    #[serde(default)]
+    pub download: DownloadSettings,
+    #[serde(default)]
+    pub hooks: HookSettings,
+    pub torrents: Option<TorrentSettings>,
+}
+
+/// The BitTorrent client that `download --push-torrents` adds torrents to.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TorrentSettings {
+    pub client: TorrentClient,
+    /// Address of the qBittorrent Web UI, e.g. `http://localhost:8080`, or the
+    /// Transmission RPC URL, e.g. `http://localhost:9091/transmission/rpc`.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TorrentClient {
+    Qbittorrent,
+    Transmission,
+}
+
+impl std::fmt::Display for TorrentClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TorrentClient::Qbittorrent => write!(f, "qBittorrent"),
+            TorrentClient::Transmission => write!(f, "Transmission"),
+        }
+    }
+}
+
+/// Shell commands to run on events. Each gets the event as JSON on stdin.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookSettings {
+    /// Run by `check` for every new bundle.
+    pub on_new_bundle: Option<String>,
+    /// Run after all files of a bundle are downloaded.
+    pub on_download_complete: Option<String>,
+    /// Run when a command fails.
+    pub on_failure: Option<String>,
+}
+
+/// Defaults for the retry options of `download` and `bulk-download`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DownloadSettings {
+    pub retries: Option<u32>,
+    /// Seconds to wait before the first retry.
+    pub retry_delay: Option<u64>,
+    #[serde(default)]
+    pub backoff: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifySettings {
+    /// Webhook URL, e.g. of a Discord or Slack channel.
+    pub webhook: String,
+    /// Events to send. All events are sent if this is not set.
+    #[serde(default = "NotifyEvent::all")]
+    pub events: Vec<NotifyEvent>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyEvent {
+    NewBundles,
+    KeyChanges,
+    DownloadFinished,
+    Failure,
+}
+
+impl NotifyEvent {
+    fn all() -> Vec<NotifyEvent> {
+        vec![
+            NotifyEvent::NewBundles,
+            NotifyEvent::KeyChanges,
+            NotifyEvent::DownloadFinished,
+            NotifyEvent::Failure,
+        ]
+    }
+}
+
+/// Read the settings file. Default settings are used if it doesn't exist.
+pub fn get_settings() -> Result<Settings, anyhow::Error> {
+    let file_name = get_settings_file_name()?;
+    if !file_name.exists() {
+        return Ok(Settings::default());
+    }
+
+    let content = std::fs::read_to_string(&file_name)
+        .with_context(|| format!("failed to read `{}`", file_name.display()))?;
+    parse_settings(&content)
+        .with_context(|| format!("invalid settings in `{}`", file_name.display()))
+}
+
+fn parse_settings(content: &str) -> Result<Settings, toml::de::Error> {
+    toml::from_str(content)
+}
+
+fn get_settings_file_name() -> anyhow::Result<PathBuf> {
+    let mut home = dirs::home_dir().ok_or_else(|| anyhow!("cannot find the home directory"))?;
+    home.push(".humble-cli.toml");
+    Ok(home)
+}
+
+#[test]
+fn test_parse_settings() {
+    let settings = parse_settings("").unwrap();
+    assert!(settings.notify.is_none());
+
+    let settings = parse_settings(
+        r#"
+        [notify]
+        webhook = "https://example.com/hook"
+        "#,
+    )
+    .unwrap();
+    let notify = settings.notify.unwrap();
+    assert_eq!(notify.webhook, "https://example.com/hook");
+    assert_eq!(notify.events, NotifyEvent::all());
+
+    let settings = parse_settings(
+        r#"
+        [notify]
+        webhook = "https://example.com/hook"
+        events = ["new-bundles", "failure"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        settings.notify.unwrap().events,
+        vec![NotifyEvent::NewBundles, NotifyEvent::Failure]
+    );
+
+    assert!(parse_settings("[notify]\nevents = []").is_err());
+
+    let settings = parse_settings("[download]\nretries = 5\nretry-delay = 2").unwrap();
+    assert_eq!(settings.download.retries, Some(5));
+    assert_eq!(settings.download.retry_delay, Some(2));
+    assert!(!settings.download.backoff);
+
+    let settings = parse_settings("[hooks]\non_failure = \"./alert.sh\"").unwrap();
+    assert_eq!(settings.hooks.on_failure.as_deref(), Some("./alert.sh"));
+    assert!(settings.hooks.on_new_bundle.is_none());
+    assert!(parse_settings("[hooks]\non_start = \"true\"").is_err());
+
+    let settings = parse_settings(
+        r#"
+        [torrents]
+        client = "transmission"
+        url = "http://localhost:9091/transmission/rpc"
+        "#,
+    )
+    .unwrap();
+    let torrents = settings.torrents.unwrap();
+    assert_eq!(torrents.client, TorrentClient::Transmission);
+    assert!(torrents.username.is_none());
+    assert!(parse_settings("[torrents]\nclient = \"deluge\"\nurl = \"x\"").is_err());
+}
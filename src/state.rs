@@ -0,0 +1,166 @@
+use crate::models::Bundle;
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// A snapshot of the library, saved between runs of `check` to detect changes.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LibraryState {
+    pub bundles: BTreeMap<String, BundleState>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BundleState {
+    pub name: String,
+    /// Whether each key (by name) is redeemed.
+    pub keys: BTreeMap<String, bool>,
+}
+
+/// A difference between two snapshots of the library.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Change {
+    NewBundle {
+        key: String,
+        name: String,
+    },
+    NewKey {
+        bundle_key: String,
+        key_name: String,
+        redeemed: bool,
+    },
+    KeyChanged {
+        bundle_key: String,
+        key_name: String,
+        redeemed: bool,
+    },
+}
+
+impl LibraryState {
+    pub fn from_bundles(bundles: &[Bundle]) -> Self {
+        let bundles = bundles
+            .iter()
+            .map(|b| {
+                let state = BundleState {
+                    name: b.details.human_name.clone(),
+                    keys: b
+                        .product_keys()
+                        .into_iter()
+                        .map(|k| (k.human_name, k.redeemed))
+                        .collect(),
+                };
+                (b.gamekey.clone(), state)
+            })
+            .collect();
+
+        Self { bundles }
+    }
+
+    /// List what changed from `previous` to this state.
+    pub fn changes_since(&self, previous: &LibraryState) -> Vec<Change> {
+        let mut changes = vec![];
+        for (bundle_key, bundle) in &self.bundles {
+            let Some(old_bundle) = previous.bundles.get(bundle_key) else {
+                changes.push(Change::NewBundle {
+                    key: bundle_key.clone(),
+                    name: bundle.name.clone(),
+                });
+                continue;
+            };
+
+            for (key_name, &redeemed) in &bundle.keys {
+                let (bundle_key, key_name) = (bundle_key.clone(), key_name.clone());
+                match old_bundle.keys.get(&key_name) {
+                    None => changes.push(Change::NewKey {
+                        bundle_key,
+                        key_name,
+                        redeemed,
+                    }),
+                    Some(&old) if old != redeemed => changes.push(Change::KeyChanged {
+                        bundle_key,
+                        key_name,
+                        redeemed,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+/// Read the saved state, or `None` if nothing has been saved yet.
+pub fn load() -> Result<Option<LibraryState>, anyhow::Error> {
+    let file_name = get_state_file_name()?;
+    if !file_name.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&file_name)
+        .with_context(|| format!("failed to read `{}`", file_name.display()))?;
+    let state = serde_json::from_str(&content)
+        .with_context(|| format!("failed to parse `{}`", file_name.display()))?;
+    Ok(Some(state))
+}
+
+pub fn save(state: &LibraryState) -> Result<(), anyhow::Error> {
+    let file_name = get_state_file_name()?;
+    std::fs::write(&file_name, serde_json::to_string_pretty(state)?)
+        .with_context(|| format!("failed to write `{}`", file_name.display()))?;
+    Ok(())
+}
+
+fn get_state_file_name() -> anyhow::Result<PathBuf> {
+    let mut home = dirs::home_dir().ok_or_else(|| anyhow!("cannot find the home directory"))?;
+    home.push(".humble-cli-state.json");
+    Ok(home)
+}
+
+#[test]
+fn test_changes_since() {
+    let bundle = |name: &str, keys: &[(&str, bool)]| BundleState {
+        name: name.to_owned(),
+        keys: keys.iter().map(|&(k, r)| (k.to_owned(), r)).collect(),
+    };
+
+    let mut previous = LibraryState::default();
+    previous.bundles.insert(
+        "a".to_owned(),
+        bundle("Bundle A", &[("Game 1", false), ("Game 2", true)]),
+    );
+
+    let mut current = LibraryState::default();
+    current.bundles.insert(
+        "a".to_owned(),
+        bundle(
+            "Bundle A",
+            &[("Game 1", true), ("Game 2", true), ("Game 3", false)],
+        ),
+    );
+    current
+        .bundles
+        .insert("b".to_owned(), bundle("Bundle B", &[]));
+
+    assert_eq!(
+        current.changes_since(&previous),
+        vec![
+            Change::KeyChanged {
+                bundle_key: "a".to_owned(),
+                key_name: "Game 1".to_owned(),
+                redeemed: true,
+            },
+            Change::NewKey {
+                bundle_key: "a".to_owned(),
+                key_name: "Game 3".to_owned(),
+                redeemed: false,
+            },
+            Change::NewBundle {
+                key: "b".to_owned(),
+                name: "Bundle B".to_owned(),
+            },
+        ]
+    );
+    assert!(previous.changes_since(&previous).is_empty());
+}
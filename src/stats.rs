@@ -0,0 +1,146 @@
+use crate::models::{Bundle, Product, ProductKey};
+use crate::util;
+use chrono::Datelike;
+use std::collections::BTreeMap;
+use tabled::settings::object::Columns;
+use tabled::settings::{Alignment, Modify, Style};
+
+/// Print the totals of `bundles`, and the optional tables of `show_stats`.
+pub fn show_stats(
+    bundles: &[Bundle],
+    by_format: bool,
+    largest: Option<usize>,
+) -> Result<(), anyhow::Error> {
+    let item_count: usize = bundles.iter().map(|b| b.products.len()).sum();
+    let total_size: u64 = bundles.iter().map(|b| b.total_size()).sum();
+    let keys: Vec<ProductKey> = bundles.iter().flat_map(|b| b.product_keys()).collect();
+    let redeemed_count = keys.iter().filter(|k| k.redeemed).count();
+
+    println!("Bundles:      {}", bundles.len());
+    println!("Items:        {}", item_count);
+    println!("Total size:   {}", util::humanize_bytes(total_size));
+    println!(
+        "Keys:         {} ({} claimed, {} unclaimed)",
+        keys.len(),
+        redeemed_count,
+        keys.len() - redeemed_count
+    );
+
+    if bundles.is_empty() {
+        return Ok(());
+    }
+
+    let mut by_year: BTreeMap<i32, (usize, u64)> = BTreeMap::new();
+    for b in bundles {
+        let (count, size) = by_year.entry(b.created.year()).or_default();
+        *count += 1;
+        *size += b.total_size();
+    }
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Year", "Bundles", "Size"]);
+    for (year, (count, size)) in by_year {
+        builder.push_record([
+            year.to_string(),
+            count.to_string(),
+            util::humanize_bytes(size),
+        ]);
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::new(1..)).with(Alignment::right()))
+        .to_string();
+
+    println!();
+    println!("{table}");
+
+    if by_format {
+        println!();
+        print_size_by_format(bundles);
+    }
+
+    if let Some(count) = largest {
+        print_largest_items(bundles, count);
+    }
+
+    Ok(())
+}
+
+fn print_largest_items(bundles: &[Bundle], count: usize) {
+    let mut items: Vec<(&Bundle, usize, &Product)> = bundles
+        .iter()
+        .flat_map(|b| {
+            b.products
+                .iter()
+                .enumerate()
+                .map(move |(idx, p)| (b, idx + 1, p))
+        })
+        .collect();
+    items.sort_by_key(|(_, _, p)| std::cmp::Reverse(p.total_size()));
+    items.truncate(count);
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Size", "Key", "#", "Sub Item", "Format"]);
+    for (bundle, item_number, product) in items {
+        builder.push_record([
+            util::humanize_bytes(product.total_size()),
+            bundle.gamekey.clone(),
+            item_number.to_string(),
+            product.human_name.clone(),
+            product.formats(),
+        ]);
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::single(0)).with(Alignment::right()))
+        .with(Modify::new(Columns::single(2)).with(Alignment::right()))
+        .with(Modify::new(Columns::single(3)).with(Alignment::left()))
+        .to_string();
+
+    println!();
+    println!("{table}");
+}
+
+fn print_size_by_format(bundles: &[Bundle]) {
+    let mut by_format: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    let files = bundles
+        .iter()
+        .flat_map(|b| &b.products)
+        .flat_map(|p| &p.downloads)
+        .flat_map(|d| &d.items);
+    for file in files {
+        let (count, size) = by_format.entry(file.format.to_lowercase()).or_default();
+        *count += 1;
+        *size += file.file_size;
+    }
+
+    // Largest formats first
+    let mut by_format: Vec<_> = by_format.into_iter().collect();
+    by_format.sort_by_key(|(_, (_, size))| std::cmp::Reverse(*size));
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.set_header(["Format", "Files", "Size"]);
+    for (format, (count, size)) in by_format {
+        builder.push_record([format, count.to_string(), util::humanize_bytes(size)]);
+    }
+
+    let table = builder
+        .build()
+        .with(Style::psql())
+        .with(Modify::new(Columns::new(1..)).with(Alignment::right()))
+        .to_string();
+
+    println!("{table}");
+}
+
+/// Print the size of each format, with a note on how to use them.
+pub fn list_formats(bundles: &[Bundle]) {
+    print_size_by_format(bundles);
+
+    println!();
+    println!("Formats are case-insensitive when used with --format.");
+}
@@ -0,0 +1,155 @@
+use crate::net;
+use crate::settings::{TorrentClient, TorrentSettings};
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_TYPE, REFERER};
+use reqwest::StatusCode;
+use std::path::Path;
+
+const TRANSMISSION_SESSION_ID: &str = "X-Transmission-Session-Id";
+
+/// Add the torrent at `torrent_path` to the BitTorrent client of `settings`,
+/// which saves the downloaded file in `save_dir`.
+pub fn push(
+    settings: &TorrentSettings,
+    torrent_path: &Path,
+    save_dir: &Path,
+) -> Result<(), anyhow::Error> {
+    let torrent = std::fs::read(torrent_path)?;
+    let filename = torrent_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let save_dir = save_dir.to_string_lossy();
+
+    // qBittorrent keeps the login in a cookie.
+    let client = net::blocking_client_builder().cookie_store(true).build()?;
+    match settings.client {
+        TorrentClient::Qbittorrent => {
+            push_qbittorrent(&client, settings, &torrent, &filename, &save_dir)
+        }
+        TorrentClient::Transmission => push_transmission(&client, settings, &torrent, &save_dir),
+    }
+    .with_context(|| format!("failed to add {} to {}", filename, settings.client))
+}
+
+fn push_qbittorrent(
+    client: &Client,
+    settings: &TorrentSettings,
+    torrent: &[u8],
+    filename: &str,
+    save_dir: &str,
+) -> Result<(), anyhow::Error> {
+    let url = settings.url.trim_end_matches('/');
+
+    // The Web API rejects requests without a matching Referer.
+    if let Some(username) = &settings.username {
+        let password = settings.password.as_deref().unwrap_or_default();
+        let reply = client
+            .post(format!("{}/api/v2/auth/login", url))
+            .header(REFERER, url)
+            .form(&[("username", username.as_str()), ("password", password)])
+            .send()?
+            .error_for_status()?
+            .text()?;
+        if reply.trim() != "Ok." {
+            return Err(anyhow!("the username or password was not accepted"));
+        }
+    }
+
+    let boundary = format!("humble-cli-{:016x}", fastrand::u64(..));
+    let reply = client
+        .post(format!("{}/api/v2/torrents/add", url))
+        .header(REFERER, url)
+        .header(
+            CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .body(multipart_body(&boundary, torrent, filename, save_dir))
+        .send()?
+        .error_for_status()?
+        .text()?;
+    if reply.trim() == "Fails." {
+        return Err(anyhow!("qBittorrent rejected the torrent"));
+    }
+    Ok(())
+}
+
+/// A `multipart/form-data` body for qBittorrent with the torrent file and
+/// its save path.
+fn multipart_body(boundary: &str, torrent: &[u8], filename: &str, save_dir: &str) -> Vec<u8> {
+    let filename = filename.replace(['"', '\r', '\n'], "_");
+    let mut body = format!(
+        "--{b}\r\n\
+        Content-Disposition: form-data; name=\"savepath\"\r\n\r\n\
+        {save_dir}\r\n\
+        --{b}\r\n\
+        Content-Disposition: form-data; name=\"torrents\"; filename=\"{filename}\"\r\n\
+        Content-Type: application/x-bittorrent\r\n\r\n",
+        b = boundary,
+    )
+    .into_bytes();
+    body.extend_from_slice(torrent);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+fn push_transmission(
+    client: &Client,
+    settings: &TorrentSettings,
+    torrent: &[u8],
+    save_dir: &str,
+) -> Result<(), anyhow::Error> {
+    let body = serde_json::json!({
+        "method": "torrent-add",
+        "arguments": {
+            "metainfo": base64::engine::general_purpose::STANDARD.encode(torrent),
+            "download-dir": save_dir,
+        },
+    });
+
+    // Transmission answers the first request with 409 and the session ID to
+    // send with the next one.
+    let mut session_id = String::new();
+    for _ in 0..2 {
+        let mut request = client
+            .post(&settings.url)
+            .header(TRANSMISSION_SESSION_ID, &session_id)
+            .json(&body);
+        if let Some(username) = &settings.username {
+            request = request.basic_auth(username, settings.password.as_ref());
+        }
+
+        let res = request.send()?;
+        if res.status() == StatusCode::CONFLICT {
+            session_id = res
+                .headers()
+                .get(TRANSMISSION_SESSION_ID)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_owned();
+            continue;
+        }
+
+        let reply: serde_json::Value = res.error_for_status()?.json()?;
+        return match reply["result"].as_str() {
+            Some("success") => Ok(()),
+            Some(result) => Err(anyhow!("Transmission replied: {}", result)),
+            None => Err(anyhow!("unexpected reply from Transmission")),
+        };
+    }
+
+    Err(anyhow!("Transmission didn't accept the session ID"))
+}
+
+#[test]
+fn test_multipart_body() {
+    let body = multipart_body("XYZ", b"d4:infoe", "a \"b\".torrent", "/books/Bundle");
+    let body = String::from_utf8(body).unwrap();
+
+    assert!(body.starts_with("--XYZ\r\n"));
+    assert!(body.contains("name=\"savepath\"\r\n\r\n/books/Bundle\r\n"));
+    assert!(body.contains("filename=\"a _b_.torrent\"\r\n"));
+    assert!(body.ends_with("\r\n\r\nd4:infoe\r\n--XYZ--\r\n"));
+}
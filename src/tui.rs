@@ -0,0 +1,404 @@
+use crate::bundle_download::DownloadOptions;
+use crate::models::{Bundle, MatchKind, MatchMode};
+use crate::util;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+const HELP: &str = "↑/↓ move  Tab switch pane  Space mark  d download  / filter  q quit";
+
+#[derive(Copy, Clone, PartialEq)]
+enum Focus {
+    Bundles,
+    Products,
+}
+
+struct App {
+    bundles: Vec<Bundle>,
+    /// Indices of the bundles that match the filter.
+    visible: Vec<usize>,
+    filter: String,
+    editing_filter: bool,
+    focus: Focus,
+    bundle_state: ListState,
+    product_state: TableState,
+    /// Marked item numbers (starting at 1) per bundle index.
+    marked: BTreeMap<usize, BTreeSet<usize>>,
+    status: String,
+}
+
+/// Run the terminal UI until the user quits.
+pub fn run(bundles: Vec<Bundle>) -> Result<(), anyhow::Error> {
+    let mut app = App::new(bundles);
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn new(bundles: Vec<Bundle>) -> Self {
+        let mut app = Self {
+            bundles,
+            visible: vec![],
+            filter: String::new(),
+            editing_filter: false,
+            focus: Focus::Bundles,
+            bundle_state: ListState::default(),
+            product_state: TableState::default(),
+            marked: BTreeMap::new(),
+            status: String::new(),
+        };
+        app.apply_filter();
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), anyhow::Error> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if self.editing_filter {
+                match key.code {
+                    KeyCode::Enter => self.editing_filter = false,
+                    KeyCode::Esc => {
+                        self.editing_filter = false;
+                        self.filter.clear();
+                        self.apply_filter();
+                    }
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                        self.apply_filter();
+                    }
+                    KeyCode::Char(c) => {
+                        self.filter.push(c);
+                        self.apply_filter();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('/') => self.editing_filter = true,
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::PageUp => self.move_selection(-10),
+                KeyCode::PageDown => self.move_selection(10),
+                KeyCode::Left | KeyCode::Char('h') => self.focus = Focus::Bundles,
+                KeyCode::Right | KeyCode::Char('l') => self.focus_products(),
+                KeyCode::Tab => match self.focus {
+                    Focus::Bundles => self.focus_products(),
+                    Focus::Products => self.focus = Focus::Bundles,
+                },
+                KeyCode::Char(' ') => self.toggle_mark(),
+                KeyCode::Char('d') => {
+                    // Leave the UI while downloading, so the usual progress
+                    // bars are shown.
+                    ratatui::restore();
+                    let result = self.download();
+                    enable_raw_mode()?;
+                    execute!(io::stdout(), EnterAlternateScreen)?;
+                    terminal.clear()?;
+
+                    self.status = match result {
+                        Ok(status) => status,
+                        Err(e) => format!("Download failed: {}", e),
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn apply_filter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        let keywords: Vec<&str> = filter.split_whitespace().collect();
+        let (mode, kind) = (MatchMode::All, MatchKind::Contains);
+
+        self.visible = self
+            .bundles
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| {
+                keywords.is_empty()
+                    || b.details.match_score(&keywords, &mode, &kind).is_some()
+                    || b.products
+                        .iter()
+                        .any(|p| p.name_matches(&keywords, &mode, &kind))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+
+        self.bundle_state.select(step(None, self.visible.len(), 0));
+        self.reset_product_selection();
+    }
+
+    fn selected_bundle(&self) -> Option<&Bundle> {
+        self.selected_bundle_index().map(|idx| &self.bundles[idx])
+    }
+
+    fn selected_bundle_index(&self) -> Option<usize> {
+        self.bundle_state
+            .selected()
+            .and_then(|idx| self.visible.get(idx).copied())
+    }
+
+    fn reset_product_selection(&mut self) {
+        let count = self.selected_bundle().map_or(0, |b| b.products.len());
+        self.product_state.select(step(None, count, 0));
+        if count == 0 {
+            self.focus = Focus::Bundles;
+        }
+    }
+
+    fn focus_products(&mut self) {
+        if self.product_state.selected().is_some() {
+            self.focus = Focus::Products;
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        match self.focus {
+            Focus::Bundles => {
+                let selected = step(self.bundle_state.selected(), self.visible.len(), delta);
+                self.bundle_state.select(selected);
+                self.reset_product_selection();
+            }
+            Focus::Products => {
+                let count = self.selected_bundle().map_or(0, |b| b.products.len());
+                let selected = step(self.product_state.selected(), count, delta);
+                self.product_state.select(selected);
+            }
+        }
+    }
+
+    /// Mark or unmark the selected item, or all items of the selected bundle.
+    fn toggle_mark(&mut self) {
+        let Some(idx) = self.selected_bundle_index() else {
+            return;
+        };
+
+        let count = self.bundles[idx].products.len();
+        let marks = self.marked.entry(idx).or_default();
+        match (self.focus, self.product_state.selected()) {
+            (Focus::Products, Some(item)) => {
+                if !marks.remove(&(item + 1)) {
+                    marks.insert(item + 1);
+                }
+            }
+            _ if marks.len() == count => marks.clear(),
+            _ => marks.extend(1..=count),
+        }
+
+        if marks.is_empty() {
+            self.marked.remove(&idx);
+        }
+    }
+
+    /// Download the marked items, or the whole selected bundle if nothing
+    /// is marked.
+    fn download(&mut self) -> Result<String, anyhow::Error> {
+        let jobs: Vec<(&Bundle, Option<String>)> = if self.marked.is_empty() {
+            self.selected_bundle()
+                .map(|b| (b, None))
+                .into_iter()
+                .collect()
+        } else {
+            self.marked
+                .iter()
+                .map(|(&idx, items)| {
+                    let items = items
+                        .iter()
+                        .map(|n| n.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    (&self.bundles[idx], Some(items))
+                })
+                .collect()
+        };
+
+        if jobs.is_empty() {
+            return Ok("Nothing to download".to_owned());
+        }
+
+        let mut result = Ok(());
+        for (bundle, item_numbers) in &jobs {
+            println!("Downloading {}", bundle.details.human_name);
+            let options = DownloadOptions {
+                item_numbers: item_numbers.clone(),
+                yes: true,
+                ..Default::default()
+            };
+            result = crate::download_bundle(&bundle.gamekey, &options);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        println!();
+        println!("Press Enter to go back");
+        io::stdin().read_line(&mut String::new())?;
+
+        let status = format!("Downloaded {} bundle(s)", jobs.len());
+        result?;
+        self.marked.clear();
+        Ok(status)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [filter_area, main_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [bundles_area, right_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main_area);
+        let [products_area, keys_area] =
+            Layout::vertical([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .areas(right_area);
+
+        let highlight = Style::new().add_modifier(Modifier::REVERSED);
+        let pane = |title: String, focused: bool| {
+            let block = Block::bordered().title(title);
+            if focused {
+                block.yellow()
+            } else {
+                block
+            }
+        };
+
+        let filter_title = if self.editing_filter {
+            "Filter (Enter to apply, Esc to clear)"
+        } else {
+            "Filter (/)"
+        };
+        frame.render_widget(
+            Paragraph::new(self.filter.as_str())
+                .block(pane(filter_title.to_owned(), self.editing_filter)),
+            filter_area,
+        );
+
+        let bundle_items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .map(|&idx| {
+                let b = &self.bundles[idx];
+                let mark = match self.marked.get(&idx) {
+                    Some(m) if m.len() == b.products.len() => "[x]",
+                    Some(_) => "[-]",
+                    None => "[ ]",
+                };
+                ListItem::new(format!("{} {}", mark, b.details.human_name))
+            })
+            .collect();
+        let bundle_list = List::new(bundle_items)
+            .block(pane(
+                format!("Bundles ({})", self.visible.len()),
+                self.focus == Focus::Bundles,
+            ))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(bundle_list, bundles_area, &mut self.bundle_state);
+
+        let selected = self.selected_bundle_index();
+        let bundle = selected.map(|idx| &self.bundles[idx]);
+        let marks = selected.and_then(|idx| self.marked.get(&idx));
+
+        let product_rows: Vec<Row> = bundle
+            .map(|b| b.products.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                let mark = if marks.is_some_and(|m| m.contains(&(idx + 1))) {
+                    "[x]"
+                } else {
+                    "[ ]"
+                };
+                Row::new([
+                    mark.to_owned(),
+                    (idx + 1).to_string(),
+                    p.human_name.clone(),
+                    p.formats(),
+                    util::humanize_bytes(p.total_size()),
+                ])
+            })
+            .collect();
+        let product_table = Table::new(
+            product_rows,
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(10),
+                Constraint::Length(16),
+                Constraint::Length(10),
+            ],
+        )
+        .header(Row::new(["", "#", "Name", "Format", "Size"]).bold())
+        .block(pane("Items".to_owned(), self.focus == Focus::Products))
+        .row_highlight_style(highlight);
+        frame.render_stateful_widget(product_table, products_area, &mut self.product_state);
+
+        let key_rows: Vec<Row> = bundle
+            .map(|b| b.product_keys())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|k| {
+                let redeemed = if k.redeemed { "Yes" } else { "No" };
+                Row::new([k.human_name, redeemed.to_owned()])
+            })
+            .collect();
+        let key_table = Table::new(key_rows, [Constraint::Min(10), Constraint::Length(8)])
+            .header(Row::new(["Key Name", "Redeemed"]).bold())
+            .block(pane("Keys".to_owned(), false));
+        frame.render_widget(key_table, keys_area);
+
+        let help = if self.status.is_empty() {
+            HELP.to_owned()
+        } else {
+            format!("{}  |  {}", self.status, HELP)
+        };
+        frame.render_widget(Paragraph::new(help), help_area);
+    }
+}
+
+/// Move a selection by `delta` within a list of `len` items, staying in bounds.
+fn step(selected: Option<usize>, len: usize, delta: isize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+
+    let current = selected.unwrap_or(0) as isize;
+    Some((current + delta).clamp(0, len as isize - 1) as usize)
+}
+
+#[test]
+fn test_step() {
+    let test_data = vec![
+        (None, 0, 1, None),
+        (None, 5, 0, Some(0)),
+        (Some(0), 5, -1, Some(0)),
+        (Some(2), 5, 1, Some(3)),
+        (Some(3), 5, 10, Some(4)),
+        (Some(4), 5, -10, Some(0)),
+    ];
+
+    for (selected, len, delta, expected) in test_data {
+        assert_eq!(step(selected, len, delta), expected);
+    }
+}
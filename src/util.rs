@@ -1,22 +1,126 @@
 use byte_unit::{Byte, UnitType};
-use std::{collections::HashSet, future::Future};
+use indicatif::MultiProgress;
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use std::cell::RefCell;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+use std::{collections::HashSet, fs::File, future::Future, path::Path};
+
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Send the status messages of downloads to stderr, so stdout only has
+/// output for other programs.
+pub fn set_status_to_stderr(enabled: bool) {
+    STATUS_TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+pub fn status_to_stderr() -> bool {
+    STATUS_TO_STDERR.load(Ordering::Relaxed)
+}
+
+static ERRORS_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Also print errors as JSON objects on stderr, for tools that run us.
+pub fn set_errors_json(enabled: bool) {
+    ERRORS_JSON.store(enabled, Ordering::Relaxed);
+}
+
+pub fn errors_json() -> bool {
+    ERRORS_JSON.load(Ordering::Relaxed)
+}
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Never ask the user anything and take the safe default instead, e.g. for
+/// scripts and cron.
+pub fn set_non_interactive(enabled: bool) {
+    NON_INTERACTIVE.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether questions may be asked: `--yes` wasn't given and stdin is a terminal.
+pub fn interactive() -> bool {
+    !NON_INTERACTIVE.load(Ordering::Relaxed) && std::io::stdin().is_terminal()
+}
+
+/// All progress bars are drawn through this, so the bars of bundles that are
+/// downloaded at the same time don't overwrite each other.
+static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+pub fn progress() -> &'static MultiProgress {
+    &PROGRESS
+}
+
+thread_local! {
+    static STATUS_PREFIX: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Start the status messages of this thread with `[prefix]`, e.g. the key of
+/// the bundle it downloads while other threads download other bundles.
+pub fn set_status_prefix(prefix: Option<String>) {
+    STATUS_PREFIX.with(|p| *p.borrow_mut() = prefix);
+}
+
+/// `line` with the status prefix of this thread, if it has one.
+pub fn with_status_prefix(line: String) -> String {
+    STATUS_PREFIX.with(|p| match &*p.borrow() {
+        Some(prefix) if !line.is_empty() => format!("[{}] {}", prefix, line),
+        _ => line,
+    })
+}
+
+/// Print a status message of `status!` above the progress bars.
+pub fn print_status(args: std::fmt::Arguments) {
+    let line = with_status_prefix(args.to_string());
+    PROGRESS.suspend(|| {
+        if status_to_stderr() {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    });
+}
 
+/// One runtime for all downloads, so the connections of a shared client stay
+/// usable, also when several threads download at once.
+static RUNTIME: LazyLock<tokio::runtime::Runtime> =
+    LazyLock::new(|| tokio::runtime::Runtime::new().unwrap());
+
+/// Run a future to completion on the shared runtime. This also works from
+/// inside a runtime's worker thread, where `block_on` alone would panic.
 pub fn run_future<F, T>(input: F) -> T
 where
     F: Future<Output = T>,
 {
-    let runtime = tokio::runtime::Runtime::new().unwrap();
-    runtime.block_on(input)
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(input)),
+        Err(_) => RUNTIME.block_on(input),
+    }
 }
 
 pub fn humanize_bytes(bytes: u64) -> String {
     let b = Byte::from_u64(bytes).get_appropriate_unit(UnitType::Binary);
-    return format!("{b:.2}");
+    format!("{b:.2}")
 }
 
 // Convert a string representing a byte size (e.g. 12MB) to a number.
-// It supports the IEC (KiB MiB ...) and KB MB ... formats.
+// It supports the IEC (KiB MiB ...) and KB MB ... formats, plain byte counts,
+// and spaces between the number and the unit. `unlimited` and `none` are
+// the same as `0`, which means no limit.
 pub fn byte_string_to_number(byte_string: &str) -> Option<u64> {
+    let byte_string = byte_string.trim();
+    if ["unlimited", "none"]
+        .iter()
+        .any(|s| byte_string.eq_ignore_ascii_case(s))
+    {
+        return Some(0);
+    }
+
+    let byte_string: String = byte_string.split_whitespace().collect();
+    if let Ok(bytes) = byte_string.parse::<u64>() {
+        return Some(bytes);
+    }
     Byte::parse_str(byte_string, true).map(|b| b.into()).ok()
 }
 
@@ -53,6 +157,317 @@ pub fn extract_filename_from_url(url: &str) -> Option<String> {
     }
 }
 
+/// Edit distance between two strings, counting insertions, deletions,
+/// substitutions and swaps of two adjacent characters as one edit each.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // d[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// Return the cover file name for an image URL, keeping the URL's extension.
+pub fn cover_filename(url: &str) -> String {
+    let extension = extract_filename_from_url(url)
+        .and_then(|name| {
+            let (_, ext) = name.rsplit_once('.')?;
+            Some(ext.to_lowercase())
+        })
+        .filter(|ext| ["jpg", "jpeg", "png", "gif", "webp"].contains(&ext.as_str()))
+        .unwrap_or_else(|| "jpg".to_owned());
+
+    format!("cover.{}", extension)
+}
+
+/// Extract the file name from the value of a `Content-Disposition` header.
+///
+/// The RFC 5987 `filename*` parameter is preferred over the plain `filename`
+/// parameter. The returned name is sanitized so it can be used as-is.
+pub fn filename_from_content_disposition(header: &str) -> Option<String> {
+    let mut plain_name = None;
+    let mut extended_name = None;
+
+    for param in header.split(';').map(str::trim) {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+
+        match key.trim().to_lowercase().as_str() {
+            "filename" => plain_name = Some(value.trim().trim_matches('"').to_owned()),
+            "filename*" => {
+                // Format is: charset'language'percent-encoded-value
+                let encoded = value.trim().splitn(3, '\'').nth(2)?;
+                let decoded = percent_encoding::percent_decode_str(encoded)
+                    .decode_utf8()
+                    .ok()?;
+                extended_name = Some(decoded.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    let name = replace_invalid_chars_in_filename(&extended_name.or(plain_name)?);
+    match name.as_str() {
+        "" | "." | ".." => None,
+        _ => Some(name),
+    }
+}
+
+/// Build a file name from a product name and its format, to be used when the
+/// download URL and the server don't provide one.
+pub fn fallback_filename(product_name: &str, format: &str) -> String {
+    let name = replace_invalid_chars_in_filename(product_name);
+    let format = format.to_lowercase();
+    let extension = format
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .find(|s| !s.is_empty());
+
+    match extension {
+        Some(ext) => format!("{}.{}", name, ext),
+        None => name,
+    }
+}
+
+/// Fill the `{name}` placeholders of a file name template with `fields`.
+/// Values are cleaned up to be valid in file names, and unknown placeholders
+/// are kept as they are.
+pub fn fill_name_template(template: &str, fields: &[(&str, &str)]) -> String {
+    let mut result = template.to_owned();
+    for (name, value) in fields {
+        let value = replace_invalid_chars_in_filename(value);
+        result = result.replace(&format!("{{{}}}", name), &value);
+    }
+    result
+}
+
+/// Parts of a video's name that media servers like Jellyfin and Plex use.
+#[derive(Debug, Default, PartialEq)]
+pub struct MediaName {
+    pub title: String,
+    pub year: Option<u16>,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+}
+
+impl MediaName {
+    /// Name template of the `media-server` preset: `Show/Season 01/Show -
+    /// S01E02.ext` for episodes, and `Movie (2019)/Movie (2019).ext` otherwise.
+    pub fn preset_template(&self) -> &'static str {
+        match (self.episode, self.year) {
+            (Some(_), _) => "{title}/Season {season}/{title} - S{season}E{episode}.{ext}",
+            (None, Some(_)) => "{title} ({year})/{title} ({year}).{ext}",
+            (None, None) => "{title}/{title}.{ext}",
+        }
+    }
+}
+
+/// Find the show, season and episode, or the title and year, in the name of
+/// a video, e.g. "Show - S01E02 - Pilot", "Show Season 1 Episode 2",
+/// "Show Episode 2" or "Movie (2019)". A missing season is season 1.
+pub fn parse_media_name(name: &str) -> MediaName {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let number = |i: usize| -> Option<u32> {
+        let word = words.get(i)?;
+        word.trim_matches(|c: char| !c.is_ascii_digit())
+            .parse()
+            .ok()
+    };
+    let is_word = |i: usize, expected: &[&str]| {
+        words.get(i).is_some_and(|w| {
+            let w = w.trim_matches(|c: char| !c.is_alphanumeric());
+            expected.iter().any(|e| w.eq_ignore_ascii_case(e))
+        })
+    };
+    let title = |end: usize| {
+        words[..end]
+            .join(" ")
+            .trim_end_matches(|c: char| c.is_whitespace() || "-:|,".contains(c))
+            .to_owned()
+    };
+
+    for (i, word) in words.iter().enumerate() {
+        // The show's name comes first, e.g. "4x4 Evo 2" is not an episode.
+        if title(i).is_empty() {
+            continue;
+        }
+        let episode = if let Some((season, episode)) = parse_episode_code(word) {
+            Some((season, episode))
+        } else if is_word(i, &["season"]) && is_word(i + 2, &["episode", "ep"]) {
+            number(i + 1).zip(number(i + 3))
+        } else if is_word(i, &["episode", "ep"]) {
+            number(i + 1).map(|episode| (1, episode))
+        } else {
+            None
+        };
+        if let Some((season, episode)) = episode {
+            return MediaName {
+                title: title(i),
+                season: Some(season),
+                episode: Some(episode),
+                ..Default::default()
+            };
+        }
+    }
+
+    // Only a year in brackets, as in "Blade Runner 2049" it's part of the title
+    let year = words.last().and_then(|w| {
+        let w = w.strip_prefix(['(', '['])?.strip_suffix([')', ']'])?;
+        let year: u16 = w.parse().ok()?;
+        (w.len() == 4 && (1900..2100).contains(&year)).then_some(year)
+    });
+    match year {
+        Some(year) if words.len() > 1 => MediaName {
+            title: title(words.len() - 1),
+            year: Some(year),
+            ..Default::default()
+        },
+        _ => MediaName {
+            title: title(words.len()),
+            ..Default::default()
+        },
+    }
+}
+
+/// Parse an episode code like `S01E02` into season and episode. Codes like
+/// `1x02` are not accepted, as they are too easy to confuse with names like
+/// "Monster Truck 4x4".
+fn parse_episode_code(word: &str) -> Option<(u32, u32)> {
+    let word = word
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase();
+    let (season, episode) = word.strip_prefix('s')?.split_once('e')?;
+    Some((season.parse().ok()?, episode.parse().ok()?))
+}
+
+/// Add `suffix` to a file name, before its extension.
+pub fn filename_with_suffix(filename: &str, suffix: &str) -> String {
+    let suffix = replace_invalid_chars_in_filename(suffix);
+    match filename.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}-{}.{}", stem, suffix, ext),
+        _ => format!("{}-{}", filename, suffix),
+    }
+}
+
+/// `filename`, or the first of `name-1.ext`, `name-2.ext`, ... that doesn't
+/// exist in `dir` yet.
+pub fn unused_filename(dir: &Path, filename: &str) -> String {
+    let mut name = filename.to_owned();
+    let mut n = 1;
+    while dir.join(&name).exists() {
+        name = filename_with_suffix(filename, &n.to_string());
+        n += 1;
+    }
+    name
+}
+
+/// Mask all but the last 4 characters of a secret, so it can be shown in
+/// logs and error messages.
+pub fn redact(secret: &str) -> String {
+    let count = secret.chars().count();
+    let visible = if count > 8 { 4 } else { 0 };
+    let tail: String = secret.chars().skip(count - visible).collect();
+    format!("{}{}", "*".repeat(count - visible), tail)
+}
+
+/// Clean up a session key pasted from a web browser.
+///
+/// Surrounding whitespace and quotes are removed. If a whole cookie or
+/// `Cookie` header was copied, the value of `_simpleauth_sess` is taken from
+/// it. Percent-encoded characters are decoded.
+pub fn clean_session_key(input: &str) -> String {
+    let input = input.trim();
+    let input = match input.get(..7) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("cookie:") => &input[7..],
+        _ => input,
+    };
+    let key = input
+        .split(';')
+        .find_map(|pair| pair.trim().strip_prefix("_simpleauth_sess="))
+        .unwrap_or(input);
+    let key = key.trim_end_matches(';').trim().trim_matches(['"', '\'']);
+    percent_encoding::percent_decode_str(key)
+        .decode_utf8_lossy()
+        .to_string()
+}
+
+/// Case-insensitive match of `text` against a glob `pattern` with `*` and `?`
+/// wildcards. A pattern without wildcards matches anywhere in the text.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    if !pattern.contains(['*', '?']) {
+        return text.contains(&pattern);
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position in the pattern after the last `*`, and the text position it
+    // is currently matched up to. Used to backtrack on a mismatch.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Calculate the MD5 checksum of a file as a lowercase hex string.
+pub fn file_md5(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Md5::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Calculate the SHA-256 checksum of a file as a lowercase hex string.
+pub fn file_sha256(path: &Path) -> Result<String, std::io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Quote a string so it can be used as a single word in a POSIX shell.
+pub fn shell_quote(input: &str) -> String {
+    format!("'{}'", input.replace('\'', "'\\''"))
+}
+
 pub fn str_vectors_intersect<T1, T2>(first: &[T1], second: &[T2]) -> bool
 where
     T1: AsRef<str>,
@@ -84,9 +499,25 @@ where
 /// - A range with beginning and end (1-5): Returns all valus between those two numbers (inclusive).
 /// - A range with no end (10-): In this case, `max_value` specifies the end of the range.
 /// - A range with no beginning (-5): In this case, the range begins at `1`.
+/// - A range with a step (1-20:2): Every `step`th value of the range, starting
+///   at its beginning.
+/// - `all`: The same as `1-`.
 ///
 /// Note: the range starts at `1`, **not** `0`.
 pub fn parse_usize_range(value: &str, max_value: usize) -> Option<Vec<usize>> {
+    if value.eq_ignore_ascii_case("all") {
+        return Some((1..max_value + 1).collect());
+    }
+
+    if let Some((range, step)) = value.split_once(':') {
+        let step = step.parse::<usize>().ok().filter(|&s| s > 0)?;
+        if !range.contains('-') {
+            return None;
+        }
+        let values = parse_usize_range(range, max_value)?;
+        return Some(values.into_iter().step_by(step).collect());
+    }
+
     let dash_idx = value.find('-');
 
     if dash_idx.is_none() {
@@ -121,17 +552,30 @@ pub fn parse_usize_range(value: &str, max_value: usize) -> Option<Vec<usize>> {
     Some((range_left..range_right + 1).collect())
 }
 
+/// Parse several ranges, in the format of `parse_usize_range`, and return
+/// all their values. A range that starts with `!` is excluded from the
+/// others, or from all values if there are only exclusions.
 pub fn union_usize_ranges(values: &[&str], max_value: usize) -> Result<Vec<usize>, anyhow::Error> {
     let mut invalid_values = vec![];
     let mut parsed = HashSet::new();
+    let mut excluded = HashSet::new();
 
     for &v in values {
-        match parse_usize_range(v, max_value) {
-            Some(usize_values) => parsed.extend(usize_values),
+        let (target, range) = match v.strip_prefix('!') {
+            Some(range) => (&mut excluded, range),
+            None => (&mut parsed, v),
+        };
+        match parse_usize_range(range, max_value) {
+            Some(usize_values) => target.extend(usize_values),
             None => invalid_values.push(v),
         }
     }
 
+    if values.iter().all(|v| v.starts_with('!')) {
+        parsed.extend(1..max_value + 1);
+    }
+    parsed.retain(|v| !excluded.contains(v));
+
     if !invalid_values.is_empty() {
         let msg = invalid_values
             .into_iter()
@@ -147,6 +591,21 @@ pub fn union_usize_ranges(values: &[&str], max_value: usize) -> Result<Vec<usize
     Ok(output)
 }
 
+#[test]
+fn test_byte_string_to_number() {
+    assert_eq!(byte_string_to_number("14MB"), Some(14_000_000));
+    assert_eq!(byte_string_to_number("4GiB"), Some(4 * 1024 * 1024 * 1024));
+    assert_eq!(byte_string_to_number("2 GiB"), Some(2 * 1024 * 1024 * 1024));
+    assert_eq!(byte_string_to_number(" 10 kb "), Some(10_000));
+    assert_eq!(byte_string_to_number("1500"), Some(1500));
+    assert_eq!(byte_string_to_number("0"), Some(0));
+    assert_eq!(byte_string_to_number("unlimited"), Some(0));
+    assert_eq!(byte_string_to_number("None"), Some(0));
+    assert_eq!(byte_string_to_number("lots"), None);
+    assert_eq!(byte_string_to_number("-5MB"), None);
+    assert_eq!(byte_string_to_number(""), None);
+}
+
 #[test]
 fn test_remove_invalid_chars() {
     let test_data = vec![
@@ -182,6 +641,104 @@ fn test_extract_filename_from_url() {
     }
 }
 
+#[test]
+fn test_filename_from_content_disposition() {
+    let test_data = vec![
+        ("attachment; filename=\"book.epub\"", Some("book.epub")),
+        ("attachment; filename=book.pdf", Some("book.pdf")),
+        (
+            "attachment; filename=\"fallback.pdf\"; filename*=UTF-8''na%C3%AFve%20book.pdf",
+            Some("naïve book.pdf"),
+        ),
+        (
+            "attachment; filename=\"../../etc/passwd\"",
+            Some(".. .. etc passwd"),
+        ),
+        ("attachment; filename=\"..\"", None),
+        ("inline", None),
+    ];
+
+    for (input, expected) in test_data {
+        assert_eq!(
+            filename_from_content_disposition(input),
+            expected.map(str::to_owned),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_edit_distance() {
+    let test_data = vec![
+        ("python", "python", 0),
+        ("pyhton", "python", 1),
+        ("pythn", "python", 1),
+        ("jav", "java", 1),
+        ("kitten", "sitting", 3),
+        ("", "abc", 3),
+    ];
+
+    for (a, b, expected) in test_data {
+        assert_eq!(edit_distance(a, b), expected, "{} vs {}", a, b);
+    }
+}
+
+#[test]
+fn test_cover_filename() {
+    let test_data = vec![
+        ("https://hb.imgix.net/abc/icon.png?auto=format", "cover.png"),
+        ("https://hb.imgix.net/abc/icon.JPEG", "cover.jpeg"),
+        ("https://hb.imgix.net/abc/icon", "cover.jpg"),
+        ("https://hb.imgix.net/abc/icon.php", "cover.jpg"),
+    ];
+
+    for (input, expected) in test_data {
+        assert_eq!(cover_filename(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_fallback_filename() {
+    assert_eq!(fallback_filename("Some: Book", "EPUB"), "Some  Book.epub");
+    assert_eq!(fallback_filename("Game", "PDF (HQ)"), "Game.pdf");
+    assert_eq!(fallback_filename("Game", "..."), "Game");
+}
+
+#[test]
+fn test_fill_name_template() {
+    let fields = [("product", "Book: Volume 1"), ("filename", "book.epub")];
+    assert_eq!(
+        fill_name_template("{product} - {filename}", &fields),
+        "Book  Volume 1 - book.epub"
+    );
+    assert_eq!(fill_name_template("{other}.pdf", &fields), "{other}.pdf");
+}
+
+#[test]
+fn test_filename_with_suffix() {
+    assert_eq!(
+        filename_with_suffix("soundtrack.zip", "some_game"),
+        "soundtrack-some_game.zip"
+    );
+    assert_eq!(filename_with_suffix("README", "game"), "README-game");
+    assert_eq!(filename_with_suffix(".hidden", "game"), ".hidden-game");
+}
+
+#[test]
+fn test_shell_quote() {
+    let test_data = vec![
+        ("simple", "'simple'"),
+        ("with space", "'with space'"),
+        ("it's", "'it'\\''s'"),
+        ("$HOME `x`", "'$HOME `x`'"),
+    ];
+
+    for (input, expected) in test_data {
+        assert_eq!(shell_quote(input), expected, "input: {}", input);
+    }
+}
+
 #[test]
 /// A test to make sure `humanize_bytes` function works as expected.
 ///
@@ -234,6 +791,16 @@ fn test_parse_usize_range() {
         ("invalid start", "abc-", None),
         ("invalid end", "-abc", None),
         ("invalid start and end", "abc-def", None),
+        ("all", "ALL", Some((1..=50).collect())),
+        ("range with step", "1-10:3", Some(vec![1, 4, 7, 10])),
+        (
+            "range with no end and a step",
+            "44-:2",
+            Some(vec![44, 46, 48, 50]),
+        ),
+        ("step of zero", "1-10:0", None),
+        ("step without a range", "5:2", None),
+        ("invalid step", "1-10:x", None),
     ];
 
     for (name, input, expected) in test_data {
@@ -252,6 +819,14 @@ fn test_union_valid_usize_ranges() {
         ("simple values", vec!["5", "10"], vec![5, 10]),
         ("simple value and range", vec!["8", "7-"], vec![7, 8, 9, 10]),
         ("two ranges", vec!["-3", "7-"], vec![1, 2, 3, 7, 8, 9, 10]),
+        (
+            "range with exclusion",
+            vec!["1-6", "!4"],
+            vec![1, 2, 3, 5, 6],
+        ),
+        ("excluded range", vec!["all", "!2-9"], vec![1, 10]),
+        ("only exclusions", vec!["!1-7", "!9"], vec![8, 10]),
+        ("step and exclusion", vec!["1-10:2", "!5"], vec![1, 3, 7, 9]),
     ];
 
     for (name, input, expected) in test_data {
@@ -273,6 +848,7 @@ fn test_union_invalid_usize_ranges() {
     let test_data = vec![
         ("invalid simple values", vec!["a", "b"]),
         ("invalid ranges", vec!["a-", "-b"]),
+        ("invalid exclusion and step", vec!["!a", "1-5:x"]),
     ];
 
     for (name, input) in test_data {
@@ -295,3 +871,103 @@ fn test_union_invalid_usize_ranges() {
         assert_eq!(output_err_msg, expected_err_msg, "{}", assert_msg);
     }
 }
+
+#[test]
+fn test_clean_session_key() {
+    let test_data = vec![
+        ("abc123", "abc123"),
+        ("  abc123\n", "abc123"),
+        ("\"abc123\"", "abc123"),
+        ("_simpleauth_sess=\"abc123\";", "abc123"),
+        ("abc%3D%3D123", "abc==123"),
+        ("'abc123'", "abc123"),
+        (
+            "Cookie: csrf_cookie=xyz; _simpleauth_sess=\"abc%3D123\"; hbflash=None",
+            "abc=123",
+        ),
+        ("cookie:_simpleauth_sess=abc123", "abc123"),
+    ];
+
+    for (input, expected) in test_data {
+        assert_eq!(clean_session_key(input), expected, "input: {:?}", input);
+    }
+}
+
+#[test]
+fn test_glob_matches() {
+    let test_data = vec![
+        ("book bundle", "Humble Book Bundle: Python", true),
+        ("Book Bundle", "Humble Game Bundle", false),
+        ("humble book*", "Humble Book Bundle: Python", true),
+        ("*python*", "Humble Book Bundle: Python by O'Reilly", true),
+        ("*python", "Humble Book Bundle: Python by O'Reilly", false),
+        ("*: ?ython*", "Humble Book Bundle: Python", true),
+        ("*a*b*", "aXXbYYb", true),
+        ("*", "", true),
+    ];
+
+    for (pattern, text, expected) in test_data {
+        assert_eq!(
+            glob_matches(pattern, text),
+            expected,
+            "pattern: {}, text: {}",
+            pattern,
+            text
+        );
+    }
+}
+
+#[test]
+fn test_redact() {
+    assert_eq!(redact("abcdefghijkl"), "********ijkl");
+    assert_eq!(redact("short"), "*****");
+    assert_eq!(redact(""), "");
+}
+
+#[test]
+fn test_parse_media_name() {
+    let episode = |title: &str, season, episode| MediaName {
+        title: title.to_owned(),
+        season: Some(season),
+        episode: Some(episode),
+        ..Default::default()
+    };
+    assert_eq!(
+        parse_media_name("Great Show - S01E02 - Pilot"),
+        episode("Great Show", 1, 2)
+    );
+    assert_eq!(
+        parse_media_name("Great Show s2e10"),
+        episode("Great Show", 2, 10)
+    );
+    assert_eq!(
+        parse_media_name("Great Show: Season 3 Episode 4"),
+        episode("Great Show", 3, 4)
+    );
+    assert_eq!(
+        parse_media_name("Great Show - Episode 5"),
+        episode("Great Show", 1, 5)
+    );
+
+    assert_eq!(
+        parse_media_name("A Movie (2019)"),
+        MediaName {
+            title: "A Movie".to_owned(),
+            year: Some(2019),
+            ..Default::default()
+        }
+    );
+    assert_eq!(parse_media_name("Blade Runner 2049").year, None);
+    assert_eq!(parse_media_name("(1917)").title, "(1917)");
+    assert_eq!(parse_media_name("Seven Seas").episode, None);
+    assert_eq!(
+        parse_media_name("4x4 Evo 2"),
+        MediaName {
+            title: "4x4 Evo 2".to_owned(),
+            ..Default::default()
+        }
+    );
+    assert_eq!(parse_media_name("Monster Truck 4x4 Rally").episode, None);
+    assert_eq!(parse_media_name("S01E02").episode, None);
+    assert_eq!(parse_media_name("Episode 2").episode, None);
+}
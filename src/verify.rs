@@ -0,0 +1,89 @@
+use crate::{history, util};
+use anyhow::anyhow;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::thread;
+
+/// Check the latest download of each file in the history. Results are
+/// printed as each file is done.
+pub fn verify_downloads(bundle: Option<&str>, jobs: usize) -> Result<(), anyhow::Error> {
+    // The latest successful download of each path, for files that still exist
+    // and have an MD5 to compare with.
+    let mut by_path = BTreeMap::new();
+    for e in history::load()? {
+        if e.error.is_none() && bundle.is_none_or(|b| e.matches_bundle(b)) {
+            by_path.insert(e.path.clone(), e);
+        }
+    }
+    let (missing, entries): (Vec<_>, Vec<_>) = by_path
+        .into_values()
+        .filter(|e| !e.md5.is_empty() || e.sha256.is_some())
+        .partition(|e| !e.path.exists());
+
+    for e in &missing {
+        println!("MISSING  {}", e.path.display());
+    }
+    if entries.is_empty() {
+        println!("No downloaded files to verify.");
+        return Ok(());
+    }
+
+    let total_size = entries.iter().map(|e| e.file_size).sum();
+    let pb = indicatif::ProgressBar::new(total_size);
+    pb.set_style(
+        indicatif::ProgressStyle::default_bar()
+            .template(
+                "  {spinner:.green} [{elapsed}] [{bar}] {bytes} / {total_bytes} ({bytes_per_sec})",
+            )
+            .expect("failed to parse progressbar template")
+            .progress_chars("=> "),
+    );
+
+    let queue = Mutex::new(entries.iter());
+    let mismatched = Mutex::new(0);
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                let Some(e) = queue.lock().unwrap().next() else {
+                    break;
+                };
+
+                // The local SHA-256 is preferred, as MD5 is a broken hash.
+                let checksum = match &e.sha256 {
+                    Some(sha256) => util::file_sha256(&e.path).map(|s| (s, sha256)),
+                    None => util::file_md5(&e.path).map(|s| (s, &e.md5)),
+                };
+                let result = match checksum {
+                    Ok((actual, expected)) if actual.eq_ignore_ascii_case(expected) => {
+                        "OK      ".to_owned()
+                    }
+                    Ok(_) => {
+                        *mismatched.lock().unwrap() += 1;
+                        "MISMATCH".to_owned()
+                    }
+                    Err(err) => {
+                        *mismatched.lock().unwrap() += 1;
+                        format!("ERROR ({})", err)
+                    }
+                };
+                pb.suspend(|| println!("{} {}", result, e.path.display()));
+                pb.inc(e.file_size);
+            });
+        }
+    });
+    pb.finish_and_clear();
+
+    let mismatched = mismatched.into_inner().unwrap();
+    println!();
+    println!(
+        "{} file(s) checked, {} bad, {} missing.",
+        entries.len(),
+        mismatched,
+        missing.len()
+    );
+
+    if mismatched + missing.len() > 0 {
+        return Err(anyhow!("some downloaded files are missing or damaged"));
+    }
+    Ok(())
+}
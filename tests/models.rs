@@ -1,3 +1,4 @@
+use chrono::Datelike;
 use humble_cli::prelude::*;
 
 fn new_download_url(web_url: &str) -> DownloadUrl {
@@ -30,6 +31,7 @@ fn get_test_product() -> Product {
         machine_name: "some-book".to_string(),
         human_name: "Some Book".to_string(),
         product_details_url: "".to_string(),
+        icon: None,
         downloads: vec![dl_entry],
     }
 }
@@ -76,12 +78,23 @@ fn choice_period_parses() {
             input: "march-2023",
             is_ok: true,
         },
+        TestData {
+            input: "october-2015",
+            is_ok: true,
+        },
+        TestData {
+            input: "march-2014",
+            is_ok: false,
+        },
         TestData {
             input: "current",
             is_ok: true,
         },
     ];
 
+    let next_year = format!("january-{}", chrono::Local::now().year() + 1);
+    assert!(ChoicePeriod::try_from(next_year.as_str()).is_ok());
+
     for test_data in data {
         let result = ChoicePeriod::try_from(test_data.input);
         assert_eq!(
@@ -95,12 +108,32 @@ fn choice_period_parses() {
     }
 }
 
+#[test]
+fn choice_period_months_between() {
+    let periods: Vec<String> = ChoicePeriod::months_between((2019, 11), (2020, 2))
+        .iter()
+        .map(|p| p.to_string())
+        .collect();
+    assert_eq!(
+        periods,
+        vec![
+            "november-2019",
+            "december-2019",
+            "january-2020",
+            "february-2020"
+        ]
+    );
+
+    assert!(ChoicePeriod::months_between((2020, 2), (2020, 1)).is_empty());
+}
+
 #[test]
 fn product_name_matches() {
     struct TestData {
         name: String,
         keywords: String,
         match_mode: MatchMode,
+        match_kind: MatchKind,
         expected: bool,
     }
 
@@ -109,39 +142,436 @@ fn product_name_matches() {
             name: "Python programming".to_owned(),
             keywords: "python".to_owned(),
             match_mode: MatchMode::Any,
+            match_kind: MatchKind::Prefix,
             expected: true,
         },
         TestData {
             name: "Python programming".to_owned(),
             keywords: "java".to_owned(),
             match_mode: MatchMode::Any,
+            match_kind: MatchKind::Prefix,
             expected: false,
         },
         TestData {
             name: "Programming in Rust second edition".to_owned(),
             keywords: "rust edition".to_owned(),
             match_mode: MatchMode::All,
+            match_kind: MatchKind::Prefix,
             expected: true,
         },
         TestData {
             name: "Programming in Rust second edition".to_owned(),
             keywords: "rust third".to_owned(),
             match_mode: MatchMode::All,
+            match_kind: MatchKind::Prefix,
             expected: false,
         },
+        TestData {
+            name: "Python programming".to_owned(),
+            keywords: "prog".to_owned(),
+            match_mode: MatchMode::Any,
+            match_kind: MatchKind::Prefix,
+            expected: true,
+        },
+        TestData {
+            name: "Python programming".to_owned(),
+            keywords: "gram".to_owned(),
+            match_mode: MatchMode::Any,
+            match_kind: MatchKind::Prefix,
+            expected: false,
+        },
+        TestData {
+            name: "Python programming".to_owned(),
+            keywords: "gram".to_owned(),
+            match_mode: MatchMode::Any,
+            match_kind: MatchKind::Contains,
+            expected: true,
+        },
+        TestData {
+            name: "Rust: The (Complete) Guide".to_owned(),
+            keywords: "rust complete".to_owned(),
+            match_mode: MatchMode::All,
+            match_kind: MatchKind::Prefix,
+            expected: true,
+        },
+        TestData {
+            name: "Modern C++ Cookbook".to_owned(),
+            keywords: "c++".to_owned(),
+            match_mode: MatchMode::Any,
+            match_kind: MatchKind::Prefix,
+            expected: true,
+        },
     ];
 
     for td in test_data {
-        let mut product = Product::default();
-        product.human_name = td.name.clone();
+        let product = Product {
+            human_name: td.name.clone(),
+            ..Default::default()
+        };
 
         let keywords = td.keywords.to_lowercase();
         let keywords: Vec<&str> = keywords.split(" ").collect();
-        let got = product.name_matches(&keywords, &td.match_mode);
+        let got = product.name_matches(&keywords, &td.match_mode, &td.match_kind);
         assert_eq!(
             got, td.expected,
-            "expected {}, got {}, name = {}, keywords = {}, mode = {:?}",
-            td.expected, got, td.name, td.keywords, &td.match_mode,
+            "expected {}, got {}, name = {}, keywords = {}, mode = {:?}, kind = {:?}",
+            td.expected, got, td.name, td.keywords, &td.match_mode, &td.match_kind,
         )
     }
 }
+
+#[test]
+fn product_fuzzy_score() {
+    let product = Product {
+        human_name: "Python Programming for Beginners".to_owned(),
+        ..Default::default()
+    };
+
+    let test_data = vec![
+        (vec!["python"], MatchMode::Any, true),
+        (vec!["pyhton"], MatchMode::Any, true),
+        (vec!["progrmming"], MatchMode::Any, true),
+        (vec!["pyhton", "java"], MatchMode::Any, true),
+        (vec!["pyhton", "java"], MatchMode::All, false),
+        (vec!["java"], MatchMode::Any, false),
+    ];
+
+    for (keywords, mode, expected) in test_data {
+        let got = product.fuzzy_score(&keywords, &mode);
+        assert_eq!(
+            got.is_some(),
+            expected,
+            "keywords = {:?}, mode = {:?}, score = {:?}",
+            keywords,
+            mode,
+            got
+        );
+    }
+
+    let exact = product.fuzzy_score(&["python"], &MatchMode::Any).unwrap();
+    let typo = product.fuzzy_score(&["pyhton"], &MatchMode::Any).unwrap();
+    assert!(exact > typo, "exact: {}, typo: {}", exact, typo);
+}
+
+#[test]
+fn bundle_details_match_score() {
+    let details = BundleDetails {
+        machine_name: "humblebookbundle_rust_oreilly".to_owned(),
+        human_name: "Humble Book Bundle: Programming by O'Reilly".to_owned(),
+        category: Some("bundle".to_owned()),
+    };
+
+    let test_data = vec![
+        (vec!["programming"], true),
+        (vec!["rust"], true),
+        (vec!["python"], false),
+    ];
+
+    for (keywords, expected) in test_data {
+        let got = details.match_score(&keywords, &MatchMode::All, &MatchKind::Prefix);
+        assert_eq!(got.is_some(), expected, "keywords = {:?}", keywords);
+    }
+}
+
+#[test]
+fn tpkd_dict_tolerates_missing_fields() {
+    let json = r#"{
+        "all_tpks": [
+            {"machine_name": "game_steam", "human_name": "Game", "key_type": "steam", "redeemed_key_val": "AAAA-BBBB"},
+            {"human_name": null, "redeemed_key_val": null},
+            {"human_name": 42}
+        ]
+    }"#;
+
+    let dict: TpkdDict = serde_json::from_str(json).unwrap();
+    assert_eq!(dict.all_tpks.len(), 2);
+
+    assert_eq!(dict.all_tpks[0].human_name, "Game");
+    assert_eq!(dict.all_tpks[0].key_type.as_deref(), Some("steam"));
+    assert!(dict.all_tpks[0].is_redeemed());
+
+    assert_eq!(dict.all_tpks[1].human_name, "");
+    assert!(!dict.all_tpks[1].is_redeemed());
+
+    let dict: TpkdDict = serde_json::from_str("{}").unwrap();
+    assert!(dict.all_tpks.is_empty());
+}
+
+#[test]
+fn store_purchase_without_subproducts() {
+    let json = r#"{
+        "gamekey": "abc123",
+        "created": "2023-05-01T10:00:00.000000",
+        "product": {"machine_name": "some_game_storefront", "human_name": null, "category": "storefront"}
+    }"#;
+
+    let bundle: Bundle = serde_json::from_str(json).unwrap();
+    assert!(bundle.is_store_purchase());
+    assert!(bundle.products.is_empty());
+    assert!(bundle.product_keys().is_empty());
+    assert_eq!(bundle.details.human_name, "");
+}
+
+#[test]
+fn bundle_amounts() {
+    let json = r#"{
+        "gamekey": "abc123",
+        "created": "2023-05-01T10:00:00.000000",
+        "amount_spent": 12,
+        "total": 15.5,
+        "currency": "USD",
+        "payee": "not an object",
+        "product": {"machine_name": "some_bundle", "human_name": "Some Bundle"}
+    }"#;
+
+    let bundle: Bundle = serde_json::from_str(json).unwrap();
+    assert_eq!(bundle.amount_spent, Some(12.0));
+    assert_eq!(bundle.amount_to_charge, None);
+    assert!(bundle.payee.is_none());
+    assert_eq!(bundle.format_amount(bundle.total.unwrap()), "15.50 USD");
+}
+
+#[test]
+fn bundle_serialization_round_trip() {
+    let json = r#"{
+        "gamekey": "abc123",
+        "created": "2023-05-01T10:00:00",
+        "product": {"machine_name": "some_bundle", "human_name": "Some Bundle"},
+        "subproducts": [{
+            "machine_name": "some-book",
+            "human_name": "Some Book",
+            "url": "",
+            "downloads": [{"download_struct": [
+                {"md5": "", "name": "EPUB", "file_size": 1000, "url": {"web": "http://foo.com/one", "bittorrent": ""}}
+            ]}]
+        }],
+        "tpkd_dict": {"all_tpks": [{"human_name": "Game", "redeemed_key_val": "AAAA"}]}
+    }"#;
+
+    let bundle: Bundle = serde_json::from_str(json).unwrap();
+    let value = serde_json::to_value(&bundle).unwrap();
+    assert_eq!(value["product"]["human_name"], "Some Bundle");
+    assert_eq!(
+        value["subproducts"][0]["downloads"][0]["download_struct"][0]["name"],
+        "EPUB"
+    );
+
+    let bundle: Bundle = serde_json::from_value(value).unwrap();
+    assert_eq!(bundle.products[0].total_size(), 1000);
+    assert_eq!(bundle.claim_status(), ClaimStatus::Yes);
+}
+
+#[test]
+fn bundle_key_counts() {
+    let mut bundle: Bundle = serde_json::from_str(
+        r#"{
+            "gamekey": "abc123",
+            "created": "2023-05-01T10:00:00",
+            "product": {"machine_name": "x", "human_name": "X"},
+            "tpkd_dict": {"all_tpks": [
+                {"human_name": "Game 1", "redeemed_key_val": "AAAA"},
+                {"human_name": "Game 2"},
+                {"human_name": "Game 3"}
+            ]}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(bundle.key_counts(), "1/3");
+
+    bundle.tpkd_dict.all_tpks.clear();
+    assert_eq!(bundle.key_counts(), "");
+}
+
+#[test]
+fn tpkd_unavailable_reason() {
+    let tpkd = |json: &str| serde_json::from_str::<Tpkd>(json).unwrap();
+
+    let open = tpkd(r#"{"gamekey": "abc", "human_name": "Game", "redeemed_key_val": null}"#);
+    assert_eq!(open.unavailable_reason(), None);
+
+    let expired = tpkd(r#"{"gamekey": "abc", "human_name": "Game", "is_expired": true}"#);
+    assert_eq!(expired.unavailable_reason(), Some("expired"));
+
+    let sold_out = tpkd(r#"{"human_name": "Game", "sold_out": true, "is_expired": null}"#);
+    assert_eq!(sold_out.unavailable_reason(), Some("sold out"));
+
+    let claimed = tpkd(
+        r#"{"gamekey": "abc", "human_name": "Game", "redeemed_key_val": "A", "sold_out": true}"#,
+    );
+    assert_eq!(claimed.unavailable_reason(), None);
+}
+
+#[test]
+fn content_choice_plan_choose_all() {
+    let options: ContentChoiceOptions = serde_json::from_str(
+        r#"{
+            "contentChoiceData": {"game_data": {
+                "a": {"title": "A", "tpkds": []},
+                "b": {"title": "B", "tpkds": [{"human_name": "B", "is_expired": true}]},
+                "c": {"title": "C", "tpkds": []},
+                "d": {"title": "D", "tpkds": []},
+                "e": {"title": "E", "tpkds": []}
+            }},
+            "gamekey": "abc123",
+            "isActiveContent": true,
+            "title": "Humble Choice",
+            "MAX_CHOICES": 3,
+            "contentChoicesMade": {"initial": {"choices_made": ["a"]}}
+        }"#,
+    )
+    .unwrap();
+
+    let plan = options.plan_choose_all();
+    let chosen: Vec<&str> = plan.choose.iter().map(|&(id, _)| id).collect();
+    assert_eq!(chosen, vec!["c", "d"]);
+    let skipped: Vec<(&str, &str)> = plan
+        .skip
+        .iter()
+        .map(|(g, reason)| (g.title.as_str(), *reason))
+        .collect();
+    assert_eq!(
+        skipped,
+        vec![
+            ("A", "already chosen"),
+            ("B", "expired"),
+            ("E", "choice limit reached")
+        ]
+    );
+}
+
+#[test]
+fn bundle_is_gift() {
+    let json = |extra: &str| {
+        format!(
+            r#"{{"gamekey": "abc123", "created": "2023-05-01T10:00:00",
+                "product": {{"machine_name": "x", "human_name": "X"}}{}}}"#,
+            extra
+        )
+    };
+
+    let bundle: Bundle = serde_json::from_str(&json("")).unwrap();
+    assert!(!bundle.is_gift);
+    let bundle: Bundle = serde_json::from_str(&json(r#", "is_gift": true"#)).unwrap();
+    assert!(bundle.is_gift);
+    let bundle: Bundle = serde_json::from_str(&json(r#", "is_gift": null"#)).unwrap();
+    assert!(!bundle.is_gift);
+}
+
+#[test]
+fn bundle_is_locked() {
+    let mut bundle: Bundle = serde_json::from_str(
+        r#"{
+            "gamekey": "abc123",
+            "created": "2023-05-01T10:00:00",
+            "product": {"machine_name": "may_2023_choice", "human_name": "Humble Choice May 2023", "category": "subscriptioncontent"}
+        }"#,
+    )
+    .unwrap();
+    assert!(bundle.is_locked());
+
+    bundle.tpkd_dict.all_tpks.push(Tpk::default());
+    assert!(!bundle.is_locked());
+
+    bundle.tpkd_dict.all_tpks.clear();
+    bundle.details.category = Some("bundle".to_owned());
+    bundle.details.human_name = "Humble Book Bundle".to_owned();
+    assert!(!bundle.is_locked());
+}
+
+#[test]
+fn download_url_torrent() {
+    let url: DownloadUrl = serde_json::from_str(
+        r#"{"web": "http://foo.com/a.pdf", "bittorrent": "http://foo.com/a.pdf.torrent"}"#,
+    )
+    .unwrap();
+    assert_eq!(url.torrent(), Some("http://foo.com/a.pdf.torrent"));
+
+    for json in [
+        r#"{"web": "http://foo.com/a.pdf", "bittorrent": ""}"#,
+        r#"{"web": "http://foo.com/a.pdf", "bittorrent": null}"#,
+        r#"{"web": "http://foo.com/a.pdf"}"#,
+    ] {
+        let url: DownloadUrl = serde_json::from_str(json).unwrap();
+        assert_eq!(url.torrent(), None, "{}", json);
+    }
+}
+
+#[test]
+fn tpk_redeem_url() {
+    let mut tpk = Tpk {
+        key_type: Some("steam".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(tpk.redeem_url(), HUMBLE_KEYS_URL);
+
+    tpk.redeemed_key_val = Some("AAAA-BBBB-CCCC".to_owned());
+    assert_eq!(
+        tpk.redeem_url(),
+        "https://store.steampowered.com/account/registerkey?key=AAAA-BBBB-CCCC"
+    );
+
+    tpk.key_type = Some("gog".to_owned());
+    assert_eq!(
+        tpk.redeem_url(),
+        "https://www.gog.com/redeem/AAAA-BBBB-CCCC"
+    );
+
+    tpk.key_type = Some("origin".to_owned());
+    assert_eq!(tpk.redeem_url(), HUMBLE_KEYS_URL);
+}
+
+#[test]
+fn bundle_details_series() {
+    let series = |name: &str| {
+        let details = BundleDetails {
+            machine_name: "".to_owned(),
+            human_name: name.to_owned(),
+            category: None,
+        };
+        details.series().map(str::to_owned)
+    };
+
+    assert_eq!(
+        series("Humble Book Bundle: Python by O'Reilly").as_deref(),
+        Some("Humble Book Bundle")
+    );
+    assert_eq!(
+        series("March 2023 Humble Choice").as_deref(),
+        Some("Humble Choice")
+    );
+    assert_eq!(series("Some Game"), None);
+}
+
+#[test]
+fn bundle_is_membership() {
+    let bundle = |name: &str, category: &str| {
+        let json = format!(
+            r#"{{
+                "gamekey": "abc123",
+                "created": "2023-05-01T10:00:00",
+                "product": {{"machine_name": "x", "human_name": "{}", "category": "{}"}}
+            }}"#,
+            name, category
+        );
+        serde_json::from_str::<Bundle>(&json).unwrap()
+    };
+
+    assert!(bundle("March 2023 Humble Choice", "subscriptioncontent").is_membership());
+    assert!(bundle("Humble Monthly: May 2019", "bundle").is_membership());
+    assert!(!bundle("Humble Book Bundle: Python", "bundle").is_membership());
+}
+
+#[test]
+fn download_order_sort() {
+    let sizes = [30, 10, 20, 10];
+    let sorted = |order: DownloadOrder| {
+        let mut items: Vec<(usize, u64)> = sizes.iter().copied().enumerate().collect();
+        order.sort(&mut items, |i| i.1);
+        items.iter().map(|i| i.0).collect::<Vec<_>>()
+    };
+
+    assert_eq!(sorted(DownloadOrder::AsListed), vec![0, 1, 2, 3]);
+    assert_eq!(sorted(DownloadOrder::SmallestFirst), vec![1, 3, 2, 0]);
+    assert_eq!(sorted(DownloadOrder::LargestFirst), vec![0, 2, 1, 3]);
+    assert!(DownloadOrder::try_from("random").is_err());
+}